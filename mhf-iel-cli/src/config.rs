@@ -0,0 +1,444 @@
+use mhf_iel::policy::POLICY_ENV;
+use mhf_iel::MhfConfig;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{fs::File, io::Read, mem::ManuallyDrop, path::Path};
+
+use crate::output::{self, ExitCode};
+use crate::profiles::PROFILES_ENV;
+use crate::CliConfig;
+
+/// Environment variable holding the JSON config data, used when no other source is specified.
+pub const CONFIG_ENV: &str = "MHF_IEL_CONFIG";
+/// Prefix of the environment variables overriding single fields, such as `MHF_IEL_SERVER_HOST`.
+pub const FIELD_ENV_PREFIX: &str = "MHF_IEL_";
+
+// Fields that stay strings when overridden, even if they look like a number
+const STRING_FIELDS: &[&str] = &[
+    "char_name",
+    "user_token",
+    "user_name",
+    "user_password",
+    "server_host",
+    "mhf_folder",
+    "version",
+    // notices.<n>.data
+    "data",
+];
+const SECRET_FIELDS: &[&str] = &["user_token", "user_password"];
+
+/// Config values by where they came from, from lowest to highest priority. Built-in defaults come
+/// before all of them.
+#[derive(Debug, Default, Serialize)]
+pub struct Layers {
+    pub document_source: Option<String>,
+    pub document: Map<String, Value>,
+    pub env: Map<String, Value>,
+    pub overrides: Map<String, Value>,
+}
+
+// The handle is checked to be open first, since wrapping anything else is undefined behaviour. It
+// stays the caller's, so it's never closed here and goes away with the process
+#[cfg(windows)]
+fn open_handle(handle: u64) -> Option<ManuallyDrop<File>> {
+    use std::os::windows::io::{FromRawHandle, RawHandle};
+    use windows::Win32::Foundation::{GetHandleInformation, HANDLE};
+    let handle = isize::try_from(handle).ok()?;
+    let mut flags = 0;
+    unsafe { GetHandleInformation(HANDLE(handle), &mut flags) }.ok()?;
+    Some(ManuallyDrop::new(unsafe {
+        File::from_raw_handle(handle as RawHandle)
+    }))
+}
+
+#[cfg(unix)]
+fn open_handle(handle: u64) -> Option<ManuallyDrop<File>> {
+    use std::os::unix::io::{FromRawFd, RawFd};
+    let fd = RawFd::try_from(handle).ok()?;
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return None;
+    }
+    Some(ManuallyDrop::new(unsafe { File::from_raw_fd(fd) }))
+}
+
+fn read_handle(handle: u64) -> String {
+    let Some(mut file) = open_handle(handle) else {
+        output::fail(
+            ExitCode::ConfigMissing,
+            format!("handle {handle} isn't open in this process"),
+        )
+    };
+    let mut data = String::new();
+    if let Err(e) = file.read_to_string(&mut data) {
+        output::fail(
+            ExitCode::ConfigMissing,
+            format!("unable to read handle {handle}: {e}"),
+        )
+    }
+    data
+}
+
+fn read_file(path: &Path) -> Option<String> {
+    File::open(path).and_then(std::io::read_to_string).ok()
+}
+
+/// Finds the config document, returning where it came from along with its contents.
+pub fn read_config_document(cli_config: &CliConfig) -> Option<(String, String)> {
+    if let Some(config_data) = &cli_config.config_data {
+        return Some(("--config-data".to_owned(), config_data.clone()));
+    }
+    if cli_config.config_stdin {
+        let data = std::io::read_to_string(std::io::stdin()).ok()?;
+        return Some(("stdin".to_owned(), data));
+    }
+    if let Some(handle) = cli_config.config_handle {
+        return Some((format!("handle {handle}"), read_handle(handle)));
+    }
+    if let Some(config_file) = &cli_config.config_file {
+        let data = read_file(config_file)?;
+        return Some((config_file.display().to_string(), data));
+    }
+    if let Ok(data) = std::env::var(CONFIG_ENV) {
+        return Some((CONFIG_ENV.to_owned(), data));
+    }
+    let config_file = std::env::current_dir().ok()?.join("config.json");
+    let data = read_file(&config_file)?;
+    Some((config_file.display().to_string(), data))
+}
+
+/// Parses a JSON, TOML or YAML document. The file extension picks the format when there is one,
+/// otherwise each is tried in that order.
+pub fn parse_document(source: &str, data: &str) -> Result<Map<String, Value>, String> {
+    let extension = Path::new(source)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let json = || serde_json::from_str::<Map<String, Value>>(data).map_err(|e| e.to_string());
+    let toml = || toml::from_str::<Map<String, Value>>(data).map_err(|e| e.to_string());
+    let yaml = || serde_yaml::from_str::<Map<String, Value>>(data).map_err(|e| e.to_string());
+    match extension.as_deref() {
+        Some("json") => json(),
+        Some("toml") => toml(),
+        Some("yaml" | "yml") => yaml(),
+        _ => json().or_else(|e| toml().or_else(|_| yaml()).map_err(|_| e)),
+    }
+}
+
+fn parse_value(key: &str, value: &str) -> Value {
+    let field = key.rsplit('.').next().unwrap_or(key);
+    if STRING_FIELDS.contains(&field) {
+        return Value::String(value.to_owned());
+    }
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()))
+}
+
+fn env_layer(vars: impl Iterator<Item = (String, String)>) -> Map<String, Value> {
+    vars
+        // MHF_IEL_CONFIG is the whole document, MHF_IEL_PROFILES and MHF_IEL_POLICY options, not
+        // fields
+        .filter(|(name, _)| name != CONFIG_ENV && name != PROFILES_ENV && name != POLICY_ENV)
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(FIELD_ENV_PREFIX)?.to_ascii_lowercase();
+            let value = parse_value(&key, &value);
+            Some((key, value))
+        })
+        .collect()
+}
+
+pub fn parse_override(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.trim().to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected 'key=value', got '{s}'"))
+}
+
+pub fn try_layers(cli_config: &CliConfig, overrides: Map<String, Value>) -> Result<Layers, String> {
+    let mut layers = Layers {
+        env: env_layer(std::env::vars()),
+        ..Default::default()
+    };
+    if let Some((source, data)) = read_config_document(cli_config) {
+        layers.document = parse_document(&source, &data)?;
+        layers.document_source = Some(source);
+    }
+    layers.overrides = cli_config
+        .set
+        .iter()
+        .map(|(key, value)| (key.clone(), parse_value(key, value)))
+        .chain(overrides)
+        .collect();
+    Ok(layers)
+}
+
+pub fn layers(cli_config: &CliConfig, overrides: Map<String, Value>) -> Layers {
+    try_layers(cli_config, overrides).unwrap_or_else(|e| {
+        output::fail(
+            ExitCode::ConfigParse,
+            format!("error parsing config data: {}", e),
+        )
+    })
+}
+
+impl Layers {
+    pub fn is_empty(&self) -> bool {
+        self.document_source.is_none() && self.env.is_empty() && self.overrides.is_empty()
+    }
+
+    pub fn merge(&self) -> Map<String, Value> {
+        let mut merged = self.document.clone();
+        merged.extend(self.env.clone());
+        for (key, value) in &self.overrides {
+            set_path(&mut merged, key, value.clone());
+        }
+        merged
+    }
+
+    pub fn redacted(mut self) -> Self {
+        for layer in [&mut self.document, &mut self.env, &mut self.overrides] {
+            redact(layer);
+        }
+        self
+    }
+}
+
+// Dotted keys reach into objects and lists, such as 'notices.0.data'. Missing objects are created,
+// and an index one past the end of a list appends to it
+fn set_path(config: &mut Map<String, Value>, key: &str, value: Value) {
+    let Some((first, rest)) = key.split_once('.') else {
+        config.insert(key.to_owned(), value);
+        return;
+    };
+    let mut target = config.entry(first).or_insert(Value::Null);
+    for part in rest.split('.') {
+        let index = match (&*target, part.parse::<usize>()) {
+            (Value::Array(items), Ok(i)) if i <= items.len() => Some(i),
+            _ => None,
+        };
+        target = match (target, index) {
+            (Value::Array(items), Some(i)) => {
+                if i == items.len() {
+                    items.push(Value::Null);
+                }
+                &mut items[i]
+            }
+            (target, _) => {
+                if !target.is_object() {
+                    *target = Value::Object(Map::new());
+                }
+                target
+                    .as_object_mut()
+                    .expect("just made an object")
+                    .entry(part)
+                    .or_insert(Value::Null)
+            }
+        };
+    }
+    *target = value;
+}
+
+pub fn redact(config: &mut Map<String, Value>) {
+    for field in SECRET_FIELDS {
+        if let Some(value) = config.get_mut(*field) {
+            if value.as_str().is_none_or(|v| !v.is_empty()) {
+                *value = Value::String("<redacted>".to_owned());
+            }
+        }
+    }
+}
+
+pub fn from_layers(layers: &Layers) -> MhfConfig {
+    serde_json::from_value(Value::Object(layers.merge())).unwrap_or_else(|e| {
+        output::fail(
+            ExitCode::ConfigParse,
+            format!("error parsing config data: {}", e),
+        )
+    })
+}
+
+pub fn load_config(cli_config: &CliConfig) -> MhfConfig {
+    let layers = layers(cli_config, Map::new());
+    if layers.is_empty() {
+        output::fail(
+            ExitCode::ConfigMissing,
+            "unable to locate 'config.json' file",
+        );
+    }
+    from_layers(&layers)
+}
+
+/// Anything in argv can be read by every other process on the machine.
+pub fn warn_argv_secrets(cli_config: &CliConfig) {
+    let sources = [
+        ("--config-data", cli_config.config_data.is_some()),
+        ("--password", cli_config.password.is_some()),
+        ("the positional token", cli_config.user_token.is_some()),
+        (
+            "--set",
+            cli_config
+                .set
+                .iter()
+                .any(|(key, _)| SECRET_FIELDS.contains(&key.as_str())),
+        ),
+    ];
+    for (source, used) in sources {
+        if used {
+            output::log(format!(
+                "warning: secrets passed through {source} can be read by other processes, use '--config-stdin', '--config-handle' or '{CONFIG_ENV}' instead"
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use serde_json::json;
+
+    fn cli_config(args: &[&str]) -> CliConfig {
+        CliConfig::try_parse_from(["mhf-iel-cli"].iter().chain(args)).unwrap()
+    }
+
+    const TOKEN: &str = r#""user_token":"0123456789abcdef""#;
+
+    #[test]
+    fn precedence() {
+        let document = format!(
+            r#"{{"char_id":1,{TOKEN},"char_name":"Document","server_host":"10.0.0.1","server_port":1}}"#
+        );
+        let cli_config = cli_config(&[
+            "--config-data",
+            &document,
+            "--set",
+            "server_port=3",
+            "--set",
+            "char_name=007",
+        ]);
+        let mut layers = try_layers(&cli_config, Map::new()).unwrap();
+        layers.env = env_layer(
+            [
+                ("MHF_IEL_SERVER_HOST", "10.0.0.2"),
+                ("MHF_IEL_SERVER_PORT", "2"),
+                ("MHF_IEL_CONFIG", "{}"),
+                ("MHF_IEL_POLICY", "{}"),
+                ("OTHER_SERVER_HOST", "10.0.0.4"),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value.to_owned())),
+        );
+
+        assert_eq!(layers.document_source.as_deref(), Some("--config-data"));
+        assert_eq!(layers.env["server_host"], "10.0.0.2");
+        assert_eq!(layers.env["server_port"], 2);
+        assert_eq!(layers.env.len(), 2);
+        let config = from_layers(&layers);
+        assert_eq!(config.char_id, 1);
+        assert_eq!(config.server_host, "10.0.0.2");
+        assert_eq!(config.server_port, 3);
+        // Stays a string, though it looks like a number
+        assert_eq!(config.char_name, "007");
+
+        // Overrides from the caller come after '--set'
+        layers.overrides.insert("server_port".to_owned(), json!(4));
+        assert_eq!(layers.merge()["server_port"], 4);
+        let overrides = json!({ "server_port": 5 }).as_object().unwrap().clone();
+        let layers = try_layers(&cli_config, overrides).unwrap();
+        assert_eq!(layers.merge()["server_port"], 5);
+    }
+
+    #[test]
+    fn nested_keys() {
+        let document =
+            format!(r#"{{"char_id":1,{TOKEN},"notices":[{{"flags":0,"data":"<BODY>Old"}}]}}"#);
+        let cli_config = cli_config(&[
+            "--config-data",
+            &document,
+            "--set",
+            "notices.0.flags=2",
+            "--set",
+            "notices.1.data=123",
+            "--set",
+            "notices.1.flags=1",
+            "--set",
+            "char_ids=[1,2]",
+        ]);
+        let config = from_layers(&try_layers(&cli_config, Map::new()).unwrap());
+        assert_eq!(config.notices.len(), 2);
+        assert_eq!(config.notices[0].flags, 2);
+        assert_eq!(config.notices[0].data, "<BODY>Old");
+        assert_eq!(config.notices[1].flags, 1);
+        assert_eq!(config.notices[1].data, "123");
+        assert_eq!(config.char_ids, [1, 2]);
+
+        let mut config = Map::new();
+        set_path(&mut config, "a.b.c", json!(1));
+        set_path(&mut config, "a.list", json!([]));
+        set_path(&mut config, "a.list.0", json!("x"));
+        // Past the end isn't an index, so it's a key of an object replacing the list
+        set_path(&mut config, "b", json!([1]));
+        set_path(&mut config, "b.5", json!(2));
+        assert_eq!(
+            Value::Object(config),
+            json!({ "a": { "b": { "c": 1 }, "list": ["x"] }, "b": { "5": 2 } })
+        );
+    }
+
+    #[test]
+    fn formats() {
+        let json = r#"{"char_id": 1, "server_port": 2}"#;
+        let toml = "char_id = 1\nserver_port = 2\n";
+        let yaml = "char_id: 1\nserver_port: 2\n";
+        let expected = json!({ "char_id": 1, "server_port": 2 });
+        // Without an extension, each is tried in turn
+        for (source, data) in [
+            ("stdin", json),
+            ("stdin", toml),
+            ("MHF_IEL_CONFIG", yaml),
+            ("config.json", json),
+            ("config.TOML", toml),
+            ("config.yml", yaml),
+            ("config.yaml", yaml),
+        ] {
+            let document = parse_document(source, data).unwrap();
+            assert_eq!(Value::Object(document), expected, "{source}: {data}");
+        }
+        // The extension picks one
+        assert!(parse_document("config.json", toml).is_err());
+        assert!(parse_document("config.toml", json).is_err());
+        // The JSON error is the one reported
+        let e = parse_document("stdin", "{").unwrap_err();
+        assert!(e.contains("EOF while parsing"), "{e}");
+        assert!(parse_document("stdin", "[1, 2]").is_err());
+    }
+
+    #[test]
+    fn overrides() {
+        assert_eq!(
+            parse_override(" server_host =a=b"),
+            Ok(("server_host".to_owned(), "a=b".to_owned()))
+        );
+        assert!(parse_override("server_host").is_err());
+        assert_eq!(parse_value("char_id", "5"), json!(5));
+        assert_eq!(parse_value("char_ids", "[1]"), json!([1]));
+        assert_eq!(parse_value("user_token", "1234"), json!("1234"));
+        assert_eq!(parse_value("notices.0.data", "1"), json!("1"));
+        assert_eq!(parse_value("mez_stalls", "not json"), json!("not json"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn handles_stay_open() {
+        use std::{io::Write, os::unix::io::AsRawFd};
+        let path = std::env::temp_dir().join(format!("mhf-iel-handle-{}", std::process::id()));
+        File::create(&path).unwrap().write_all(b"{}").unwrap();
+        let file = File::open(&path).unwrap();
+        let fd = file.as_raw_fd() as u64;
+        assert_eq!(read_handle(fd), "{}");
+        // Still the test's to read and close
+        assert!(open_handle(fd).is_some());
+        drop(file);
+        assert!(open_handle(fd).is_none());
+        assert!(open_handle(u64::MAX).is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,624 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use mhf_iel::settings::MhfSettings;
+use mhf_iel::{MhfConfig, MhfVersion};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::config;
+use crate::output::{self, ExitCode, OutputFormat};
+use crate::CliConfig;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub checks: Vec<Check>,
+    pub pass: usize,
+    pub warn: usize,
+    pub fail: usize,
+}
+
+impl Report {
+    fn add(&mut self, name: &'static str, status: Status, message: impl Into<String>) {
+        match status {
+            Status::Pass => self.pass += 1,
+            Status::Warn => self.warn += 1,
+            Status::Fail => self.fail += 1,
+        }
+        self.checks.push(Check {
+            name,
+            status,
+            message: message.into(),
+        });
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for check in &self.checks {
+            let status = match check.status {
+                Status::Pass => "PASS",
+                Status::Warn => "WARN",
+                Status::Fail => "FAIL",
+            };
+            text.push_str(&format!("[{status}] {}: {}\n", check.name, check.message));
+        }
+        text.push_str(&format!(
+            "\n{} passed, {} warnings, {} failed",
+            self.pass, self.warn, self.fail
+        ));
+        text
+    }
+}
+
+// mhfo-hd.dll only ships with ZZ. Without it, it's either F5 or a ZZ install without HD graphics
+fn check_game_folder(report: &mut Report, mhf_folder: &Path) -> bool {
+    if !mhf_folder.is_dir() {
+        report.add(
+            "game folder",
+            Status::Fail,
+            format!("'{}' isn't a folder", mhf_folder.display()),
+        );
+        return false;
+    }
+    report.add(
+        "game folder",
+        Status::Pass,
+        mhf_folder.display().to_string(),
+    );
+    let dlls: Vec<_> = ["mhfo.dll", "mhfo-hd.dll"]
+        .into_iter()
+        .filter(|dll| mhf_folder.join(dll).is_file())
+        .collect();
+    match dlls.is_empty() {
+        true => report.add(
+            "game dlls",
+            Status::Fail,
+            "neither 'mhfo.dll' nor 'mhfo-hd.dll' is in the game folder",
+        ),
+        false => report.add("game dlls", Status::Pass, dlls.join(", ")),
+    }
+    true
+}
+
+fn check_version(report: &mut Report, mhf_folder: &Path, config: Option<&MhfConfig>) {
+    let detected = mhf_iel::detect_version(mhf_folder);
+    let configured = config.map(|c| c.version);
+    let (status, message) = match (detected, configured) {
+        (None, _) => (Status::Warn, "unable to detect the game version".to_owned()),
+        (Some(detected), None) => (Status::Pass, format!("detected {detected}")),
+        (Some(detected), Some(configured)) if detected == configured => {
+            (Status::Pass, format!("detected and configured {detected}"))
+        }
+        (Some(MhfVersion::F5), Some(MhfVersion::ZZ)) => (
+            Status::Warn,
+            "configured ZZ, but 'mhfo-hd.dll' is missing so it looks like F5".to_owned(),
+        ),
+        (Some(detected), Some(configured)) => (
+            Status::Warn,
+            format!("configured {configured}, but the game folder looks like {detected}"),
+        ),
+    };
+    report.add("version", status, message);
+}
+
+#[cfg(windows)]
+fn monitor_size() -> Option<(u32, u32)> {
+    use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN};
+    let (w, h) = unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+    (w > 0 && h > 0).then_some((w as u32, h as u32))
+}
+
+#[cfg(not(windows))]
+fn monitor_size() -> Option<(u32, u32)> {
+    None
+}
+
+fn check_settings(report: &mut Report, mhf_folder: &Path, version: Option<MhfVersion>) {
+    let ini = mhf_folder.join("mhf.ini");
+    if !ini.is_file() {
+        report.add(
+            "mhf.ini",
+            Status::Warn,
+            "missing, the game will use its defaults",
+        );
+    } else {
+        report.add("mhf.ini", Status::Pass, ini.display().to_string());
+    }
+    let settings = MhfSettings::load(mhf_folder);
+    if let Some((monitor_w, monitor_h)) = monitor_size() {
+        for (name, w, h) in [
+            (
+                "window resolution",
+                settings.window_resolution_w,
+                settings.window_resolution_h,
+            ),
+            (
+                "fullscreen resolution",
+                settings.fullscreen_resolution_w,
+                settings.fullscreen_resolution_h,
+            ),
+        ] {
+            match w > monitor_w || h > monitor_h {
+                true => report.add(
+                    name,
+                    Status::Warn,
+                    format!("{w}x{h} is larger than the {monitor_w}x{monitor_h} monitor"),
+                ),
+                false => report.add(name, Status::Pass, format!("{w}x{h}")),
+            }
+        }
+    }
+    // `run` only picks the HD DLL for ZZ
+    if version != Some(MhfVersion::F5) && settings.graphics_ver == 1 {
+        match mhf_folder.join("mhfo-hd.dll").is_file() {
+            true => report.add(
+                "graphics",
+                Status::Pass,
+                "GRAPHICS_VER=1 uses 'mhfo-hd.dll'",
+            ),
+            false => report.add(
+                "graphics",
+                Status::Fail,
+                "GRAPHICS_VER=1 needs 'mhfo-hd.dll', which is missing",
+            ),
+        }
+    } else if !mhf_folder.join("mhfo.dll").is_file() {
+        report.add(
+            "graphics",
+            Status::Fail,
+            format!(
+                "GRAPHICS_VER={} needs 'mhfo.dll', which is missing",
+                settings.graphics_ver
+            ),
+        );
+    } else {
+        report.add(
+            "graphics",
+            Status::Pass,
+            format!("GRAPHICS_VER={} uses 'mhfo.dll'", settings.graphics_ver),
+        );
+    }
+}
+
+fn load_config(report: &mut Report, cli_config: &CliConfig) -> Option<MhfConfig> {
+    let layers = match config::try_layers(cli_config, Map::new()) {
+        Ok(layers) => layers,
+        Err(e) => {
+            report.add("config", Status::Fail, format!("unable to parse: {e}"));
+            return None;
+        }
+    };
+    if layers.is_empty() {
+        report.add("config", Status::Warn, "no config data found");
+        return None;
+    }
+    let source = layers
+        .document_source
+        .clone()
+        .unwrap_or_else(|| "environment and overrides".to_owned());
+    let config: MhfConfig = match serde_json::from_value(Value::Object(layers.merge())) {
+        Ok(config) => config,
+        Err(e) => {
+            report.add("config", Status::Fail, format!("{source}: {e}"));
+            return None;
+        }
+    };
+    match config.validate() {
+        Ok(()) => report.add("config", Status::Pass, format!("{source} is valid")),
+        Err(e) => report.add("config", Status::Fail, format!("{source}: {e}")),
+    }
+    Some(config)
+}
+
+fn check_server(report: &mut Report, config: &MhfConfig) {
+    if let Err(e) = mhf_iel::policy::policy().check(config) {
+        report.add("server", Status::Fail, e.to_string());
+        return;
+    }
+    let address = format!("{}:{}", config.server_host, config.server_port);
+    let reachable = address.to_socket_addrs().is_ok_and(|mut addrs| {
+        addrs.any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok())
+    });
+    match reachable {
+        true => report.add("server", Status::Pass, format!("'{address}' is reachable")),
+        false => report.add(
+            "server",
+            Status::Fail,
+            format!("'{address}' is unreachable"),
+        ),
+    }
+}
+
+#[cfg(windows)]
+fn wine_version() -> Option<String> {
+    use windows::core::s;
+    use windows::Win32::System::LibraryLoader::{GetModuleHandleA, GetProcAddress};
+    // Only Wine's ntdll exports it
+    let ntdll = unsafe { GetModuleHandleA(s!("ntdll.dll")) }.ok()?;
+    let proc = unsafe { GetProcAddress(ntdll, s!("wine_get_version")) }?;
+    let wine_get_version: extern "C" fn() -> *const std::ffi::c_char =
+        unsafe { std::mem::transmute(proc) };
+    let version = unsafe { std::ffi::CStr::from_ptr(wine_get_version()) };
+    Some(version.to_string_lossy().into_owned())
+}
+
+#[cfg(windows)]
+fn check_wine(report: &mut Report) {
+    match wine_version() {
+        Some(version) => report.add(
+            "wine",
+            Status::Pass,
+            format!("running under Wine {version}"),
+        ),
+        None => report.add("wine", Status::Pass, "not running under Wine"),
+    }
+}
+
+#[cfg(not(windows))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[cfg(not(windows))]
+fn check_wine(report: &mut Report) {
+    let wine = std::env::var("WINE").unwrap_or_else(|_| "wine".to_owned());
+    check_wine_program(report, &wine);
+}
+
+#[cfg(not(windows))]
+fn check_wine_program(report: &mut Report, wine: &str) {
+    match command_output(wine, &["--version"]) {
+        Some(version) => report.add("wine", Status::Pass, version),
+        None => report.add(
+            "wine",
+            Status::Warn,
+            format!("'{wine}' not found, it's needed to run the game outside of Windows"),
+        ),
+    }
+}
+
+#[cfg(windows)]
+fn check_locale(report: &mut Report) {
+    use windows::Win32::Globalization::GetACP;
+    let code_page = unsafe { GetACP() };
+    match code_page {
+        932 => report.add("locale", Status::Pass, "code page 932 (Japanese)"),
+        code_page => report.add(
+            "locale",
+            Status::Warn,
+            format!("code page {code_page}, Japanese text may show up garbled"),
+        ),
+    }
+    let fonts = std::env::var_os("WINDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\Windows"))
+        .join("Fonts");
+    match ["msgothic.ttc", "msgothic.ttf"]
+        .iter()
+        .any(|font| fonts.join(font).is_file())
+    {
+        true => report.add("fonts", Status::Pass, "MS Gothic is installed"),
+        false if wine_version().is_some() => report.add(
+            "fonts",
+            Status::Warn,
+            "MS Gothic isn't installed, make sure the prefix replaces it (see 'wine --font')",
+        ),
+        false => report.add("fonts", Status::Warn, "MS Gothic isn't installed"),
+    }
+}
+
+#[cfg(not(windows))]
+fn check_locale(report: &mut Report) {
+    let locales = command_output("locale", &["-a"]).unwrap_or_default();
+    let fonts = command_output("fc-list", &[":lang=ja", "family"]).unwrap_or_default();
+    check_locale_output(report, &locales, &fonts);
+}
+
+// Takes the output of `locale -a` and `fc-list :lang=ja family`
+#[cfg(not(windows))]
+fn check_locale_output(report: &mut Report, locales: &str, fonts: &str) {
+    let japanese = locales.lines().find(|l| {
+        let l = l.to_ascii_lowercase();
+        l == "ja_jp.utf8" || l == "ja_jp.utf-8"
+    });
+    match japanese {
+        Some(locale) => report.add("locale", Status::Pass, format!("{locale} is available")),
+        None => report.add(
+            "locale",
+            Status::Warn,
+            "ja_JP.UTF-8 isn't available, Japanese text may show up garbled",
+        ),
+    }
+    match fonts.lines().next() {
+        Some(font) => report.add(
+            "fonts",
+            Status::Pass,
+            format!("found Japanese fonts ({font})"),
+        ),
+        None => report.add(
+            "fonts",
+            Status::Warn,
+            "no Japanese fonts found, install one such as Noto Sans CJK JP",
+        ),
+    }
+}
+
+/// Runs every check, exiting with `ExitCode::Doctor` if any of them failed.
+pub fn doctor(cli_config: &CliConfig, mhf_folder: Option<PathBuf>) -> ! {
+    let mut report = Report::default();
+    let config = load_config(&mut report, cli_config);
+    let mhf_folder = mhf_folder
+        .or_else(|| config.as_ref().and_then(|c| c.mhf_folder.clone()))
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+    // Everything about the game files would fail the same way without the folder
+    if check_game_folder(&mut report, &mhf_folder) {
+        check_version(&mut report, &mhf_folder, config.as_ref());
+        let version = config
+            .as_ref()
+            .map(|c| c.version)
+            .or_else(|| mhf_iel::detect_version(&mhf_folder));
+        check_settings(&mut report, &mhf_folder, version);
+    }
+    if let Some(config) = &config {
+        check_server(&mut report, config);
+    }
+    check_wine(&mut report);
+    check_locale(&mut report);
+    let code = match report.fail {
+        0 => ExitCode::Success,
+        _ => ExitCode::Doctor,
+    };
+    match cli_config.output {
+        OutputFormat::Text => output::success(code, report.text()),
+        OutputFormat::Json => output::success(code, report),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::net::TcpListener;
+
+    use clap::Parser;
+
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("mhf-iel-doctor-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn with(self, files: &[(&str, &str)]) -> Self {
+            for (name, contents) in files {
+                fs::write(self.0.join(name), contents).unwrap();
+            }
+            self
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn statuses(report: &Report) -> Vec<(&'static str, Status)> {
+        report.checks.iter().map(|c| (c.name, c.status)).collect()
+    }
+
+    fn config(version: MhfVersion) -> MhfConfig {
+        MhfConfig {
+            version,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn game_folder() {
+        let dir = TempDir::new("folder");
+        let mut report = Report::default();
+        assert!(!check_game_folder(&mut report, &dir.0.join("missing")));
+        assert_eq!(statuses(&report), [("game folder", Status::Fail)]);
+
+        let mut report = Report::default();
+        assert!(check_game_folder(&mut report, &dir.0));
+        assert_eq!(
+            statuses(&report),
+            [("game folder", Status::Pass), ("game dlls", Status::Fail)]
+        );
+
+        let dir = dir.with(&[("mhfo.dll", ""), ("mhfo-hd.dll", "")]);
+        let mut report = Report::default();
+        assert!(check_game_folder(&mut report, &dir.0));
+        assert_eq!(
+            statuses(&report),
+            [("game folder", Status::Pass), ("game dlls", Status::Pass)]
+        );
+        assert_eq!(report.checks[1].message, "mhfo.dll, mhfo-hd.dll");
+        assert_eq!((report.pass, report.warn, report.fail), (2, 0, 0));
+    }
+
+    #[test]
+    fn version() {
+        let empty = TempDir::new("version-empty");
+        let f5 = TempDir::new("version-f5").with(&[("mhfo.dll", "")]);
+        let zz = TempDir::new("version-zz").with(&[("mhfo.dll", ""), ("mhfo-hd.dll", "")]);
+        let cases = [
+            (&empty, None, Status::Warn),
+            (&zz, None, Status::Pass),
+            (&zz, Some(MhfVersion::ZZ), Status::Pass),
+            (&f5, Some(MhfVersion::F5), Status::Pass),
+            (&f5, Some(MhfVersion::ZZ), Status::Warn),
+            (&zz, Some(MhfVersion::F5), Status::Warn),
+        ];
+        for (dir, version, status) in cases {
+            let mut report = Report::default();
+            check_version(&mut report, &dir.0, version.map(config).as_ref());
+            assert_eq!(statuses(&report), [("version", status)], "{version:?}");
+        }
+    }
+
+    #[test]
+    fn settings() {
+        let graphics = |dir: &TempDir, version| {
+            let mut report = Report::default();
+            check_settings(&mut report, &dir.0, version);
+            let ini = report.checks[0].status;
+            let graphics = report.checks.last().unwrap();
+            (ini, graphics.status, graphics.message.clone())
+        };
+
+        // GRAPHICS_VER defaults to 1
+        let dir = TempDir::new("settings-missing").with(&[("mhfo.dll", "")]);
+        let (ini, status, message) = graphics(&dir, Some(MhfVersion::ZZ));
+        assert_eq!((ini, status), (Status::Warn, Status::Fail));
+        assert!(message.contains("'mhfo-hd.dll'"));
+        let (_, status, _) = graphics(&dir, Some(MhfVersion::F5));
+        assert_eq!(status, Status::Pass);
+
+        let dir = dir.with(&[("mhfo-hd.dll", "")]);
+        let (_, status, message) = graphics(&dir, None);
+        assert_eq!(status, Status::Pass);
+        assert_eq!(message, "GRAPHICS_VER=1 uses 'mhfo-hd.dll'");
+
+        let dir = TempDir::new("settings-ini").with(&[
+            ("mhf.ini", "[VIDEO]\nGRAPHICS_VER=0\n"),
+            ("mhfo-hd.dll", ""),
+        ]);
+        let (ini, status, message) = graphics(&dir, Some(MhfVersion::ZZ));
+        assert_eq!((ini, status), (Status::Pass, Status::Fail));
+        assert!(message.contains("'mhfo.dll'"));
+        let dir = dir.with(&[("mhfo.dll", "")]);
+        let (_, status, message) = graphics(&dir, Some(MhfVersion::ZZ));
+        assert_eq!(status, Status::Pass);
+        assert_eq!(message, "GRAPHICS_VER=0 uses 'mhfo.dll'");
+    }
+
+    #[test]
+    fn config_data() {
+        let check = |args: &[&str]| {
+            let cli_config = CliConfig::try_parse_from(["mhf-iel-cli"].iter().chain(args)).unwrap();
+            let mut report = Report::default();
+            let config = load_config(&mut report, &cli_config);
+            (config.is_some(), statuses(&report))
+        };
+
+        assert_eq!(
+            check(&["--config-data", "{"]),
+            (false, vec![("config", Status::Fail)])
+        );
+        assert_eq!(
+            check(&["--config-data", r#"{"char_id":"one"}"#]),
+            (false, vec![("config", Status::Fail)])
+        );
+        // It still returns the config, so the rest of the checks can use it
+        assert_eq!(
+            check(&["--config-data", r#"{"char_id":1,"user_token":"short"}"#]),
+            (true, vec![("config", Status::Fail)])
+        );
+        assert_eq!(
+            check(&[
+                "--config-data",
+                r#"{"char_id":1,"user_token":"0123456789abcdef"}"#
+            ]),
+            (true, vec![("config", Status::Pass)])
+        );
+    }
+
+    #[test]
+    fn server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut config = MhfConfig {
+            server_host: "127.0.0.1".to_owned(),
+            server_port: listener.local_addr().unwrap().port() as u32,
+            ..Default::default()
+        };
+        let mut report = Report::default();
+        check_server(&mut report, &config);
+        assert_eq!(statuses(&report), [("server", Status::Pass)]);
+
+        drop(listener);
+        let mut report = Report::default();
+        check_server(&mut report, &config);
+        assert_eq!(statuses(&report), [("server", Status::Fail)]);
+
+        config.server_host = "invalid host".to_owned();
+        let mut report = Report::default();
+        check_server(&mut report, &config);
+        assert_eq!(statuses(&report), [("server", Status::Fail)]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wine() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("wine").with(&[("wine", "#!/bin/sh\necho wine-9.0\n")]);
+        let wine = dir.0.join("wine");
+        fs::set_permissions(&wine, fs::Permissions::from_mode(0o755)).unwrap();
+        let mut report = Report::default();
+        check_wine_program(&mut report, wine.to_str().unwrap());
+        check_wine_program(&mut report, dir.0.join("missing").to_str().unwrap());
+        assert_eq!(
+            statuses(&report),
+            [("wine", Status::Pass), ("wine", Status::Warn)]
+        );
+        assert_eq!(report.checks[0].message, "wine-9.0");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn locale() {
+        let mut report = Report::default();
+        check_locale_output(
+            &mut report,
+            "C\nC.utf8\nja_JP.utf8\nPOSIX",
+            "Noto Sans CJK JP\n",
+        );
+        assert_eq!(
+            statuses(&report),
+            [("locale", Status::Pass), ("fonts", Status::Pass)]
+        );
+        assert_eq!(report.checks[0].message, "ja_JP.utf8 is available");
+
+        let mut report = Report::default();
+        check_locale_output(&mut report, "C\nja_JP.eucjp\nPOSIX", "");
+        assert_eq!(
+            statuses(&report),
+            [("locale", Status::Warn), ("fonts", Status::Warn)]
+        );
+        assert!(report.text().ends_with("0 passed, 2 warnings, 0 failed"));
+    }
+}
@@ -0,0 +1,192 @@
+use mhf_iel::signv2::{Character, Signv2Client};
+use mhf_iel::MhfConfig;
+
+use serde_json::{Map, Value};
+
+use crate::config;
+use crate::output::{self, ExitCode};
+use crate::CliConfig;
+
+pub struct LegacyArgs {
+    pub char_id: u32,
+    pub char_new: bool,
+    pub user_token: String,
+}
+
+impl LegacyArgs {
+    pub fn from_cli(cli_config: &CliConfig) -> Option<Self> {
+        Some(Self {
+            char_id: cli_config.char_id?,
+            char_new: cli_config.char_new?,
+            user_token: cli_config.user_token.clone()?,
+        })
+    }
+}
+
+fn from_server(
+    args: &LegacyArgs,
+    cli_config: &CliConfig,
+    user_name: &str,
+    user_password: &str,
+) -> MhfConfig {
+    let client = Signv2Client::new(&cli_config.endpoint);
+    let auth = client.login(user_name, user_password).unwrap_or_else(|e| {
+        output::fail(ExitCode::ConfigMissing, format!("error logging in: {}", e))
+    });
+    if auth.user.token != args.user_token {
+        // The token id has to match the token, so the fresh session wins
+        output::log("warning: using the session from signv2server instead of the positional token");
+    }
+    let character = auth
+        .characters
+        .iter()
+        .find(|c| c.id == args.char_id)
+        .cloned()
+        .unwrap_or_else(|| Character {
+            id: args.char_id,
+            ..Default::default()
+        });
+    MhfConfig {
+        server_host: cli_config
+            .server_host
+            .clone()
+            .unwrap_or_else(|| client.host().to_owned()),
+        server_port: cli_config.server_port,
+        version: cli_config.mhf_version,
+        ..auth.to_config(&character, args.char_new, user_name, user_password)
+    }
+}
+
+/// Builds a config from the `<char_id> <char_new> <token>` arguments the original `mhf-iel.exe`
+/// took. Everything else comes from the regular config layers, or from signv2server when there's no
+/// config data but there are credentials to log in with.
+pub fn load_config(args: &LegacyArgs, cli_config: &CliConfig) -> MhfConfig {
+    output::log(
+        "warning: positional arguments are deprecated and will be removed, use '--config-file' or '--config-data' instead",
+    );
+    let overrides = Map::from_iter([
+        ("char_id".to_owned(), Value::from(args.char_id)),
+        ("char_new".to_owned(), Value::from(args.char_new)),
+        (
+            "user_token".to_owned(),
+            Value::from(args.user_token.clone()),
+        ),
+    ]);
+    let layers = config::layers(cli_config, overrides);
+    match (
+        &layers.document_source,
+        &cli_config.username,
+        &cli_config.password,
+    ) {
+        (None, Some(user_name), Some(user_password)) => {
+            from_server(args, cli_config, user_name, user_password)
+        }
+        _ => config::from_layers(&layers),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use clap::Parser;
+
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn cli_config(args: &[&str]) -> CliConfig {
+        CliConfig::try_parse_from(["mhf-iel-cli"].iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn positional() {
+        let args = LegacyArgs::from_cli(&cli_config(&["42", "true", TOKEN])).unwrap();
+        assert_eq!(args.char_id, 42);
+        assert!(args.char_new);
+        assert_eq!(args.user_token, TOKEN);
+
+        let args = LegacyArgs::from_cli(&cli_config(&["42", "0", TOKEN])).unwrap();
+        assert!(!args.char_new);
+        assert!(LegacyArgs::from_cli(&cli_config(&[])).is_none());
+        // All three or nothing
+        assert!(CliConfig::try_parse_from(["mhf-iel-cli", "42"]).is_err());
+        assert!(CliConfig::try_parse_from(["mhf-iel-cli", "42", "maybe", TOKEN]).is_err());
+    }
+
+    #[test]
+    fn overrides_config_data() {
+        let cli_config = cli_config(&[
+            "--config-data",
+            r#"{"char_id":1,"char_name":"Hunter","user_token":"fedcba9876543210","server_host":"10.0.0.1"}"#,
+            "--username",
+            "account",
+            "--password",
+            "hunter2",
+            "42",
+            "yes",
+            TOKEN,
+        ]);
+        let args = LegacyArgs::from_cli(&cli_config).unwrap();
+        // The credentials are only used without config data
+        let config = load_config(&args, &cli_config);
+        assert_eq!(config.char_id, 42);
+        assert!(config.char_new);
+        assert_eq!(config.user_token, TOKEN);
+        assert_eq!(config.char_name, "Hunter");
+        assert_eq!(config.server_host, "10.0.0.1");
+    }
+
+    #[test]
+    fn logs_in() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let signv2server = thread::spawn(move || {
+            let mut request = server.recv().unwrap();
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            let url = request.url().to_owned();
+            let response = tiny_http::Response::from_string(format!(
+                r#"{{"user":{{"tokenId":7,"token":"{TOKEN}"}},"characters":[{{"id":41,"name":"Main"}},{{"id":42,"name":"Alt","hr":7}}]}}"#
+            ));
+            request.respond(response).unwrap();
+            (url, body)
+        });
+        let missing = std::env::temp_dir().join(format!("mhf-iel-legacy-{}", std::process::id()));
+        let cli_config = cli_config(&[
+            "--config-file",
+            missing.to_str().unwrap(),
+            "--endpoint",
+            &endpoint,
+            "--server-port",
+            "53313",
+            "--mhf-version",
+            "F5",
+            "--username",
+            "account",
+            "--password",
+            "hunter2",
+            "42",
+            "false",
+            "fedcba9876543210",
+        ]);
+        let args = LegacyArgs::from_cli(&cli_config).unwrap();
+        let config = load_config(&args, &cli_config);
+        let (url, body) = signv2server.join().unwrap();
+        assert_eq!(url, "/login");
+        assert_eq!(body, r#"{"username":"account","password":"hunter2"}"#);
+
+        assert_eq!(config.char_id, 42);
+        assert_eq!(config.char_name, "Alt");
+        assert_eq!(config.char_hr, 7);
+        assert_eq!(config.char_ids, [41, 42]);
+        assert!(!config.char_new);
+        // The fresh session wins over the positional token
+        assert_eq!(config.user_token, TOKEN);
+        assert_eq!(config.user_token_id, 7);
+        assert_eq!(config.user_name, "account");
+        assert_eq!(config.server_host, "127.0.0.1");
+        assert_eq!(config.server_port, 53313);
+        assert_eq!(config.version, mhf_iel::MhfVersion::F5);
+    }
+}
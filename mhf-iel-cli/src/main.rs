@@ -1,45 +1,928 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-use mhf_iel::MhfConfig;
+mod config;
+mod doctor;
+mod legacy;
+mod link;
+mod output;
+mod profiles;
+mod serve;
+mod shortcut;
+mod tui;
+mod vdf;
+mod wine;
 
-use std::{fs::File, path::PathBuf, process::exit};
+use mhf_iel::ipc::{EventWriter, IpcAddress};
+use mhf_iel::mezfes::MezFesEvent;
+use mhf_iel::notice::{NoticeBuilder, NoticeFlags};
+use mhf_iel::policy::{Policy, POLICY_ENV};
+use mhf_iel::registry;
+use mhf_iel::settings::MhfSettings;
+use mhf_iel::signv2::{DEFAULT_ENDPOINT, DEFAULT_SERVER_PORT};
+use mhf_iel::update::{Manifest, UpdateReport, UpdateSource};
+use mhf_iel::{CliFlags, MhfConfig, MhfVersion, Notice};
+use output::{ExitCode, OutputFormat};
 
-use clap::Parser;
+use std::path::{Path, PathBuf};
+
+use clap::{builder::BoolishValueParser, ArgAction, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
 
 #[derive(Parser, Debug, Default)]
-#[command(about = Some("Runs MHF. Config data can be specified through arguments, and defaults to a 'config.json' file in the current folder."))]
+#[command(about = Some("Runs MHF. Config data can be specified through arguments, or the 'MHF_IEL_CONFIG' environment variable, and defaults to a 'config.json' file in the current folder."))]
 pub struct CliConfig {
-    #[arg(long, help = "JSON config file")]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(
+        long,
+        global = true,
+        group = "config_source",
+        help = "JSON config file"
+    )]
     pub config_file: Option<PathBuf>,
-    #[arg(long, help = "JSON config data")]
+    #[arg(
+        long,
+        global = true,
+        group = "config_source",
+        help = "JSON config data. Other processes can read it, so prefer the other options when it has secrets"
+    )]
     pub config_data: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        group = "config_source",
+        help = "Read the JSON config data from stdin"
+    )]
+    pub config_stdin: bool,
+    #[arg(
+        long,
+        global = true,
+        group = "config_source",
+        help = "Read the JSON config data from an inherited pipe or file handle (a file descriptor outside of Windows)"
+    )]
+    pub config_handle: Option<u64>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        help = "Output format"
+    )]
+    pub output: OutputFormat,
+    #[arg(
+        long,
+        global = true,
+        help = "Write the output to a file instead of stdout"
+    )]
+    pub output_file: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        help = "Append warnings, errors and output to a file, useful since release builds have no console"
+    )]
+    pub log_file: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        help = "Print status events to stdout as JSON lines while running"
+    )]
+    pub events: bool,
+    #[arg(
+        long,
+        global = true,
+        value_name = "ADDRESS",
+        help = "Also send the status events to a named pipe, or a Unix socket with 'unix:<path>'. The caller has to be listening already"
+    )]
+    pub ipc: Option<IpcAddress>,
+    #[arg(
+        long,
+        global = true,
+        help = "URL or folder mirroring the game files, used when 'mhf_flags' has Selfup or Restat"
+    )]
+    pub update_source: Option<UpdateSource>,
+    #[arg(
+        long,
+        global = true,
+        help = "JSON update manifest, defaults to 'manifest.json' from the update source"
+    )]
+    pub update_manifest: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        env = profiles::PROFILES_ENV,
+        help = "Server profiles file, defaults to 'profiles.json' in the current folder"
+    )]
+    pub profiles: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        help = "Log in through signv2server and pick a character interactively, instead of reading a config"
+    )]
+    pub interactive: bool,
+    #[arg(long, global = true, default_value = DEFAULT_ENDPOINT, help = "signv2server endpoint used when logging in")]
+    pub endpoint: String,
+    #[arg(
+        long,
+        global = true,
+        help = "Sign server host used when logging in, defaults to the endpoint's host"
+    )]
+    pub server_host: Option<String>,
+    #[arg(long, global = true, default_value_t = DEFAULT_SERVER_PORT, help = "Sign server port used when logging in")]
+    pub server_port: u32,
+    #[arg(
+        long,
+        global = true,
+        default_value = "ZZ",
+        help = "Game version used when logging in"
+    )]
+    pub mhf_version: MhfVersion,
+    #[arg(
+        long,
+        global = true,
+        help = "Username to log in with, when using positional arguments without config data"
+    )]
+    pub username: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        help = "Password to log in with, when using positional arguments without config data"
+    )]
+    pub password: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "KEY=VALUE",
+        value_parser = config::parse_override,
+        help = "Override a config field, taking priority over the config data and 'MHF_IEL_<FIELD>' environment variables"
+    )]
+    pub set: Vec<(String, String)>,
+
+    // Deprecated `<char_id> <char_new> <token>` form used by the original 'mhf-iel.exe'
+    #[arg(hide = true, requires_all = ["char_new", "user_token"])]
+    pub char_id: Option<u32>,
+    #[arg(hide = true, action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+    pub char_new: Option<bool>,
+    #[arg(hide = true)]
+    pub user_token: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Runs MHF. This is the default when no command is given
+    Run,
+    /// Checks that the config is valid, without running MHF
+    Validate,
+    /// Prints what 'run' would pass to the game, without running MHF
+    Plan,
+    /// Prints the JSON Schema of the config
+    Schema,
+    /// Prints the settings read from 'mhf.ini'
+    Settings {
+        #[arg(long, help = "Game folder, defaults to the current folder")]
+        mhf_folder: Option<PathBuf>,
+    },
+    /// Runs MHF from a 'mhf://launch' link
+    Open {
+        link: String,
+        #[arg(long, help = "Game folder, defaults to the current folder")]
+        mhf_folder: Option<PathBuf>,
+    },
+    /// Prints what registers the 'mhf://' link scheme to run 'open'
+    RegisterScheme {
+        #[arg(long, value_enum)]
+        format: SchemeFormat,
+        #[arg(long, help = "Game folder, defaults to the current folder")]
+        mhf_folder: Option<PathBuf>,
+        #[arg(long, help = "Path to 'mhf-iel-cli.exe', defaults to this executable")]
+        exe: Option<PathBuf>,
+        #[arg(
+            long,
+            default_value = "wine",
+            help = "Wine command used by the '.desktop' entry"
+        )]
+        wine: String,
+    },
+    /// Serves an HTTP API on 127.0.0.1 to launch the game from a browser
+    Serve {
+        #[arg(
+            long,
+            default_value_t = 0,
+            help = "Port to listen on, picks a free one by default"
+        )]
+        port: u16,
+        #[arg(
+            long,
+            value_name = "ORIGIN",
+            help = "Origin allowed to call the API, such as 'https://launcher.example.com'"
+        )]
+        allow_origin: Vec<String>,
+        #[arg(
+            long,
+            value_name = "HOST[:PORT]",
+            help = "Server allowed in launch requests, on top of the profiles and loopback"
+        )]
+        allow_server: Vec<String>,
+        #[arg(long, help = "Write the session token to a file")]
+        token_file: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Game folder used for '/settings', defaults to the current folder"
+        )]
+        mhf_folder: Option<PathBuf>,
+    },
+    /// Checks the game folder, config, server and system for common problems
+    Doctor {
+        #[arg(
+            long,
+            help = "Game folder, defaults to the config's 'mhf_folder' or the current folder"
+        )]
+        mhf_folder: Option<PathBuf>,
+    },
+    /// Makes a Lutris installer or a Steam shortcut launching a profile's server
+    Shortcut {
+        #[arg(long, value_enum)]
+        format: ShortcutFormat,
+        #[arg(long, help = "Name of the server profile")]
+        profile: String,
+        #[arg(long, help = "Game folder, defaults to the current folder")]
+        mhf_folder: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Path to 'mhf-iel-cli.exe', defaults to the one in the game folder"
+        )]
+        exe: Option<PathBuf>,
+        #[arg(
+            long,
+            required_if_eq("format", "steam"),
+            help = "Steam's 'shortcuts.vdf' to add the shortcut to, in 'userdata/<user id>/config'"
+        )]
+        vdf: Option<PathBuf>,
+        #[arg(
+            long,
+            default_value = "proton_experimental",
+            help = "Steam compatibility tool the shortcut runs with"
+        )]
+        compat_tool: String,
+    },
+    /// Runs the Windows CLI through Wine or Proton with the config, setting up the prefix first
+    Wine {
+        #[arg(
+            long,
+            help = "Wine prefix, or Proton compat data folder, defaults to 'WINEPREFIX' or one in '~/.local/share/mhf-iel'"
+        )]
+        prefix: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with = "proton",
+            help = "Wine executable, defaults to 'WINE' or 'wine' from PATH"
+        )]
+        wine: Option<PathBuf>,
+        #[arg(long, help = "Proton folder or 'proton' script, used instead of Wine")]
+        proton: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Path to 'mhf-iel-cli.exe', defaults to the one next to this executable"
+        )]
+        exe: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Use DXVK for Direct3D 9, which has to be installed in the prefix for Wine"
+        )]
+        dxvk: bool,
+        #[arg(
+            long,
+            value_name = "DLL=MODE",
+            help = "Extra 'WINEDLLOVERRIDES' entry, such as 'd3d9=n,b'"
+        )]
+        dll_override: Vec<String>,
+        #[arg(
+            long,
+            default_value = "ja_JP.UTF-8",
+            help = "Locale the game runs with"
+        )]
+        locale: String,
+        #[arg(
+            long,
+            help = "Font replacing the Japanese Windows fonts, set on new prefixes to 'Noto Sans CJK JP'"
+        )]
+        font: Option<String>,
+        #[arg(long, help = "Print the commands that would run instead")]
+        dry_run: bool,
+    },
+    /// Writes a zip with the redacted config, 'mhf.ini', the plan, the encoded launcher data and the
+    /// end of '--log-file', to attach to bug reports
+    Bundle {
+        #[arg(help = "Zip file to write")]
+        path: PathBuf,
+    },
+    /// Replays a bundle through 'plan' and compares what it encodes to the bundled launcher data
+    Import {
+        #[arg(help = "Zip file written by 'bundle'")]
+        path: PathBuf,
+    },
+    /// Decodes a launcher block dumped from the original launcher, and optionally its global alloc
+    Decode {
+        #[arg(help = "Raw dump of the block passed to 'mhDLL_Main'")]
+        block: PathBuf,
+        #[arg(long, help = "Raw dump of the global alloc")]
+        global: Option<PathBuf>,
+        #[arg(long = "game-version", default_value = "ZZ")]
+        version: MhfVersion,
+    },
+    /// Compares a dumped launcher block field by field with another dump, or with what the config
+    /// encodes
+    Diff {
+        #[arg(help = "Raw dump of the block passed to 'mhDLL_Main'")]
+        block: PathBuf,
+        #[arg(help = "Dump to compare with, defaults to the config's encoding")]
+        other: Option<PathBuf>,
+        #[arg(long, help = "Raw dump of the global alloc")]
+        global: Option<PathBuf>,
+        #[arg(long, help = "Raw dump of the other global alloc")]
+        other_global: Option<PathBuf>,
+        #[arg(
+            long = "game-version",
+            default_value = "ZZ",
+            help = "Version of the dumps, the config's is used when comparing with it"
+        )]
+        version: MhfVersion,
+    },
+    /// Prints the known launcher data fields, or scripts labelling them in a disassembler
+    Fields {
+        #[arg(long, value_enum, default_value = "json")]
+        format: FieldsFormat,
+        #[arg(
+            long = "game-version",
+            default_value = "ZZ",
+            help = "Version whose 'mhf.exe' the Ghidra and IDA scripts label"
+        )]
+        version: MhfVersion,
+    },
+    /// Prints a MezFes event from this week's Monday to the next, in Japan time, with every stall
+    /// open, as the config's 'mez_*' fields
+    Mezfes {
+        #[arg(
+            long,
+            help = "Unix timestamp the week is worked out from, defaults to now"
+        )]
+        current_ts: Option<u32>,
+        #[arg(long, help = "Print it with RFC 3339 times instead")]
+        typed: bool,
+    },
+    /// Writes or previews notices for the config's 'notices' list
+    Notice {
+        #[command(subcommand)]
+        command: NoticeCommand,
+    },
+    /// Inspects the config
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Prints the config values by where they came from, with secrets redacted
+    Show {
+        #[arg(
+            long,
+            help = "Print the config after merging every layer and applying defaults instead"
+        )]
+        effective: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum NoticeCommand {
+    /// Prints a notice with a centred title and left aligned body sections
+    Build {
+        #[arg(long)]
+        title: Option<String>,
+        #[arg(long, help = "Body section, can be given more than once")]
+        body: Vec<String>,
+        #[arg(long, help = "Set the flag thought to show the notice as new")]
+        new: bool,
+        #[arg(long, help = "Set the flag thought to show the notice as important")]
+        important: bool,
+    },
+    /// Prints the flags, title, body and plain text of a notice
+    Preview {
+        #[arg(help = "Notice markup, as in 'notices.data'")]
+        data: String,
+        #[arg(long, default_value_t = 0)]
+        flags: u16,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SchemeFormat {
+    /// Windows '.reg' file
+    Reg,
+    /// Linux '.desktop' entry, running the CLI through Wine
+    Desktop,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ShortcutFormat {
+    /// Lutris installer YAML
+    Lutris,
+    /// Entry in Steam's 'shortcuts.vdf'
+    Steam,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FieldsFormat {
+    Json,
+    Markdown,
+    /// Python script for Ghidra's script manager
+    Ghidra,
+    /// IDAPython script
+    Ida,
+}
+
+#[derive(Serialize)]
+struct NoticePreview {
+    flags: Vec<String>,
+    title: Option<String>,
+    body: String,
+    preview: String,
+}
+
+#[derive(Serialize)]
+struct RunResult {
+    update: Option<UpdateReport>,
+    game_code: isize,
+}
+
+fn resolve_config(cli_config: &CliConfig) -> MhfConfig {
+    if cli_config.interactive {
+        tui::run(tui::TuiConfig {
+            endpoint: cli_config.endpoint.clone(),
+            server_host: cli_config.server_host.clone(),
+            server_port: cli_config.server_port,
+            version: cli_config.mhf_version,
+        })
+        .unwrap_or_else(|| output::success(ExitCode::Success, ()))
+    } else if let Some(legacy_args) = legacy::LegacyArgs::from_cli(cli_config) {
+        legacy::load_config(&legacy_args, cli_config)
+    } else {
+        config::load_config(cli_config)
+    }
+}
+
+fn read_dump(path: &Path) -> Vec<u8> {
+    std::fs::read(path).unwrap_or_else(|e| {
+        output::fail(
+            ExitCode::Dump,
+            format!("unable to read '{}': {e}", path.display()),
+        )
+    })
+}
+
+fn validate(mhf_config: &MhfConfig) {
+    if let Err(e) = mhf_config.validate() {
+        output::fail(ExitCode::Invalid, format!("invalid config: {}", e));
+    }
+}
+
+fn update(cli_config: &CliConfig, mhf_config: &MhfConfig) -> mhf_iel::Result<UpdateReport> {
+    let source = cli_config
+        .update_source
+        .as_ref()
+        .ok_or_else(|| mhf_iel::Error::Update("no update source specified".to_owned()))?;
+    let manifest = match &cli_config.update_manifest {
+        Some(path) => Manifest::from_file(path)?,
+        None => source.manifest()?,
+    };
+    let mhf_folder = match &mhf_config.mhf_folder {
+        Some(mhf_folder) => mhf_folder.clone(),
+        None => std::env::current_dir().or(Err(mhf_iel::Error::GamePath))?,
+    };
+    let report = mhf_iel::update::update(&manifest, source, &mhf_folder)?;
+    output::log(format!(
+        "checked {} files, updated {} ({} resumed)",
+        report.checked,
+        report.updated.len(),
+        report.resumed.len()
+    ));
+    Ok(report)
+}
+
+// The profiles decide which servers `run` may connect to and which keys sign tickets
+fn apply_policy(cli_config: &CliConfig) {
+    let profiles = profiles::load(cli_config.profiles.as_deref());
+    mhf_iel::policy::set_policy(profiles.policy());
+}
+
+// A library caller's policy, passed on by `launch_detached`, has to allow the config too
+fn check_inherited_policy(mhf_config: &MhfConfig) {
+    let Some(policy) = std::env::var_os(POLICY_ENV) else {
+        return;
+    };
+    let policy: Policy = policy
+        .to_str()
+        .and_then(|policy| serde_json::from_str(policy).ok())
+        .unwrap_or_else(|| output::fail(ExitCode::Invalid, format!("invalid {POLICY_ENV}")));
+    if let Err(e) = policy.check(mhf_config) {
+        output::fail(ExitCode::Invalid, format!("invalid config: {e}"))
+    }
+}
+
+fn warn(mhf_config: &MhfConfig) {
+    for warning in mhf_config.warnings() {
+        output::log(format!("warning: {warning}"));
+    }
+    warn_research(mhf_config);
+}
+
+// Hard to miss on purpose, these can make the game do anything
+fn warn_research(mhf_config: &MhfConfig) {
+    if mhf_config.research.is_empty() {
+        return;
+    }
+    let rule = "warning: ".to_owned() + &"*".repeat(72);
+    output::log(&rule);
+    output::log(format!(
+        "warning: {} research writes will be applied to the launcher data",
+        mhf_config.research.len()
+    ));
+    output::log("warning: they're for reverse engineering only, and can crash the game or corrupt");
+    output::log("warning: its data, don't play with them");
+    output::log(&rule);
+}
+
+fn run(cli_config: &CliConfig, mhf_config: MhfConfig) -> ! {
+    validate(&mhf_config);
+    warn(&mhf_config);
+    apply_policy(cli_config);
+    if let Err(e) = mhf_iel::policy::policy().check(&mhf_config) {
+        output::fail(ExitCode::Invalid, format!("invalid config: {e}"))
+    }
+    check_inherited_policy(&mhf_config);
+    let wants_update = mhf_config
+        .mhf_flags
+        .iter()
+        .flatten()
+        .any(CliFlags::requests_update);
+    let has_source = cli_config.update_source.is_some() || cli_config.update_manifest.is_some();
+    if wants_update && !has_source {
+        output::log("no update source, passing the update flags on to the game");
+    }
+    let update = (wants_update && has_source).then(|| {
+        update(cli_config, &mhf_config).unwrap_or_else(|e| {
+            output::fail(ExitCode::Update, format!("error updating mhf: {}", e))
+        })
+    });
+    let mut ipc = cli_config.ipc.as_ref().and_then(|address| {
+        EventWriter::connect(address)
+            .map_err(|e| output::log(format!("warning: {e}")))
+            .ok()
+    });
+    let game_code = mhf_iel::run_with_events(mhf_config, |event| {
+        if cli_config.events {
+            output::event(event)
+        }
+        if let Some(writer) = &mut ipc {
+            if let Err(e) = writer.send(&event) {
+                output::log(format!("warning: {e}"));
+                ipc = None;
+            }
+        }
+    })
+    .unwrap_or_else(|e| output::fail(ExitCode::Run, format!("error running mhf: {}", e)));
+    if game_code != 0 {
+        output::log(format!("mhf exited with code {game_code}"));
+    }
+    output::success(
+        ExitCode::from_game(game_code),
+        RunResult { update, game_code },
+    )
 }
 
 fn main() {
     let cli_config = CliConfig::try_parse().unwrap_or_else(|e| {
-        eprintln!("{e}");
-        exit(1);
-    });
-    let config_data = cli_config
-        .config_data
-        .or_else(|| {
-            cli_config
-                .config_file
-                .or_else(|| std::env::current_dir().map(|d| d.join("config.json")).ok())
-                .and_then(|v| File::open(v).ok())
-                .and_then(|v| std::io::read_to_string(v).ok())
-        })
-        .unwrap_or_else(|| {
-            eprintln!("unable to locate 'config.json' file");
-            exit(2);
-        });
-    let mhf_config: MhfConfig = serde_json::from_str(&config_data).unwrap_or_else(|e| {
-        eprintln!("error parsing config data: {}", e);
-        exit(3);
+        let code = if e.use_stderr() {
+            ExitCode::Args
+        } else {
+            ExitCode::Success
+        };
+        let _ = e.print();
+        std::process::exit(code as i32);
     });
-    let result = mhf_iel::run(mhf_config);
-    if let Err(e) = result {
-        eprintln!("error running mhf: {}", e);
-        exit(4);
+    output::init(
+        cli_config.output,
+        cli_config.output_file.clone(),
+        cli_config.log_file.clone(),
+    );
+    config::warn_argv_secrets(&cli_config);
+    match &cli_config.command {
+        None | Some(Command::Run) => run(&cli_config, resolve_config(&cli_config)),
+        Some(Command::Validate) => {
+            let mhf_config = resolve_config(&cli_config);
+            validate(&mhf_config);
+            warn(&mhf_config);
+            output::success(ExitCode::Success, "config is valid")
+        }
+        Some(Command::Plan) => {
+            apply_policy(&cli_config);
+            let mhf_config = resolve_config(&cli_config);
+            warn(&mhf_config);
+            let plan = mhf_iel::plan(&mhf_config).unwrap_or_else(|e| {
+                output::fail(ExitCode::Invalid, format!("invalid config: {}", e))
+            });
+            output::success(ExitCode::Success, plan)
+        }
+        Some(Command::Schema) => output::success(ExitCode::Success, mhf_iel::config_schema()),
+        Some(Command::Settings { mhf_folder }) => {
+            let mhf_folder = mhf_folder
+                .clone()
+                .or_else(|| std::env::current_dir().ok())
+                .unwrap_or_default();
+            output::success(ExitCode::Success, MhfSettings::load(&mhf_folder))
+        }
+        Some(Command::Open { link, mhf_folder }) => {
+            let profiles = profiles::load(cli_config.profiles.as_deref());
+            let mhf_config = link::Link::parse(link)
+                .and_then(|link| link.to_config(&profiles.profiles, mhf_folder.clone()))
+                .unwrap_or_else(|e| output::fail(ExitCode::Invalid, format!("invalid link: {e}")));
+            run(&cli_config, mhf_config)
+        }
+        Some(Command::RegisterScheme {
+            format,
+            mhf_folder,
+            exe,
+            wine,
+        }) => {
+            let current_dir = std::env::current_dir().unwrap_or_default();
+            let exe = exe
+                .clone()
+                .or_else(|| std::env::current_exe().ok())
+                .unwrap_or_default();
+            let profiles = current_dir.join(
+                cli_config
+                    .profiles
+                    .clone()
+                    .unwrap_or_else(|| "profiles.json".into()),
+            );
+            let mhf_folder = current_dir.join(mhf_folder.clone().unwrap_or_default());
+            let artifact = match format {
+                SchemeFormat::Reg => link::reg_file(&exe, &profiles, &mhf_folder),
+                SchemeFormat::Desktop => link::desktop_entry(wine, &exe, &profiles, &mhf_folder),
+            };
+            output::success(ExitCode::Success, artifact)
+        }
+        Some(Command::Serve {
+            port,
+            allow_origin,
+            allow_server,
+            token_file,
+            mhf_folder,
+        }) => {
+            // So the launches see the same profiles, whatever folder they were passed relative to
+            if let Some(path) = &cli_config.profiles {
+                let path = std::env::current_dir().unwrap_or_default().join(path);
+                std::env::set_var(profiles::PROFILES_ENV, path);
+            }
+            serve::serve(serve::ServeConfig {
+                port: *port,
+                allow_origins: allow_origin.clone(),
+                allow_servers: allow_server.clone(),
+                token_file: token_file.clone(),
+                mhf_folder: mhf_folder.clone(),
+                profiles: profiles::load(cli_config.profiles.as_deref()).profiles,
+            })
+        }
+        Some(Command::Doctor { mhf_folder }) => {
+            apply_policy(&cli_config);
+            doctor::doctor(&cli_config, mhf_folder.clone())
+        }
+        Some(Command::Shortcut {
+            format,
+            profile,
+            mhf_folder,
+            exe,
+            vdf,
+            compat_tool,
+        }) => {
+            let profiles = profiles::load(cli_config.profiles.as_deref());
+            let profile = profiles::find(&profiles.profiles, profile).unwrap_or_else(|| {
+                output::fail(ExitCode::Invalid, format!("unknown profile '{profile}'"))
+            });
+            let current_dir = std::env::current_dir().unwrap_or_default();
+            let mhf_folder = current_dir.join(mhf_folder.clone().unwrap_or_default());
+            let shortcut = shortcut::ShortcutConfig {
+                profile,
+                exe: exe
+                    .as_ref()
+                    .map(|exe| current_dir.join(exe))
+                    .unwrap_or_else(|| mhf_folder.join("mhf-iel-cli.exe")),
+                mhf_folder,
+            };
+            match format {
+                ShortcutFormat::Lutris => output::success(ExitCode::Success, shortcut.lutris()),
+                ShortcutFormat::Steam => {
+                    let vdf = vdf.clone().unwrap_or_default();
+                    match shortcut.steam(&vdf, compat_tool) {
+                        Ok(result) => output::success(ExitCode::Success, result),
+                        Err(e) => output::fail(ExitCode::Invalid, e),
+                    }
+                }
+            }
+        }
+        Some(Command::Wine {
+            prefix,
+            wine,
+            proton,
+            exe,
+            dxvk,
+            dll_override,
+            locale,
+            font,
+            dry_run,
+        }) => {
+            let mhf_config = resolve_config(&cli_config);
+            validate(&mhf_config);
+            wine::run(
+                wine::WineConfig {
+                    prefix: prefix.clone(),
+                    wine: wine.clone(),
+                    proton: proton.clone(),
+                    exe: exe.clone(),
+                    dxvk: *dxvk,
+                    dll_overrides: dll_override.clone(),
+                    locale: locale.clone(),
+                    font: font.clone(),
+                    dry_run: *dry_run,
+                    output: cli_config.output,
+                    events: cli_config.events,
+                },
+                mhf_config,
+            )
+        }
+        Some(Command::Bundle { path }) => {
+            let mhf_config = resolve_config(&cli_config);
+            let log = cli_config.log_file.as_deref().and_then(output::log_tail);
+            let mut data = std::io::Cursor::new(vec![]);
+            let info = mhf_iel::bundle::create(&mhf_config, log.as_deref(), &mut data)
+                .unwrap_or_else(|e| output::fail(ExitCode::Bundle, e));
+            if let Err(e) = std::fs::write(path, data.into_inner()) {
+                output::fail(
+                    ExitCode::Bundle,
+                    format!("unable to write '{}': {e}", path.display()),
+                )
+            }
+            output::success(ExitCode::Success, info)
+        }
+        Some(Command::Import { path }) => {
+            let data = std::fs::read(path).unwrap_or_else(|e| {
+                output::fail(
+                    ExitCode::Bundle,
+                    format!("unable to read '{}': {e}", path.display()),
+                )
+            });
+            match mhf_iel::bundle::replay(&data) {
+                Ok(replay) => output::success(ExitCode::Success, replay),
+                Err(e) => output::fail(ExitCode::Bundle, e),
+            }
+        }
+        Some(Command::Decode {
+            block,
+            global,
+            version,
+        }) => {
+            let block = read_dump(block);
+            let global = global.as_deref().map(read_dump);
+            match mhf_iel::dump::decode(*version, &block, global.as_deref()) {
+                Ok(decoded) => output::success(ExitCode::Success, decoded),
+                Err(e) => output::fail(ExitCode::Dump, e),
+            }
+        }
+        Some(Command::Diff {
+            block,
+            other,
+            global,
+            other_global,
+            version,
+        }) => {
+            let (version, other_block, other_global) = match other {
+                Some(other) => (
+                    *version,
+                    read_dump(other),
+                    other_global.as_deref().map(read_dump),
+                ),
+                None => {
+                    let mhf_config = resolve_config(&cli_config);
+                    let plan = mhf_iel::plan(&mhf_config).unwrap_or_else(|e| {
+                        output::fail(ExitCode::Invalid, format!("invalid config: {}", e))
+                    });
+                    (
+                        mhf_config.version,
+                        mhf_iel::block::launcher_block(&mhf_config, &plan),
+                        Some(mhf_iel::block::global_alloc(&mhf_config, &plan)),
+                    )
+                }
+            };
+            let block = read_dump(block);
+            let global = global.as_deref().map(read_dump);
+            let decoded =
+                mhf_iel::dump::decode(version, &block, global.as_deref()).and_then(|left| {
+                    let right =
+                        mhf_iel::dump::decode(version, &other_block, other_global.as_deref())?;
+                    mhf_iel::dump::diff(&left, &right)
+                });
+            match decoded {
+                Ok(diff) => output::success(ExitCode::Success, diff),
+                Err(e) => output::fail(ExitCode::Dump, e),
+            }
+        }
+        Some(Command::Fields { format, version }) => match format {
+            FieldsFormat::Json => output::success(ExitCode::Success, registry::fields()),
+            FieldsFormat::Markdown => output::success(ExitCode::Success, registry::markdown()),
+            FieldsFormat::Ghidra => {
+                output::success(ExitCode::Success, registry::ghidra_script(*version))
+            }
+            FieldsFormat::Ida => output::success(ExitCode::Success, registry::ida_script(*version)),
+        },
+        Some(Command::Mezfes { current_ts, typed }) => {
+            let current_ts = current_ts.unwrap_or_else(|| MhfConfig::default().current_ts);
+            let event = MezFesEvent::weekly(current_ts);
+            if *typed {
+                output::success(ExitCode::Success, event)
+            }
+            match event.raw() {
+                Ok(raw) => output::success(ExitCode::Success, raw),
+                Err(e) => output::fail(ExitCode::Invalid, e),
+            }
+        }
+        Some(Command::Notice {
+            command:
+                NoticeCommand::Build {
+                    title,
+                    body,
+                    new,
+                    important,
+                },
+        }) => {
+            let mut builder = NoticeBuilder::new();
+            if *new {
+                builder = builder.flags(NoticeFlags::NEW);
+            }
+            if *important {
+                builder = builder.flags(NoticeFlags::IMPORTANT);
+            }
+            if let Some(title) = title {
+                builder = builder.title(title);
+            }
+            for body in body {
+                builder = builder.body(body);
+            }
+            match builder.build() {
+                Ok(notice) => output::success(ExitCode::Success, notice),
+                Err(e) => output::fail(ExitCode::Invalid, e),
+            }
+        }
+        Some(Command::Notice {
+            command: NoticeCommand::Preview { data, flags },
+        }) => {
+            let notice = Notice {
+                flags: *flags,
+                data: data.clone(),
+            };
+            let notice_flags = notice.notice_flags();
+            let mut flags: Vec<String> = notice_flags
+                .iter_names()
+                .map(|(name, _)| name.to_lowercase())
+                .collect();
+            let unknown = notice_flags.difference(NoticeFlags::all()).bits();
+            if unknown != 0 {
+                flags.push(format!("{unknown:#x}"));
+            }
+            output::success(
+                ExitCode::Success,
+                NoticePreview {
+                    flags,
+                    title: notice.title(),
+                    body: notice.body(),
+                    preview: notice.preview(),
+                },
+            )
+        }
+        Some(Command::Config {
+            command: ConfigCommand::Show { effective: false },
+        }) => {
+            let layers = config::layers(&cli_config, Default::default());
+            output::success(ExitCode::Success, layers.redacted())
+        }
+        Some(Command::Config {
+            command: ConfigCommand::Show { effective: true },
+        }) => {
+            let effective = serde_json::to_value(resolve_config(&cli_config));
+            let Ok(serde_json::Value::Object(mut effective)) = effective else {
+                output::fail(ExitCode::ConfigParse, "unable to serialize config");
+            };
+            config::redact(&mut effective);
+            output::success(ExitCode::Success, effective)
+        }
     }
-    exit(0);
 }
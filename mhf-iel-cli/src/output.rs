@@ -0,0 +1,204 @@
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Process exit codes. Listed in the README, so only ever add to the end.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy)]
+pub enum ExitCode {
+    Success = 0,
+    Args = 1,
+    ConfigMissing = 2,
+    ConfigParse = 3,
+    Run = 4,
+    Update = 5,
+    Invalid = 6,
+    Game = 7,
+    Serve = 8,
+    Wine = 9,
+    Doctor = 10,
+    Bundle = 11,
+    Dump = 12,
+}
+
+/// The CLI's own codes stay below this, so game exit codes from here to 255 are passed on as is.
+pub const GAME_CODES: i32 = 64;
+
+impl ExitCode {
+    /// The game's exit code when the process can exit with it, or `Game`.
+    pub fn from_game(game_code: isize) -> i32 {
+        match game_code {
+            0 => Self::Success as i32,
+            code if (GAME_CODES as isize..=255).contains(&code) => code as i32,
+            _ => Self::Game as i32,
+        }
+    }
+}
+
+impl From<ExitCode> for i32 {
+    fn from(code: ExitCode) -> Self {
+        code as i32
+    }
+}
+
+struct Output {
+    format: OutputFormat,
+    file: Option<PathBuf>,
+    log: Option<Mutex<File>>,
+}
+
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+fn output() -> &'static Output {
+    OUTPUT.get_or_init(|| Output {
+        format: OutputFormat::Text,
+        file: None,
+        log: None,
+    })
+}
+
+pub fn init(format: OutputFormat, file: Option<PathBuf>, log_file: Option<PathBuf>) {
+    let log = log_file.and_then(|path| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(Mutex::new)
+            .ok()
+    });
+    let _ = OUTPUT.set(Output { format, file, log });
+    if output().log.is_some() {
+        // Release builds have no console, so panics would otherwise go nowhere
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            write_log(&info.to_string());
+            hook(info);
+        }));
+    }
+}
+
+// How much of the log file goes into bundles
+const LOG_TAIL: u64 = 0x10000;
+
+/// The end of `path`, starting at a line.
+pub fn log_tail(path: &Path) -> Option<Vec<u8>> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(LOG_TAIL)))
+        .ok()?;
+    let mut data = vec![];
+    file.read_to_end(&mut data).ok()?;
+    if len > LOG_TAIL {
+        let start = data.iter().position(|b| *b == b'\n').map_or(0, |i| i + 1);
+        data.drain(..start);
+    }
+    Some(data)
+}
+
+fn write_log(message: &str) {
+    if let Some(log) = &output().log {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        if let Ok(mut log) = log.lock() {
+            let _ = writeln!(log, "[{ts}] {message}");
+        }
+    }
+}
+
+fn emit(data: &str) {
+    write_log(data);
+    match &output().file {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, format!("{data}\n")) {
+                eprintln!("unable to write output file: {e}");
+            }
+        }
+        None => println!("{data}"),
+    }
+}
+
+/// Informational messages and warnings. Always goes to stderr, so it never mixes with JSON output.
+pub fn log(message: impl Display) {
+    let message = message.to_string();
+    eprintln!("{message}");
+    write_log(&message);
+}
+
+/// A JSON line on stdout, even when the output goes to a file, so callers can follow along.
+pub fn event(event: impl Serialize) {
+    if let Ok(data) = serde_json::to_string(&event) {
+        write_log(&data);
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{data}");
+        let _ = stdout.flush();
+    }
+}
+
+pub fn fail(code: ExitCode, message: impl Display) -> ! {
+    match output().format {
+        OutputFormat::Text => log(&message),
+        OutputFormat::Json => emit(
+            &json!({
+                "ok": false,
+                "exit_code": code as i32,
+                "error": message.to_string(),
+            })
+            .to_string(),
+        ),
+    }
+    exit(code as i32)
+}
+
+pub fn success(code: impl Into<i32>, result: impl Serialize) -> ! {
+    let code = code.into();
+    let result = serde_json::to_value(result).unwrap_or_default();
+    match output().format {
+        OutputFormat::Text => match &result {
+            serde_json::Value::String(s) => emit(s),
+            serde_json::Value::Null => {}
+            result => emit(&serde_json::to_string_pretty(result).unwrap_or_default()),
+        },
+        OutputFormat::Json => emit(
+            &json!({
+                "ok": code == ExitCode::Success as i32,
+                "exit_code": code,
+                "result": result,
+            })
+            .to_string(),
+        ),
+    }
+    exit(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_exit_codes() {
+        assert_eq!(ExitCode::from_game(0), 0);
+        assert_eq!(ExitCode::from_game(64), 64);
+        assert_eq!(ExitCode::from_game(255), 255);
+        for code in [1, 7, 63, 256, -1, isize::MAX] {
+            assert_eq!(ExitCode::from_game(code), ExitCode::Game as i32, "{code}");
+        }
+        // Every CLI code stays below the game's
+        assert!((ExitCode::Dump as i32) < GAME_CODES);
+    }
+}
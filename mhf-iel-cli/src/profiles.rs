@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use mhf_iel::policy::{AllowedServer, Policy, TrustedKey};
+use mhf_iel::signv2::DEFAULT_SERVER_PORT;
+use mhf_iel::MhfVersion;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config;
+use crate::output::{self, ExitCode};
+
+/// Passes `--profiles` on to the CLI processes `serve` starts.
+pub const PROFILES_ENV: &str = "MHF_IEL_PROFILES";
+
+/// A server the user chose to trust, so launch requests can name it instead of passing a host.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub server_host: String,
+    #[serde(default = "default_server_port")]
+    pub server_port: u32,
+    #[serde(default)]
+    pub version: MhfVersion,
+    // signv2server endpoint, for launchers that log in themselves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Base64 Ed25519 key the server signs tickets with. Links for the profile need a signed one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+impl Profile {
+    pub fn trusted_key(&self) -> Option<TrustedKey> {
+        self.public_key.as_ref().map(|public_key| TrustedKey {
+            name: self.name.clone(),
+            public_key: public_key.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ProfilesFile {
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    /// Only lets `run` connect to the profiles' servers
+    #[serde(default)]
+    pub restrict_servers: bool,
+}
+
+impl ProfilesFile {
+    pub fn policy(&self) -> Policy {
+        Policy {
+            allowed_servers: self.restrict_servers.then(|| {
+                self.profiles
+                    .iter()
+                    .map(|p| AllowedServer {
+                        host: p.server_host.clone(),
+                        port: Some(p.server_port),
+                    })
+                    .collect()
+            }),
+            trusted_keys: self
+                .profiles
+                .iter()
+                .filter_map(Profile::trusted_key)
+                .collect(),
+        }
+    }
+}
+
+fn default_server_port() -> u32 {
+    DEFAULT_SERVER_PORT
+}
+
+/// Defaults to 'profiles.json' in the current folder. A missing file has no profiles.
+pub fn load(path: Option<&Path>) -> ProfilesFile {
+    let path = path
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("profiles.json"));
+    let Ok(data) = std::fs::read_to_string(&path) else {
+        return ProfilesFile::default();
+    };
+    let source = path.display().to_string();
+    let parsed = config::parse_document(&source, &data).and_then(|document| {
+        serde_json::from_value::<ProfilesFile>(Value::Object(document)).map_err(|e| e.to_string())
+    });
+    match parsed {
+        Ok(file) => {
+            for profile in &file.profiles {
+                if profile.trusted_key().is_some_and(|key| !key.is_valid()) {
+                    output::log(format!(
+                        "warning: profile '{}' has an invalid public_key, its tickets won't verify",
+                        profile.name
+                    ));
+                }
+            }
+            file
+        }
+        Err(e) => output::fail(
+            ExitCode::ConfigParse,
+            format!("error parsing profiles '{source}': {e}"),
+        ),
+    }
+}
+
+pub fn find<'a>(profiles: &'a [Profile], name: &str) -> Option<&'a Profile> {
+    profiles.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,546 @@
+use std::io::Read;
+use std::path::PathBuf;
+
+use mhf_iel::policy::{AllowedServer, Policy, SignedTicket};
+use mhf_iel::session::{launch_detached_with, SessionExit};
+use mhf_iel::settings::MhfSettings;
+use mhf_iel::{Event, GameSession, MhfConfig, MhfVersion};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::output::{self, ExitCode};
+use crate::profiles::Profile;
+
+const MAX_BODY: u64 = 0x10000;
+const LOOPBACK_HOSTS: &[&str] = &["127.0.0.1", "localhost", "::1"];
+// What the game loads and how is up to the user, as with links. Raw writes into the game's memory
+// are only for the user to make too
+const FORBIDDEN_FIELDS: &[&str] = &["version", "mhf_folder", "mhf_flags", "research"];
+
+pub struct ServeConfig {
+    pub port: u16,
+    pub allow_origins: Vec<String>,
+    // 'host' or 'host:port'
+    pub allow_servers: Vec<String>,
+    pub token_file: Option<PathBuf>,
+    pub mhf_folder: Option<PathBuf>,
+    pub profiles: Vec<Profile>,
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    state: &'static str,
+    pid: Option<u32>,
+    events: &'a [Event],
+    exit: Option<&'a SessionExit>,
+}
+
+struct Launched {
+    session: GameSession,
+    events: Vec<Event>,
+    exit: Option<SessionExit>,
+}
+
+struct State {
+    config: ServeConfig,
+    token: String,
+    port: u16,
+    policy: Policy,
+    launched: Option<Launched>,
+}
+
+type Reply = (u16, serde_json::Value);
+
+fn error(code: u16, message: impl ToString) -> Reply {
+    (code, json!({ "error": message.to_string() }))
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).unwrap_or_else(|e| {
+        output::fail(ExitCode::Serve, format!("unable to generate token: {e}"))
+    });
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+// Compares every byte, so the time taken doesn't tell how much of the token was right
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+impl State {
+    fn origin_allowed(&self, request: &Request) -> bool {
+        match header(request, "Origin") {
+            Some(origin) => self.config.allow_origins.iter().any(|o| o == origin),
+            // Not sent by browsers for same-origin GETs, nor by anything that isn't a browser
+            None => true,
+        }
+    }
+
+    // Pages can point a DNS name they control at 127.0.0.1, but the Host header gives them away
+    fn host_allowed(&self, request: &Request) -> bool {
+        let port = self.port;
+        header(request, "Host").is_some_and(|host| {
+            [format!("127.0.0.1:{port}"), format!("localhost:{port}")].contains(&host.to_owned())
+        })
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        header(request, "Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| token_matches(given, &self.token))
+    }
+
+    fn update(&mut self) {
+        let Some(launched) = &mut self.launched else {
+            return;
+        };
+        launched.events.extend(launched.session.events().try_iter());
+        if launched.exit.is_none() {
+            launched.exit = launched.session.try_wait().ok().flatten();
+        }
+    }
+
+    fn status(&mut self) -> Reply {
+        self.update();
+        let status = match &self.launched {
+            None => Status {
+                state: "idle",
+                pid: None,
+                events: &[],
+                exit: None,
+            },
+            Some(launched) => Status {
+                state: match launched.exit {
+                    Some(_) => "exited",
+                    None => "running",
+                },
+                pid: Some(launched.session.id()),
+                events: &launched.events,
+                exit: launched.exit.as_ref(),
+            },
+        };
+        (200, json!(status))
+    }
+
+    // The config a launch request's body asks for, or the error to reply with
+    fn launch_config(&self, body: &str) -> Result<MhfConfig, Reply> {
+        let body: Value = match serde_json::from_str(body) {
+            Ok(body) => body,
+            Err(e) => return Err(error(400, format!("error parsing config data: {e}"))),
+        };
+        // Either the config itself or a `SignedTicket` with it
+        let fields = match body.get("signature") {
+            Some(_) => body.get("config"),
+            None => Some(&body),
+        };
+        if let Some(field) = FORBIDDEN_FIELDS
+            .iter()
+            .find(|field| fields.and_then(|f| f.get(**field)).is_some())
+        {
+            return Err(error(
+                403,
+                format!("'{field}' can't be set through the API"),
+            ));
+        }
+        let config = match body.get("signature") {
+            Some(_) => serde_json::from_value::<SignedTicket>(body)
+                .map_err(|e| mhf_iel::Error::Ticket(e.to_string()))
+                .and_then(|ticket| ticket.to_config(&self.policy.trusted_keys)),
+            None => serde_json::from_value::<MhfConfig>(body)
+                .map_err(|e| mhf_iel::Error::Ticket(e.to_string()))
+                .and_then(|config| config.validate().map(|_| config)),
+        };
+        let mut config = match config {
+            Ok(config) => config,
+            Err(e) => return Err(error(400, e)),
+        };
+        config.mhf_folder = self.config.mhf_folder.clone();
+        config.version = self
+            .config
+            .profiles
+            .iter()
+            .find(|p| p.server_host == config.server_host && p.server_port == config.server_port)
+            .map_or_else(MhfVersion::default, |p| p.version);
+        if let Err(e) = self.policy.check(&config) {
+            return Err(error(
+                403,
+                format!("{e}, add it to the profiles or pass '--allow-server'"),
+            ));
+        }
+        Ok(config)
+    }
+
+    fn launch(&mut self, request: &mut Request) -> Reply {
+        self.update();
+        if self.launched.as_ref().is_some_and(|l| l.exit.is_none()) {
+            return error(409, "the game is already running");
+        }
+        let mut body = String::new();
+        if let Err(e) = request.as_reader().take(MAX_BODY).read_to_string(&mut body) {
+            return error(400, e);
+        }
+        let config = match self.launch_config(&body) {
+            Ok(config) => config,
+            Err(reply) => return reply,
+        };
+        let cli_path = std::env::current_exe().unwrap_or_default();
+        match launch_detached_with(&cli_path, config) {
+            Ok(session) => {
+                let pid = session.id();
+                self.launched = Some(Launched {
+                    session,
+                    events: vec![],
+                    exit: None,
+                });
+                (202, json!({ "pid": pid }))
+            }
+            Err(e) => error(500, e),
+        }
+    }
+
+    fn handle(&mut self, request: &mut Request) -> Reply {
+        if !self.host_allowed(request) || !self.origin_allowed(request) {
+            return error(403, "forbidden");
+        }
+        if *request.method() == Method::Options {
+            return (204, json!(null));
+        }
+        if !self.authorized(request) {
+            return error(401, "missing or invalid token");
+        }
+        let path = request
+            .url()
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        match (request.method(), path.as_str()) {
+            (Method::Post, "/launch") => self.launch(request),
+            (Method::Get, "/status") => self.status(),
+            (Method::Get, "/profiles") => (200, json!(self.config.profiles)),
+            (Method::Get, "/settings") => {
+                let mhf_folder = self
+                    .config
+                    .mhf_folder
+                    .clone()
+                    .or_else(|| std::env::current_dir().ok())
+                    .unwrap_or_default();
+                (200, json!(MhfSettings::load(&mhf_folder)))
+            }
+            _ => error(404, "not found"),
+        }
+    }
+
+    fn respond(&self, request: Request, (code, body): Reply) {
+        let mut response = match body.is_null() {
+            true => Response::from_string(""),
+            false => Response::from_string(body.to_string()).with_header(
+                Header::from_bytes("Content-Type", "application/json").expect("valid header"),
+            ),
+        }
+        .with_status_code(code);
+        if let Some(origin) = header(&request, "Origin") {
+            if self.config.allow_origins.iter().any(|o| o == origin) {
+                for (field, value) in [
+                    ("Access-Control-Allow-Origin", origin),
+                    (
+                        "Access-Control-Allow-Headers",
+                        "Authorization, Content-Type",
+                    ),
+                    ("Access-Control-Allow-Methods", "GET, POST"),
+                    ("Vary", "Origin"),
+                ] {
+                    if let Ok(header) = Header::from_bytes(field, value) {
+                        response.add_header(header);
+                    }
+                }
+            }
+        }
+        let _ = request.respond(response);
+    }
+}
+
+fn allowed_server(server: &str) -> AllowedServer {
+    match server.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => match port.parse() {
+            Ok(port) => AllowedServer {
+                host: host.to_owned(),
+                port: Some(port),
+            },
+            Err(_) => AllowedServer {
+                host: server.to_owned(),
+                port: None,
+            },
+        },
+        _ => AllowedServer {
+            host: server.to_owned(),
+            port: None,
+        },
+    }
+}
+
+// Stricter than what `run` checks by default since requests come from web pages: loopback, the
+// profiles and `--allow-server`
+fn server_policy(config: &ServeConfig) -> Policy {
+    let loopback = LOOPBACK_HOSTS.iter().map(|host| AllowedServer {
+        host: (*host).to_owned(),
+        port: None,
+    });
+    let profiles = config.profiles.iter().map(|p| AllowedServer {
+        host: p.server_host.clone(),
+        port: Some(p.server_port),
+    });
+    let allowed = config.allow_servers.iter().map(|s| allowed_server(s));
+    Policy {
+        allowed_servers: Some(loopback.chain(profiles).chain(allowed).collect()),
+        trusted_keys: config
+            .profiles
+            .iter()
+            .filter_map(Profile::trusted_key)
+            .collect(),
+    }
+}
+
+/// Serves the HTTP API on 127.0.0.1 until the process is killed.
+pub fn serve(config: ServeConfig) -> ! {
+    let server = Server::http(("127.0.0.1", config.port))
+        .unwrap_or_else(|e| output::fail(ExitCode::Serve, format!("unable to start server: {e}")));
+    let port = server
+        .server_addr()
+        .to_ip()
+        .map_or(config.port, |addr| addr.port());
+    let token = new_token();
+    if let Some(token_file) = &config.token_file {
+        if let Err(e) = std::fs::write(token_file, &token) {
+            output::fail(ExitCode::Serve, format!("unable to write token file: {e}"));
+        }
+    }
+    // Printed as soon as it's ready, since `serve` never returns
+    output::event(json!({ "address": format!("http://127.0.0.1:{port}"), "token": token }));
+    let mut state = State {
+        policy: server_policy(&config),
+        config,
+        token,
+        port,
+        launched: None,
+    };
+    for mut request in server.incoming_requests() {
+        let reply = state.handle(&mut request);
+        state.respond(request, reply);
+    }
+    output::fail(ExitCode::Serve, "server stopped")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use ed25519_dalek::SigningKey;
+    use tiny_http::TestRequest;
+
+    const TOKEN: &str = "secret";
+    const SECRET_KEY: [u8; 32] = [7; 32];
+
+    fn state() -> State {
+        let config = ServeConfig {
+            port: 8000,
+            allow_origins: vec!["https://mhf.example.com".to_owned()],
+            allow_servers: vec!["10.0.0.3:53312".to_owned()],
+            token_file: None,
+            mhf_folder: None,
+            profiles: vec![Profile {
+                name: "Main".to_owned(),
+                server_host: "10.0.0.2".to_owned(),
+                server_port: 53312,
+                version: MhfVersion::F5,
+                endpoint: None,
+                public_key: Some(
+                    STANDARD.encode(
+                        SigningKey::from_bytes(&SECRET_KEY)
+                            .verifying_key()
+                            .to_bytes(),
+                    ),
+                ),
+            }],
+        };
+        State {
+            policy: server_policy(&config),
+            config,
+            token: TOKEN.to_owned(),
+            port: 8000,
+            launched: None,
+        }
+    }
+
+    fn header(field: &str, value: &str) -> Header {
+        Header::from_bytes(field, value).unwrap()
+    }
+
+    fn request(method: Method, path: &str) -> TestRequest {
+        TestRequest::new()
+            .with_method(method)
+            .with_path(path)
+            .with_header(header("Host", "127.0.0.1:8000"))
+            .with_header(header("Authorization", &format!("Bearer {TOKEN}")))
+    }
+
+    fn handle(request: TestRequest) -> Reply {
+        state().handle(&mut request.into())
+    }
+
+    // The body has to outlive the request
+    fn launch(body: Value) -> Reply {
+        let body: &'static str = Box::leak(body.to_string().into_boxed_str());
+        handle(request(Method::Post, "/launch").with_body(body))
+    }
+
+    #[test]
+    fn hosts() {
+        assert_eq!(handle(request(Method::Get, "/profiles")).0, 200);
+        for host in ["localhost:8000", "127.0.0.1:8000"] {
+            let request = request(Method::Get, "/status").with_header(header("Host", host));
+            assert_eq!(handle(request).0, 200, "{host}");
+        }
+        let host = |host| {
+            TestRequest::new()
+                .with_path("/status")
+                .with_header(header("Host", host))
+                .with_header(header("Authorization", &format!("Bearer {TOKEN}")))
+        };
+        for name in ["evil.example.com:8000", "127.0.0.1:8001", "127.0.0.1"] {
+            assert_eq!(handle(host(name)), error(403, "forbidden"), "{name}");
+        }
+        let no_host = TestRequest::new()
+            .with_path("/status")
+            .with_header(header("Authorization", &format!("Bearer {TOKEN}")));
+        assert_eq!(handle(no_host).0, 403);
+    }
+
+    #[test]
+    fn origins() {
+        let origin = |origin| request(Method::Get, "/status").with_header(header("Origin", origin));
+        assert_eq!(handle(origin("https://mhf.example.com")).0, 200);
+        assert_eq!(handle(origin("https://evil.example.com")).0, 403);
+        assert_eq!(handle(origin("null")).0, 403);
+        // Preflights don't carry the token
+        let preflight = TestRequest::new()
+            .with_method(Method::Options)
+            .with_path("/launch")
+            .with_header(header("Host", "127.0.0.1:8000"))
+            .with_header(header("Origin", "https://mhf.example.com"));
+        assert_eq!(handle(preflight).0, 204);
+    }
+
+    #[test]
+    fn tokens() {
+        let token = |value| {
+            TestRequest::new()
+                .with_path("/status")
+                .with_header(header("Host", "127.0.0.1:8000"))
+                .with_header(header("Authorization", value))
+        };
+        assert_eq!(handle(token("Bearer secret")).0, 200);
+        for value in [
+            "Bearer secreT",
+            "Bearer secre",
+            "Bearer secrets",
+            "secret",
+            "Basic secret",
+        ] {
+            assert_eq!(handle(token(value)).0, 401, "{value}");
+        }
+        let none = TestRequest::new()
+            .with_path("/status")
+            .with_header(header("Host", "127.0.0.1:8000"));
+        assert_eq!(handle(none).0, 401);
+        assert!(token_matches("abc", "abc"));
+        assert!(!token_matches("abd", "abc"));
+        assert!(!token_matches("", "abc"));
+        assert_eq!(handle(request(Method::Get, "/nothing")).0, 404);
+    }
+
+    #[test]
+    fn launch_checks() {
+        let config = |extra: Value| {
+            let mut config = json!({ "char_id": 1, "user_token": "0123456789abcdef" });
+            config
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            config
+        };
+        for (field, value) in [
+            ("version", json!("ZZ")),
+            ("mhf_folder", json!("C:/elsewhere")),
+            ("mhf_flags", json!(["Selfup"])),
+            ("research", json!([{ "field": "user_rights", "value": 1 }])),
+        ] {
+            assert_eq!(
+                launch(config(json!({ field: value }))),
+                error(403, format!("'{field}' can't be set through the API")),
+            );
+            // Nor through a signed ticket
+            let ticket = SignedTicket::sign(
+                config(json!({ field: value })).as_object().unwrap().clone(),
+                &SECRET_KEY,
+            );
+            assert_eq!(launch(json!(ticket)).0, 403, "{field}");
+        }
+
+        let (code, body) = launch(config(json!({ "server_host": "10.0.0.9" })));
+        assert_eq!(code, 403);
+        assert!(
+            body["error"].as_str().unwrap().contains("--allow-server"),
+            "{body}"
+        );
+        assert_eq!(
+            launch(config(
+                json!({ "server_host": "10.0.0.3", "server_port": 1 })
+            ))
+            .0,
+            403
+        );
+
+        assert_eq!(launch(json!({ "char_id": 1 })).0, 400);
+        assert_eq!(state().launch_config("{").unwrap_err().0, 400);
+        assert_eq!(launch(json!("not a config")).0, 400);
+        let ticket = SignedTicket::sign(config(json!({})).as_object().unwrap().clone(), &[8; 32]);
+        assert_eq!(launch(json!(ticket)).0, 400);
+    }
+
+    #[test]
+    fn launch_config() {
+        let mut state = state();
+        state.config.mhf_folder = Some("C:/MHF".into());
+        let config = |server_host| {
+            json!({ "char_id": 1, "user_token": "0123456789abcdef", "server_host": server_host })
+                .to_string()
+        };
+        let main = state.launch_config(&config("10.0.0.2")).unwrap();
+        assert_eq!(main.mhf_folder, Some("C:/MHF".into()));
+        assert_eq!(main.version, MhfVersion::F5);
+        let other = state.launch_config(&config("10.0.0.3")).unwrap();
+        assert_eq!(other.version, MhfVersion::default());
+
+        let fields = json!({ "char_id": 1, "user_token": "0123456789abcdef" });
+        let ticket = SignedTicket::sign(fields.as_object().unwrap().clone(), &SECRET_KEY);
+        let signed = state.launch_config(&json!(ticket).to_string()).unwrap();
+        assert_eq!(signed.mhf_folder, Some("C:/MHF".into()));
+    }
+}
@@ -0,0 +1,328 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_yaml::{Mapping, Value as Yaml};
+
+use crate::profiles::Profile;
+use crate::vdf::{self, Value};
+use crate::wine::JAPANESE_FACES;
+
+const LOCALE: &str = "ja_JP.UTF-8";
+const FONT: &str = "Noto Sans CJK JP";
+
+pub struct ShortcutConfig<'a> {
+    pub profile: &'a Profile,
+    pub mhf_folder: PathBuf,
+    pub exe: PathBuf,
+}
+
+#[derive(Serialize)]
+pub struct SteamShortcut {
+    pub appid: u32,
+    pub name: String,
+    pub vdf: PathBuf,
+    /// To merge into `config/config.vdf`, Steam keeps the compatibility tool there
+    pub compat_tool_mapping: String,
+}
+
+fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    match !arg.is_empty() && arg.chars().all(safe) {
+        true => arg.to_owned(),
+        false => format!("'{}'", arg.replace('\'', r"'\''")),
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+impl ShortcutConfig<'_> {
+    fn name(&self) -> String {
+        format!("Monster Hunter Frontier ({})", self.profile.name)
+    }
+
+    /// Logs in through the profile's endpoint when it has one, otherwise runs with 'config.json'
+    /// from the game folder pointed at the profile's server.
+    fn args(&self) -> Vec<String> {
+        let profile = self.profile;
+        let version = profile.version.to_string();
+        match &profile.endpoint {
+            Some(endpoint) => vec![
+                "--interactive".to_owned(),
+                "--endpoint".to_owned(),
+                endpoint.clone(),
+                "--server-host".to_owned(),
+                profile.server_host.clone(),
+                "--server-port".to_owned(),
+                profile.server_port.to_string(),
+                "--mhf-version".to_owned(),
+                version,
+            ],
+            None => vec![
+                "--set".to_owned(),
+                format!("server_host={}", profile.server_host),
+                "--set".to_owned(),
+                format!("server_port={}", profile.server_port),
+                "--set".to_owned(),
+                format!("version={version}"),
+            ],
+        }
+    }
+
+    fn args_line(&self) -> String {
+        self.args()
+            .iter()
+            .map(|a| shell_quote(a))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Lutris installer using its Wine runner on the existing game folder.
+    pub fn lutris(&self) -> String {
+        let slug = format!(
+            "monster-hunter-frontier-{}",
+            self.profile
+                .name
+                .to_ascii_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "-")
+        );
+        let prefix = self.mhf_folder.join("prefix").display().to_string();
+        let mut installer = vec![yaml_map([(
+            "task",
+            yaml_map([
+                ("name", "create_prefix".into()),
+                ("prefix", prefix.clone().into()),
+            ]),
+        )])];
+        for face in JAPANESE_FACES {
+            installer.push(yaml_map([(
+                "task",
+                yaml_map([
+                    ("name", "set_regedit".into()),
+                    ("prefix", prefix.clone().into()),
+                    (
+                        "path",
+                        r"HKEY_CURRENT_USER\Software\Wine\Fonts\Replacements".into(),
+                    ),
+                    ("key", (*face).into()),
+                    ("value", FONT.into()),
+                ]),
+            )]));
+        }
+        let script = yaml_map([
+            (
+                "game",
+                yaml_map([
+                    ("exe", self.exe.display().to_string().into()),
+                    ("args", self.args_line().into()),
+                    ("prefix", prefix.into()),
+                    ("working_dir", self.mhf_folder.display().to_string().into()),
+                ]),
+            ),
+            (
+                "system",
+                yaml_map([(
+                    "env",
+                    yaml_map([("LANG", LOCALE.into()), ("LC_ALL", LOCALE.into())]),
+                )]),
+            ),
+            ("wine", yaml_map([("dxvk", false.into())])),
+            ("installer", Yaml::Sequence(installer)),
+        ]);
+        let document = yaml_map([
+            ("name", self.name().into()),
+            ("game_slug", slug.clone().into()),
+            ("version", "mhf-iel".into()),
+            ("slug", format!("{slug}-mhf-iel").into()),
+            ("runner", "wine".into()),
+            ("script", script),
+        ]);
+        serde_yaml::to_string(&document).unwrap_or_default()
+    }
+
+    fn steam_entry(&self, appid: u32) -> Value {
+        let quoted = |p: &Path| format!("\"{}\"", p.display());
+        let string = |s: String| Value::String(s);
+        Value::Map(vec![
+            ("appid".to_owned(), Value::Int(appid)),
+            ("AppName".to_owned(), string(self.name())),
+            ("Exe".to_owned(), string(quoted(&self.exe))),
+            ("StartDir".to_owned(), string(quoted(&self.mhf_folder))),
+            ("icon".to_owned(), string(String::new())),
+            ("ShortcutPath".to_owned(), string(String::new())),
+            (
+                "LaunchOptions".to_owned(),
+                string(format!(
+                    "LANG={LOCALE} LC_ALL={LOCALE} %command% {}",
+                    self.args_line()
+                )),
+            ),
+            ("IsHidden".to_owned(), Value::Int(0)),
+            ("AllowDesktopConfig".to_owned(), Value::Int(1)),
+            ("AllowOverlay".to_owned(), Value::Int(1)),
+            ("OpenVR".to_owned(), Value::Int(0)),
+            ("Devkit".to_owned(), Value::Int(0)),
+            ("DevkitGameID".to_owned(), string(String::new())),
+            ("DevkitOverrideAppID".to_owned(), Value::Int(0)),
+            ("LastPlayTime".to_owned(), Value::Int(0)),
+            ("FlatpakAppID".to_owned(), string(String::new())),
+            ("tags".to_owned(), Value::Map(vec![])),
+        ])
+    }
+
+    /// Adds the shortcut to `vdf`, replacing an older one for the same profile and game folder.
+    pub fn steam(&self, vdf: &Path, compat_tool: &str) -> Result<SteamShortcut, String> {
+        // The ID Steam gives non-Steam games, which it finds them by
+        let exe = format!("\"{}\"", self.exe.display());
+        let appid = crc32(format!("{exe}{}", self.name()).as_bytes()) | 0x80000000;
+        let mut root = match std::fs::read(vdf) {
+            Ok(data) => {
+                vdf::read(&data).map_err(|e| format!("error parsing '{}': {e}", vdf.display()))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(format!("unable to read '{}': {e}", vdf.display())),
+        };
+        if !root.iter().any(|(key, _)| key == "shortcuts") {
+            root.push(("shortcuts".to_owned(), Value::Map(vec![])));
+        }
+        let Some((_, Value::Map(shortcuts))) = root.iter_mut().find(|(key, _)| key == "shortcuts")
+        else {
+            return Err(format!("'{}' has no shortcuts", vdf.display()));
+        };
+        shortcuts.retain(|(_, entry)| entry.get("appid") != Some(&Value::Int(appid)));
+        shortcuts.push((String::new(), self.steam_entry(appid)));
+        // Entries are keyed by their index
+        for (i, (key, _)) in shortcuts.iter_mut().enumerate() {
+            *key = i.to_string();
+        }
+        if vdf.exists() {
+            let backup = vdf.with_extension("vdf.bak");
+            std::fs::copy(vdf, &backup)
+                .map_err(|e| format!("unable to back up '{}': {e}", vdf.display()))?;
+        }
+        std::fs::write(vdf, vdf::write(&root))
+            .map_err(|e| format!("unable to write '{}': {e}", vdf.display()))?;
+        Ok(SteamShortcut {
+            appid,
+            name: self.name(),
+            vdf: vdf.to_path_buf(),
+            compat_tool_mapping: format!(
+                "\"CompatToolMapping\"\n{{\n\t\"{appid}\"\n\t{{\n\t\t\"name\"\t\t\"{compat_tool}\"\n\t\t\"config\"\t\t\"\"\n\t\t\"priority\"\t\t\"250\"\n\t}}\n}}\n"
+            ),
+        })
+    }
+}
+
+fn yaml_map<const N: usize>(entries: [(&str, Yaml); N]) -> Yaml {
+    Yaml::Mapping(Mapping::from_iter(
+        entries.into_iter().map(|(k, v)| (Yaml::from(k), v)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mhf_iel::MhfVersion;
+
+    fn profile() -> Profile {
+        Profile {
+            name: "Main".to_owned(),
+            server_host: "10.0.0.2".to_owned(),
+            server_port: 53312,
+            version: MhfVersion::ZZ,
+            endpoint: None,
+            public_key: None,
+        }
+    }
+
+    fn shortcuts(vdf: &Path) -> Vec<(String, Value)> {
+        match vdf::read(&std::fs::read(vdf).unwrap()).unwrap().remove(0) {
+            (key, Value::Map(shortcuts)) if key == "shortcuts" => shortcuts,
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn steam_replaces_its_own_entry() {
+        let dir = std::env::temp_dir().join(format!("mhf-iel-shortcut-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let vdf = dir.join("shortcuts.vdf");
+        let profile = profile();
+        let config = ShortcutConfig {
+            profile: &profile,
+            mhf_folder: "/games/mhf".into(),
+            exe: "/games/mhf/mhf-iel-cli.exe".into(),
+        };
+
+        // Made from scratch
+        let first = config.steam(&vdf, "proton_9").unwrap();
+        assert_eq!(first.name, "Monster Hunter Frontier (Main)");
+        assert_ne!(first.appid & 0x80000000, 0);
+        assert!(first.compat_tool_mapping.contains("\"proton_9\""));
+        assert!(!dir.join("shortcuts.vdf.bak").exists());
+
+        // Someone else's shortcut in front of it, which has to come through untouched
+        let other = Value::Map(vec![
+            ("appid".to_owned(), Value::Int(1234)),
+            ("AppName".to_owned(), Value::String("Other".to_owned())),
+            (
+                "scale".to_owned(),
+                Value::Other(0x03, vec![0, 0, 0x80, 0x3f]),
+            ),
+        ]);
+        let mut entries = shortcuts(&vdf);
+        entries.insert(0, ("x".to_owned(), other.clone()));
+        let before = vdf::write(&[("shortcuts".to_owned(), Value::Map(entries))]);
+        std::fs::write(&vdf, &before).unwrap();
+
+        let second = config.steam(&vdf, "proton_9").unwrap();
+        assert_eq!(second.appid, first.appid);
+        assert_eq!(
+            std::fs::read(dir.join("shortcuts.vdf.bak")).unwrap(),
+            before
+        );
+        let entries = shortcuts(&vdf);
+        let keys: Vec<&str> = entries.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["0", "1"]);
+        assert_eq!(entries[0].1, other);
+        let entry = &entries[1].1;
+        assert_eq!(entry.get("appid"), Some(&Value::Int(first.appid)));
+        assert_eq!(
+            entry.get("Exe"),
+            Some(&Value::String("\"/games/mhf/mhf-iel-cli.exe\"".to_owned()))
+        );
+        let Some(Value::String(options)) = entry.get("LaunchOptions") else {
+            panic!("{entry:?}");
+        };
+        assert!(options.starts_with("LANG=ja_JP.UTF-8 LC_ALL=ja_JP.UTF-8 %command% --set"));
+
+        std::fs::write(&vdf, b"\x05").unwrap();
+        let Err(e) = config.steam(&vdf, "proton_9") else {
+            panic!("parsed an invalid file");
+        };
+        assert!(e.starts_with("error parsing"), "{e}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(shell_quote("server_host=10.0.0.2"), "server_host=10.0.0.2");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("it's here"), r"'it'\''s here'");
+    }
+}
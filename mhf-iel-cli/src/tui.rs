@@ -0,0 +1,127 @@
+use mhf_iel::notice;
+use mhf_iel::signv2::{AuthData, Character, Signv2Client};
+use mhf_iel::{MhfConfig, MhfVersion};
+
+use dialoguer::{theme::ColorfulTheme, Input, Password, Select};
+
+pub struct TuiConfig {
+    pub endpoint: String,
+    pub server_host: Option<String>,
+    pub server_port: u32,
+    pub version: MhfVersion,
+}
+
+struct Session {
+    client: Signv2Client,
+    auth: AuthData,
+    user_name: String,
+    user_password: String,
+}
+
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AllocConsole, AttachConsole, ATTACH_PARENT_PROCESS};
+    // Release builds use the GUI subsystem, so there's no console unless we ask for one
+    unsafe {
+        if AttachConsole(ATTACH_PARENT_PROCESS).is_err() {
+            let _ = AllocConsole();
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+fn login(client: Signv2Client, theme: &ColorfulTheme) -> Option<Session> {
+    loop {
+        let action = Select::with_theme(theme)
+            .with_prompt(format!("MHF @ {}", client.endpoint()))
+            .items(&["Login", "Create Account", "Quit"])
+            .default(0)
+            .interact()
+            .ok()?;
+        if action == 2 {
+            return None;
+        }
+        let user_name: String = Input::with_theme(theme)
+            .with_prompt("Username")
+            .interact_text()
+            .ok()?;
+        let user_password = Password::with_theme(theme)
+            .with_prompt("Password")
+            .interact()
+            .ok()?;
+        let result = match action {
+            0 => client.login(&user_name, &user_password),
+            _ => client.register(&user_name, &user_password),
+        };
+        match result {
+            Ok(auth) => {
+                return Some(Session {
+                    client,
+                    auth,
+                    user_name,
+                    user_password,
+                })
+            }
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
+
+fn select_character(session: &mut Session, theme: &ColorfulTheme) -> Option<(Character, bool)> {
+    loop {
+        let mut items: Vec<String> = session
+            .auth
+            .characters
+            .iter()
+            .map(|c| format!("{} (HR {}, GR {})", c.name, c.hr, c.gr))
+            .collect();
+        items.push("Create New Character".to_owned());
+        items.push("Quit".to_owned());
+        let selected = Select::with_theme(theme)
+            .with_prompt("Select a character")
+            .items(&items)
+            .default(0)
+            .interact()
+            .ok()?;
+        let characters = &session.auth.characters;
+        if selected < characters.len() {
+            return Some((characters[selected].clone(), false));
+        }
+        if selected == characters.len() + 1 {
+            return None;
+        }
+        match session.client.create_character(&session.auth.user.token) {
+            Ok(character) => return Some((character, true)),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
+
+/// Logs into signv2server and boots the selected character, replacing `gui.py`.
+pub fn run(tui_config: TuiConfig) -> Option<MhfConfig> {
+    attach_console();
+    let theme = ColorfulTheme::default();
+    let client = Signv2Client::new(&tui_config.endpoint);
+    let server_host = tui_config
+        .server_host
+        .unwrap_or_else(|| client.host().to_owned());
+
+    let mut session = login(client, &theme)?;
+    for notice in &session.auth.notices {
+        println!("{}\n", notice::preview(&notice::parse(notice)));
+    }
+    let (character, char_new) = select_character(&mut session, &theme)?;
+    Some(MhfConfig {
+        server_host,
+        server_port: tui_config.server_port,
+        version: tui_config.version,
+        ..session.auth.to_config(
+            &character,
+            char_new,
+            &session.user_name,
+            &session.user_password,
+        )
+    })
+}
@@ -0,0 +1,173 @@
+// Steam's binary VDF, the format of `shortcuts.vdf`
+
+const MAP: u8 = 0x00;
+const STRING: u8 = 0x01;
+const INT: u8 = 0x02;
+const FLOAT: u8 = 0x03;
+const UINT64: u8 = 0x07;
+const END: u8 = 0x08;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Map(Vec<(String, Value)>),
+    String(String),
+    Int(u32),
+    // Types shortcuts don't use, kept as is so rewriting a file doesn't lose them
+    Other(u8, Vec<u8>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or("unexpected end of file")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.data[self.pos..]
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or("unterminated string")?;
+        let s = std::str::from_utf8(&self.data[self.pos..self.pos + len])
+            .map_err(|e| e.to_string())?
+            .to_owned();
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    fn entries(&mut self) -> Result<Vec<(String, Value)>, String> {
+        let mut entries = vec![];
+        loop {
+            let kind = self.take(1)?[0];
+            if kind == END {
+                return Ok(entries);
+            }
+            let key = self.string()?;
+            let value = match kind {
+                MAP => Value::Map(self.entries()?),
+                STRING => Value::String(self.string()?),
+                INT => Value::Int(u32::from_le_bytes(
+                    self.take(4)?.try_into().expect("4 bytes"),
+                )),
+                FLOAT => Value::Other(kind, self.take(4)?.to_vec()),
+                UINT64 => Value::Other(kind, self.take(8)?.to_vec()),
+                kind => return Err(format!("unknown type {kind:#04x} at {}", self.pos)),
+            };
+            entries.push((key, value));
+        }
+    }
+}
+
+pub fn read(data: &[u8]) -> Result<Vec<(String, Value)>, String> {
+    let mut reader = Reader { data, pos: 0 };
+    reader.entries()
+}
+
+fn write_entries(entries: &[(String, Value)], out: &mut Vec<u8>) {
+    for (key, value) in entries {
+        let kind = match value {
+            Value::Map(_) => MAP,
+            Value::String(_) => STRING,
+            Value::Int(_) => INT,
+            Value::Other(kind, _) => *kind,
+        };
+        out.push(kind);
+        out.extend(key.as_bytes());
+        out.push(0);
+        match value {
+            Value::Map(entries) => write_entries(entries, out),
+            Value::String(s) => {
+                out.extend(s.as_bytes());
+                out.push(0);
+            }
+            Value::Int(n) => out.extend(n.to_le_bytes()),
+            Value::Other(_, bytes) => out.extend(bytes),
+        }
+    }
+    out.push(END);
+}
+
+pub fn write(entries: &[(String, Value)]) -> Vec<u8> {
+    let mut out = vec![];
+    write_entries(entries, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 'shortcuts.vdf' with one shortcut, and a float and a uint64 Steam may add
+    const FILE: &[u8] = b"\x00shortcuts\x00\
+        \x000\x00\
+        \x02appid\x00\x01\x02\x03\x84\
+        \x01AppName\x00Game\x00\
+        \x03scale\x00\x00\x00\x80\x3f\
+        \x07big\x00\x01\x02\x03\x04\x05\x06\x07\x08\
+        \x00tags\x00\x010\x00fav\x00\x08\
+        \x08\
+        \x08\
+        \x08";
+
+    #[test]
+    fn round_trip() {
+        let entries = read(FILE).unwrap();
+        let shortcut = Value::Map(vec![
+            ("appid".to_owned(), Value::Int(0x84030201)),
+            ("AppName".to_owned(), Value::String("Game".to_owned())),
+            (
+                "scale".to_owned(),
+                Value::Other(FLOAT, 1f32.to_le_bytes().to_vec()),
+            ),
+            ("big".to_owned(), Value::Other(UINT64, (1..=8).collect())),
+            (
+                "tags".to_owned(),
+                Value::Map(vec![("0".to_owned(), Value::String("fav".to_owned()))]),
+            ),
+        ]);
+        assert_eq!(
+            entries,
+            [(
+                "shortcuts".to_owned(),
+                Value::Map(vec![("0".to_owned(), shortcut)])
+            )]
+        );
+        assert_eq!(write(&entries), FILE);
+        assert_eq!(
+            entries[0].1.get("0").and_then(|s| s.get("appid")),
+            Some(&Value::Int(0x84030201))
+        );
+        assert_eq!(read(b"\x08").unwrap(), []);
+        assert_eq!(write(&[]), b"\x08");
+    }
+
+    #[test]
+    fn invalid() {
+        for end in 0..FILE.len() - 1 {
+            assert!(read(&FILE[..end]).is_err(), "{end}");
+        }
+        assert_eq!(
+            read(b"\x05key\x00\x08").unwrap_err(),
+            "unknown type 0x05 at 5"
+        );
+        assert_eq!(read(b"\x01key").unwrap_err(), "unterminated string");
+        assert!(read(b"\x01key\x00\xff\x00\x08").is_err());
+    }
+}
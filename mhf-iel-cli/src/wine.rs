@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use clap::ValueEnum;
+use mhf_iel::MhfConfig;
+use serde::Serialize;
+
+use crate::output::{self, ExitCode, OutputFormat};
+
+// Faces the game asks for, which Wine doesn't ship
+pub const JAPANESE_FACES: &[&str] = &[
+    "MS Gothic",
+    "MS PGothic",
+    "MS UI Gothic",
+    "ＭＳ ゴシック",
+    "ＭＳ Ｐゴシック",
+];
+
+pub struct WineConfig {
+    pub prefix: Option<PathBuf>,
+    pub wine: Option<PathBuf>,
+    pub proton: Option<PathBuf>,
+    pub exe: Option<PathBuf>,
+    pub dxvk: bool,
+    pub dll_overrides: Vec<String>,
+    pub locale: String,
+    pub font: Option<String>,
+    pub dry_run: bool,
+    pub output: OutputFormat,
+    pub events: bool,
+}
+
+enum Runner {
+    Wine(PathBuf),
+    // Proton keeps the Wine prefix in 'pfx' inside its compat data folder
+    Proton(PathBuf),
+}
+
+/// A process `wine` starts, printed as is by `--dry-run`.
+#[derive(Debug, Serialize)]
+pub struct WineCommand {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+}
+
+impl WineCommand {
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).envs(&self.env);
+        command
+    }
+
+    fn status(&self) -> Result<i32, String> {
+        let status = self
+            .command()
+            .status()
+            .map_err(|e| format!("unable to start '{}': {e}", self.program.display()))?;
+        status
+            .code()
+            .ok_or_else(|| format!("'{}' was killed", self.program.display()))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DryRun {
+    prefix: PathBuf,
+    create_prefix: bool,
+    setup: Vec<WineCommand>,
+    launch: WineCommand,
+}
+
+// Wine maps Z: to the Linux root
+pub fn wine_path(path: &Path) -> String {
+    let path = path.display().to_string();
+    match path.starts_with('/') {
+        true => format!("Z:{}", path.replace('/', "\\")),
+        false => path,
+    }
+}
+
+fn data_dir() -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .unwrap_or_default()
+        .join("mhf-iel")
+}
+
+// Bare names are looked up in PATH, paths are made absolute since the game runs from its folder
+fn program(path: PathBuf) -> PathBuf {
+    match path.components().count() > 1 {
+        true => std::env::current_dir().unwrap_or_default().join(path),
+        false => path,
+    }
+}
+
+impl WineConfig {
+    fn runner(&self) -> Runner {
+        match &self.proton {
+            Some(proton) => Runner::Proton(program(match proton.is_dir() {
+                true => proton.join("proton"),
+                false => proton.clone(),
+            })),
+            None => Runner::Wine(program(
+                self.wine
+                    .clone()
+                    .or_else(|| std::env::var_os("WINE").map(PathBuf::from))
+                    .unwrap_or_else(|| "wine".into()),
+            )),
+        }
+    }
+
+    fn prefix(&self, runner: &Runner) -> PathBuf {
+        if let Some(prefix) = &self.prefix {
+            return std::env::current_dir().unwrap_or_default().join(prefix);
+        }
+        match runner {
+            Runner::Wine(_) => std::env::var_os("WINEPREFIX")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| data_dir().join("prefix")),
+            Runner::Proton(_) => std::env::var_os("STEAM_COMPAT_DATA_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| data_dir().join("proton")),
+        }
+    }
+
+    fn exe(&self) -> Result<PathBuf, String> {
+        if let Some(exe) = &self.exe {
+            return Ok(std::env::current_dir().unwrap_or_default().join(exe));
+        }
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.parent()?.join("mhf-iel-cli.exe")))
+            .filter(|exe| exe.is_file())
+            .ok_or_else(|| "no 'mhf-iel-cli.exe' next to this executable, pass '--exe'".to_owned())
+    }
+
+    fn env(&self, runner: &Runner, prefix: &Path) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::new();
+        let mut overrides = vec!["winemenubuilder.exe=d".to_owned()];
+        match runner {
+            Runner::Wine(_) => {
+                env.insert("WINEPREFIX".to_owned(), prefix.display().to_string());
+                if self.dxvk {
+                    overrides.push("d3d9=n,b".to_owned());
+                }
+            }
+            Runner::Proton(_) => {
+                env.insert(
+                    "STEAM_COMPAT_DATA_PATH".to_owned(),
+                    prefix.display().to_string(),
+                );
+                // Proton only needs it to exist when running outside of Steam
+                let steam =
+                    std::env::var("STEAM_COMPAT_CLIENT_INSTALL_PATH").unwrap_or_else(|_| {
+                        data_dir()
+                            .parent()
+                            .unwrap_or(Path::new(""))
+                            .join("Steam")
+                            .display()
+                            .to_string()
+                    });
+                env.insert("STEAM_COMPAT_CLIENT_INSTALL_PATH".to_owned(), steam);
+                if !self.dxvk {
+                    env.insert("PROTON_USE_WINED3D".to_owned(), "1".to_owned());
+                }
+            }
+        }
+        overrides.extend(self.dll_overrides.iter().cloned());
+        env.insert("WINEDLLOVERRIDES".to_owned(), overrides.join(";"));
+        if std::env::var_os("WINEDEBUG").is_none() {
+            env.insert("WINEDEBUG".to_owned(), "-all".to_owned());
+        }
+        env.insert("LANG".to_owned(), self.locale.clone());
+        env.insert("LC_ALL".to_owned(), self.locale.clone());
+        env
+    }
+
+    fn wine_command(&self, runner: &Runner, prefix: &Path, args: Vec<String>) -> WineCommand {
+        let (program, args) = match runner {
+            Runner::Wine(wine) => (wine.clone(), args),
+            Runner::Proton(proton) => (proton.clone(), [vec!["run".to_owned()], args].concat()),
+        };
+        WineCommand {
+            program,
+            args,
+            env: self.env(runner, prefix),
+        }
+    }
+}
+
+// regedit only reads non-ASCII names from UTF-16 files
+fn fonts_reg(font: &str) -> Vec<u8> {
+    let mut reg = "Windows Registry Editor Version 5.00\r\n\r\n\
+                   [HKEY_CURRENT_USER\\Software\\Wine\\Fonts\\Replacements]\r\n"
+        .to_owned();
+    for face in JAPANESE_FACES {
+        reg.push_str(&format!("\"{face}\"=\"{font}\"\r\n"));
+    }
+    let mut data = vec![0xff, 0xfe];
+    data.extend(reg.encode_utf16().flat_map(u16::to_le_bytes));
+    data
+}
+
+fn fail(message: impl Display) -> ! {
+    output::fail(ExitCode::Wine, message)
+}
+
+/// Runs the Windows CLI through Wine or Proton with `config`, exiting with its exit code.
+pub fn run(wine: WineConfig, mut config: MhfConfig) -> ! {
+    let runner = wine.runner();
+    let prefix = wine.prefix(&runner);
+    let exe = wine.exe().unwrap_or_else(|e| fail(e));
+    let mhf_folder = match &config.mhf_folder {
+        Some(folder) => std::env::current_dir().unwrap_or_default().join(folder),
+        None => std::env::current_dir().unwrap_or_default(),
+    };
+    config.mhf_folder = Some(wine_path(&mhf_folder).into());
+
+    let system_reg = match runner {
+        Runner::Wine(_) => prefix.join("system.reg"),
+        Runner::Proton(_) => prefix.join("pfx").join("system.reg"),
+    };
+    let create_prefix = !system_reg.exists();
+    let mut setup = vec![];
+    if create_prefix {
+        if let Runner::Wine(_) = runner {
+            setup.push(wine.wine_command(
+                &runner,
+                &prefix,
+                vec!["wineboot".into(), "--init".into()],
+            ));
+        }
+    }
+    let fonts_file = prefix.join("mhf-iel-fonts.reg");
+    let font = wine
+        .font
+        .clone()
+        .or_else(|| create_prefix.then(|| "Noto Sans CJK JP".to_owned()));
+    if font.is_some() {
+        let args = vec!["regedit".into(), "/S".into(), wine_path(&fonts_file)];
+        setup.push(wine.wine_command(&runner, &prefix, args));
+    }
+
+    let mut args = vec![
+        wine_path(&exe),
+        "run".to_owned(),
+        "--config-stdin".to_owned(),
+        "--output".to_owned(),
+    ];
+    args.extend(
+        wine.output
+            .to_possible_value()
+            .map(|v| v.get_name().to_owned()),
+    );
+    if wine.events {
+        args.push("--events".to_owned());
+    }
+    let launch = wine.wine_command(&runner, &prefix, args);
+
+    if wine.dry_run {
+        output::success(
+            ExitCode::Success,
+            DryRun {
+                prefix,
+                create_prefix,
+                setup,
+                launch,
+            },
+        )
+    }
+    std::fs::create_dir_all(&prefix)
+        .unwrap_or_else(|e| fail(format!("unable to create '{}': {e}", prefix.display())));
+    if let Some(font) = &font {
+        std::fs::write(&fonts_file, fonts_reg(font))
+            .unwrap_or_else(|e| fail(format!("unable to write font replacements: {e}")));
+    }
+    for command in &setup {
+        output::log(format!(
+            "running {} {}",
+            command.program.display(),
+            command.args.join(" ")
+        ));
+        match command.status() {
+            Ok(0) => {}
+            Ok(code) => fail(format!(
+                "'{} {}' exited with {code}",
+                command.program.display(),
+                command.args.join(" ")
+            )),
+            Err(e) => fail(e),
+        }
+    }
+
+    let data = serde_json::to_vec(&config)
+        .unwrap_or_else(|e| fail(format!("unable to serialize config: {e}")));
+    let mut child = launch
+        .command()
+        .current_dir(&mhf_folder)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| {
+            fail(format!(
+                "unable to start '{}': {e}",
+                launch.program.display()
+            ))
+        });
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(e) = stdin.write_all(&data) {
+            output::log(format!("warning: unable to send config: {e}"));
+        }
+    }
+    // The Windows CLI already printed its own result, so only its exit code is passed on
+    match child.wait().map(|status| status.code()) {
+        Ok(Some(code)) => std::process::exit(code),
+        Ok(None) => fail(format!("'{}' was killed", launch.program.display())),
+        Err(e) => fail(e),
+    }
+}
@@ -0,0 +1,21 @@
+//! Runs with update flags, but nowhere to update from.
+
+use std::process::Command;
+
+#[test]
+fn update_flags_without_a_source() {
+    let config = r#"{"char_id":1,"user_token":"0123456789abcdef","mhf_flags":["Selfup"]}"#;
+    let output = Command::new(env!("CARGO_BIN_EXE_mhf-iel-cli"))
+        .args(["--config-data", config, "run"])
+        .env_remove("MHF_IEL_CONFIG")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("no update source, passing the update flags on to the game"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("error updating mhf"), "{stderr}");
+    // Fails further on outside of Windows, but not because of the update
+    assert_ne!(output.status.code(), Some(5), "{stderr}");
+}
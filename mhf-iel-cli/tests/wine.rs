@@ -0,0 +1,192 @@
+#![cfg(unix)]
+//! Runs `wine` against a stub that records how it was called, instead of a real Wine.
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// Logs its arguments and environment, creates the prefix like 'wineboot' would and keeps what it
+// gets on stdin when launching
+const STUB: &str = r#"#!/bin/sh
+dir="$(dirname "$0")"
+{
+    printf 'args=%s\n' "$*"
+    printf 'WINEPREFIX=%s\n' "$WINEPREFIX"
+    printf 'WINEDLLOVERRIDES=%s\n' "$WINEDLLOVERRIDES"
+    printf 'WINEDEBUG=%s\n' "$WINEDEBUG"
+    printf 'LANG=%s\n' "$LANG"
+    printf 'LC_ALL=%s\n' "$LC_ALL"
+    printf 'PWD=%s\n' "$(pwd)"
+} >> "$dir/calls"
+case "$1" in
+    wineboot) touch "$WINEPREFIX/system.reg" ;;
+    regedit) ;;
+    *) cat > "$dir/stdin"; exit 3 ;;
+esac
+"#;
+
+const CONFIG: &str = r#"{"char_id":1,"user_token":"0123456789abcdef","server_host":"10.0.0.2"}"#;
+
+struct Fixture {
+    dir: PathBuf,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("mhf-iel-wine-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("game")).unwrap();
+        std::fs::write(dir.join("wine"), STUB).unwrap();
+        std::fs::set_permissions(dir.join("wine"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(dir.join("mhf-iel-cli.exe"), "").unwrap();
+        Self { dir }
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_mhf-iel-cli"))
+            .arg("wine")
+            .arg("--wine")
+            .arg(self.dir.join("wine"))
+            .arg("--prefix")
+            .arg(self.dir.join("prefix"))
+            .arg("--exe")
+            .arg(self.dir.join("mhf-iel-cli.exe"))
+            .args(["--config-data", CONFIG])
+            .args(args)
+            .current_dir(self.dir.join("game"))
+            .env_remove("WINEDEBUG")
+            .output()
+            .unwrap()
+    }
+
+    // One map of 'NAME=value' lines per call
+    fn calls(&self) -> Vec<Vec<(String, String)>> {
+        let calls = std::fs::read_to_string(self.dir.join("calls")).unwrap_or_default();
+        calls
+            .lines()
+            .map(|line| line.split_once('=').unwrap())
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect::<Vec<_>>()
+            .chunks(7)
+            .map(<[_]>::to_vec)
+            .collect()
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        self.dir.join(path)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn get<'a>(call: &'a [(String, String)], name: &str) -> &'a str {
+    call.iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+        .unwrap()
+}
+
+fn wine_path(path: &Path) -> String {
+    format!("Z:{}", path.display().to_string().replace('/', "\\"))
+}
+
+#[test]
+fn creates_the_prefix_and_passes_the_config_on_stdin() {
+    let fixture = Fixture::new("new");
+    let output = fixture.run(&["--dxvk", "--dll-override", "dinput8=n,b"]);
+    // The Windows CLI's exit code is passed on
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    let calls = fixture.calls();
+    assert_eq!(calls.len(), 3);
+    assert_eq!(get(&calls[0], "args"), "wineboot --init");
+    let fonts = fixture.path("prefix/mhf-iel-fonts.reg");
+    assert_eq!(
+        get(&calls[1], "args"),
+        format!("regedit /S {}", wine_path(&fonts))
+    );
+    assert_eq!(
+        get(&calls[2], "args"),
+        format!(
+            "{} run --config-stdin --output text",
+            wine_path(&fixture.path("mhf-iel-cli.exe"))
+        )
+    );
+    for call in &calls {
+        assert_eq!(
+            get(call, "WINEPREFIX"),
+            fixture.path("prefix").display().to_string()
+        );
+        assert_eq!(
+            get(call, "WINEDLLOVERRIDES"),
+            "winemenubuilder.exe=d;d3d9=n,b;dinput8=n,b"
+        );
+        assert_eq!(get(call, "WINEDEBUG"), "-all");
+        assert_eq!(get(call, "LANG"), "ja_JP.UTF-8");
+        assert_eq!(get(call, "LC_ALL"), "ja_JP.UTF-8");
+    }
+    assert_eq!(
+        get(&calls[2], "PWD"),
+        fixture.path("game").display().to_string()
+    );
+
+    // UTF-16 with a BOM, replacing every Japanese face with the default font
+    let fonts = std::fs::read(fonts).unwrap();
+    assert_eq!(fonts[..2], [0xff, 0xfe]);
+    let fonts: Vec<u16> = fonts[2..]
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    let fonts = String::from_utf16(&fonts).unwrap();
+    assert!(fonts.starts_with("Windows Registry Editor Version 5.00\r\n"));
+    assert!(fonts.contains("\"ＭＳ ゴシック\"=\"Noto Sans CJK JP\"\r\n"));
+
+    let stdin = std::fs::read_to_string(fixture.path("stdin")).unwrap();
+    let config: serde_json::Value = serde_json::from_str(&stdin).unwrap();
+    assert_eq!(config["char_id"], 1);
+    assert_eq!(config["server_host"], "10.0.0.2");
+    assert_eq!(
+        config["mhf_folder"],
+        wine_path(&fixture.path("game").canonicalize().unwrap())
+    );
+}
+
+#[test]
+fn reuses_an_existing_prefix() {
+    let fixture = Fixture::new("existing");
+    std::fs::create_dir_all(fixture.path("prefix")).unwrap();
+    std::fs::write(fixture.path("prefix/system.reg"), "").unwrap();
+    let output = fixture.run(&["--locale", "ja_JP.EUC-JP", "--events"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    // No wineboot, and no fonts unless asked for
+    let calls = fixture.calls();
+    assert_eq!(calls.len(), 1);
+    assert!(get(&calls[0], "args").ends_with(" run --config-stdin --output text --events"));
+    assert_eq!(get(&calls[0], "WINEDLLOVERRIDES"), "winemenubuilder.exe=d");
+    assert_eq!(get(&calls[0], "LANG"), "ja_JP.EUC-JP");
+    assert!(!fixture.path("prefix/mhf-iel-fonts.reg").exists());
+}
+
+#[test]
+fn dry_run_starts_nothing() {
+    let fixture = Fixture::new("dry-run");
+    let output = fixture.run(&["--dry-run", "--output", "json", "--font", "IPAGothic"]);
+    assert_eq!(output.status.code(), Some(0), "{output:?}");
+    assert!(fixture.calls().is_empty());
+    assert!(!fixture.path("prefix").exists());
+
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let result = &output["result"];
+    assert_eq!(result["create_prefix"], true);
+    assert_eq!(
+        result["setup"][0]["args"],
+        serde_json::json!(["wineboot", "--init"])
+    );
+    assert_eq!(result["setup"][1]["args"][0], "regedit");
+    assert_eq!(result["launch"]["env"]["LANG"], "ja_JP.UTF-8");
+}
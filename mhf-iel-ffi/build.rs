@@ -0,0 +1,12 @@
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    // A stale header is better than a failed build, the committed one is still usable
+    match cbindgen::generate(&crate_dir) {
+        Ok(bindings) => {
+            bindings.write_to_file(format!("{crate_dir}/include/mhf_iel.h"));
+        }
+        Err(e) => println!("cargo:warning=unable to generate header: {e}"),
+    }
+}
@@ -0,0 +1,73 @@
+// Exercises the C ABI against the parts that don't need Windows. Run through 'run.sh' or cargo test.
+#include <stdio.h>
+#include <string.h>
+
+#include "mhf_iel.h"
+
+#define VALID_CONFIG "{\"char_id\": 1, \"user_token\": \"0123456789abcdef\"}"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                  \
+    do {                                                             \
+        if (!(cond)) {                                               \
+            fprintf(stderr, "%s:%d: failed: %s\n", __FILE__, __LINE__, #cond); \
+            failures++;                                              \
+        }                                                            \
+    } while (0)
+
+static int events[8];
+static int event_count = 0;
+
+static void on_event(MhfIelEvent event, intptr_t value, void *user_data) {
+    (void)value;
+    CHECK(user_data == &event_count);
+    if (event_count < 8) {
+        events[event_count] = event;
+    }
+    event_count++;
+}
+
+int main(void) {
+    CHECK(mhf_iel_validate_json(VALID_CONFIG) == MHF_IEL_STATUS_OK);
+    CHECK(mhf_iel_last_error_code() == MHF_IEL_STATUS_OK);
+    CHECK(mhf_iel_last_error_message() == NULL);
+
+    CHECK(mhf_iel_validate_json(NULL) == MHF_IEL_STATUS_INVALID_ARGUMENT);
+    CHECK(mhf_iel_validate_json("\xff") == MHF_IEL_STATUS_INVALID_ARGUMENT);
+
+    CHECK(mhf_iel_validate_json("{") == MHF_IEL_STATUS_CONFIG_PARSE);
+    CHECK(mhf_iel_last_error_code() == MHF_IEL_STATUS_CONFIG_PARSE);
+    CHECK(mhf_iel_last_error_message() != NULL);
+
+    CHECK(mhf_iel_validate_json("{\"char_id\": 1, \"user_token\": \"short\"}") ==
+          MHF_IEL_STATUS_INVALID_CONFIG);
+    CHECK(strstr(mhf_iel_last_error_message(), "token") != NULL);
+
+    CHECK(mhf_iel_validate_json("{\"user_token\": \"0123456789abcdef\"}") ==
+          MHF_IEL_STATUS_INVALID_CONFIG);
+    CHECK(strstr(mhf_iel_last_error_message(), "char_id") != NULL);
+
+    // Invalid configs fail before any event
+    mhf_iel_set_event_callback(on_event, &event_count);
+    CHECK(mhf_iel_run_json("{}", NULL) == MHF_IEL_STATUS_INVALID_CONFIG);
+    CHECK(event_count == 0);
+
+    // Outside of Windows the config is validated, then running fails
+    intptr_t game_code = 42;
+    CHECK(mhf_iel_run_json(VALID_CONFIG, &game_code) == MHF_IEL_STATUS_RUN);
+    CHECK(event_count == 1 && events[0] == MHF_IEL_EVENT_CONFIG_VALIDATED);
+    CHECK(game_code == 42);
+    CHECK(strstr(mhf_iel_last_error_message(), "Windows") != NULL);
+
+    mhf_iel_set_event_callback(NULL, NULL);
+    CHECK(mhf_iel_run_json(VALID_CONFIG, NULL) == MHF_IEL_STATUS_RUN);
+    CHECK(event_count == 1);
+
+    if (failures) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("all checks passed\n");
+    return 0;
+}
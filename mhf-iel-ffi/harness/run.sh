@@ -0,0 +1,10 @@
+#!/bin/sh
+# Builds the library for the host and runs the C ABI checks against it.
+set -eu
+
+cd "$(dirname "$0")/.."
+target="$(rustc -vV | sed -n 's/^host: //p')"
+cargo build --target "$target"
+out="../target/$target/debug"
+${CC:-cc} -Wall -Wextra -std=c99 -Iinclude harness/abi.c -L"$out" -lmhf_iel -o "$out/abi-harness"
+LD_LIBRARY_PATH="$out" DYLD_LIBRARY_PATH="$out" "$out/abi-harness"
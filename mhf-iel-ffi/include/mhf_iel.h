@@ -0,0 +1,100 @@
+/* Generated by cbindgen from mhf-iel-ffi, don't edit by hand */
+
+#ifndef MHF_IEL_H
+#define MHF_IEL_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+enum MhfIelEvent
+#ifdef __cplusplus
+  : int32_t
+#endif // __cplusplus
+ {
+  MHF_IEL_EVENT_CONFIG_VALIDATED = 0,
+  MHF_IEL_EVENT_DLL_LOADED = 1,
+  /**
+   * Sent right before control is handed to the game, `mhf_iel_run_json` blocks until it exits
+   */
+  MHF_IEL_EVENT_MAIN_ENTERED = 2,
+  MHF_IEL_EVENT_GAME_EXITED = 3,
+};
+#ifndef __cplusplus
+typedef int32_t MhfIelEvent;
+#endif // __cplusplus
+
+/**
+ * Returned by every call, and kept per thread for `mhf_iel_last_error_code`.
+ */
+enum MhfIelStatus
+#ifdef __cplusplus
+  : int32_t
+#endif // __cplusplus
+ {
+  MHF_IEL_STATUS_OK = 0,
+  /**
+   * A null pointer, or a string that isn't UTF-8
+   */
+  MHF_IEL_STATUS_INVALID_ARGUMENT = 1,
+  MHF_IEL_STATUS_CONFIG_PARSE = 2,
+  MHF_IEL_STATUS_INVALID_CONFIG = 3,
+  MHF_IEL_STATUS_RUN = 4,
+  MHF_IEL_STATUS_PANIC = 5,
+};
+#ifndef __cplusplus
+typedef int32_t MhfIelStatus;
+#endif // __cplusplus
+
+/**
+ * Called on the thread running `mhf_iel_run_json`. `value` is the exit code for
+ * `MHF_IEL_EVENT_GAME_EXITED` and 0 otherwise.
+ */
+typedef void (*MhfIelEventCallback)(MhfIelEvent event, intptr_t value, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Parses and validates a JSON config, without running the game.
+ *
+ * # Safety
+ *
+ * `config` must be null or point to a null-terminated string.
+ */
+MhfIelStatus mhf_iel_validate_json(const char *config);
+
+/**
+ * Runs the game with a JSON config, blocking until it exits. Its exit code is written to
+ * `game_code` when that isn't null.
+ *
+ * # Safety
+ *
+ * `config` must be null or point to a null-terminated string, and `game_code` must be null or
+ * valid for writes.
+ */
+MhfIelStatus mhf_iel_run_json(const char *config, intptr_t *game_code);
+
+/**
+ * Sets the callback used by every following `mhf_iel_run_json`, null removes it.
+ */
+void mhf_iel_set_event_callback(MhfIelEventCallback callback, void *user_data);
+
+/**
+ * Status of the last call made on this thread.
+ */
+MhfIelStatus mhf_iel_last_error_code(void);
+
+/**
+ * Message of the last failed call made on this thread, or null if it succeeded. Valid until the
+ * next call on this thread.
+ */
+const char *mhf_iel_last_error_message(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* MHF_IEL_H */
@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Mutex;
+
+use mhf_iel::{Error, Event, MhfConfig};
+
+/// Returned by every call, and kept per thread for `mhf_iel_last_error_code`.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MhfIelStatus {
+    Ok = 0,
+    /// A null pointer, or a string that isn't UTF-8
+    InvalidArgument = 1,
+    ConfigParse = 2,
+    InvalidConfig = 3,
+    Run = 4,
+    Panic = 5,
+}
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MhfIelEvent {
+    ConfigValidated = 0,
+    DllLoaded = 1,
+    /// Sent right before control is handed to the game, `mhf_iel_run_json` blocks until it exits
+    MainEntered = 2,
+    GameExited = 3,
+}
+
+/// Called on the thread running `mhf_iel_run_json`. `value` is the exit code for
+/// `MHF_IEL_EVENT_GAME_EXITED` and 0 otherwise.
+pub type MhfIelEventCallback =
+    Option<unsafe extern "C" fn(event: MhfIelEvent, value: isize, user_data: *mut c_void)>;
+
+type Failure = (MhfIelStatus, String);
+
+// `user_data` is only ever handed back to the callback, so it's kept as an integer
+static CALLBACK: Mutex<(MhfIelEventCallback, usize)> = Mutex::new((None, 0));
+
+thread_local! {
+    static LAST_ERROR: RefCell<(MhfIelStatus, Option<CString>)> =
+        const { RefCell::new((MhfIelStatus::Ok, None)) };
+}
+
+fn error_status(e: &Error) -> MhfIelStatus {
+    match e {
+        Error::TokenLength | Error::MissingField(_) | Error::TooMany(..) | Error::TooLong(..) => {
+            MhfIelStatus::InvalidConfig
+        }
+        _ => MhfIelStatus::Run,
+    }
+}
+
+fn call(f: impl FnOnce() -> Result<(), Failure>) -> MhfIelStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => (MhfIelStatus::Ok, None),
+        Ok(Err((status, message))) => (status, Some(message)),
+        Err(_) => (MhfIelStatus::Panic, Some("mhf-iel panicked".to_owned())),
+    };
+    let message = message.map(|m| CString::new(m.replace('\0', "")).unwrap_or_default());
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = (status, message));
+    status
+}
+
+unsafe fn parse_config(config: *const c_char) -> Result<MhfConfig, Failure> {
+    if config.is_null() {
+        return Err((MhfIelStatus::InvalidArgument, "config is null".to_owned()));
+    }
+    let config = unsafe { CStr::from_ptr(config) }.to_str().or(Err((
+        MhfIelStatus::InvalidArgument,
+        "config isn't valid UTF-8".to_owned(),
+    )))?;
+    serde_json::from_str(config).map_err(|e| {
+        (
+            MhfIelStatus::ConfigParse,
+            format!("error parsing config data: {e}"),
+        )
+    })
+}
+
+/// Parses and validates a JSON config, without running the game.
+///
+/// # Safety
+///
+/// `config` must be null or point to a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn mhf_iel_validate_json(config: *const c_char) -> MhfIelStatus {
+    call(|| {
+        let config = unsafe { parse_config(config) }?;
+        config
+            .validate()
+            .map_err(|e| (error_status(&e), format!("invalid config: {e}")))
+    })
+}
+
+/// Runs the game with a JSON config, blocking until it exits. Its exit code is written to
+/// `game_code` when that isn't null.
+///
+/// # Safety
+///
+/// `config` must be null or point to a null-terminated string, and `game_code` must be null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn mhf_iel_run_json(
+    config: *const c_char,
+    game_code: *mut isize,
+) -> MhfIelStatus {
+    call(|| {
+        let config = unsafe { parse_config(config) }?;
+        // Copied so the callback can be changed from inside itself
+        let (callback, user_data) = *CALLBACK.lock().unwrap_or_else(|e| e.into_inner());
+        let on_event = |event| {
+            let Some(callback) = callback else {
+                return;
+            };
+            let (event, value) = match event {
+                Event::ConfigValidated => (MhfIelEvent::ConfigValidated, 0),
+                Event::DllLoaded => (MhfIelEvent::DllLoaded, 0),
+                Event::MainEntered => (MhfIelEvent::MainEntered, 0),
+                Event::GameExited { code } => (MhfIelEvent::GameExited, code),
+            };
+            unsafe { callback(event, value, user_data as *mut c_void) };
+        };
+        let code = mhf_iel::run_with_events(config, on_event)
+            .map_err(|e| (error_status(&e), format!("error running mhf: {e}")))?;
+        if !game_code.is_null() {
+            unsafe { *game_code = code };
+        }
+        Ok(())
+    })
+}
+
+/// Sets the callback used by every following `mhf_iel_run_json`, null removes it.
+#[no_mangle]
+pub extern "C" fn mhf_iel_set_event_callback(
+    callback: MhfIelEventCallback,
+    user_data: *mut c_void,
+) {
+    *CALLBACK.lock().unwrap_or_else(|e| e.into_inner()) = (callback, user_data as usize);
+}
+
+/// Status of the last call made on this thread.
+#[no_mangle]
+pub extern "C" fn mhf_iel_last_error_code() -> MhfIelStatus {
+    LAST_ERROR.with(|last_error| last_error.borrow().0)
+}
+
+/// Message of the last failed call made on this thread, or null if it succeeded. Valid until the
+/// next call on this thread.
+#[no_mangle]
+pub extern "C" fn mhf_iel_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .1
+            .as_ref()
+            .map_or(ptr::null(), |m| m.as_ptr())
+    })
+}
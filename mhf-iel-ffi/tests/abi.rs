@@ -0,0 +1,63 @@
+#![cfg(not(windows))]
+//! Builds `harness/abi.c` against the library and runs it, like `harness/run.sh`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Cargo doesn't build cdylibs for integration tests, so this builds one for the host in a folder
+// of its own, which also keeps it from waiting on the lock of the running build
+fn build_library() -> PathBuf {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned());
+    let rustc = Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned()))
+        .arg("-vV")
+        .output()
+        .unwrap();
+    let rustc = String::from_utf8(rustc.stdout).unwrap();
+    let target = rustc
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .unwrap();
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("abi");
+    let status = Command::new(cargo)
+        .args(["build", "-p", "mhf-iel-ffi", "--target", target])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "building the library failed");
+    target_dir.join(target).join("debug")
+}
+
+#[test]
+fn c_harness() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let lib = build_library();
+    let harness = Path::new(env!("CARGO_TARGET_TMPDIR")).join("abi-harness");
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let status = Command::new(&cc)
+        .args(["-Wall", "-Wextra", "-Werror", "-std=c99"])
+        .arg("-I")
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("harness/abi.c"))
+        .arg("-L")
+        .arg(&lib)
+        .arg("-lmhf_iel")
+        .arg("-o")
+        .arg(&harness)
+        .status()
+        .unwrap_or_else(|e| panic!("unable to run '{cc}', set CC to a C compiler: {e}"));
+    assert!(status.success(), "compiling the harness failed");
+
+    let output = Command::new(&harness)
+        .env("LD_LIBRARY_PATH", &lib)
+        .env("DYLD_LIBRARY_PATH", &lib)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use mhf_iel::settings::MhfSettings;
+use mhf_iel::MhfConfig;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyAttributeError, PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+create_exception!(mhf_iel, MhfIelError, PyException);
+
+fn lib_error(e: mhf_iel::Error) -> PyErr {
+    MhfIelError::new_err(e.to_string())
+}
+
+// Values go through Python's json module, so they look exactly like the JSON config
+fn to_py(py: Python<'_>, value: &impl Serialize) -> PyResult<PyObject> {
+    let data = serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(py.import("json")?.call_method1("loads", (data,))?.unbind())
+}
+
+fn from_py<T: DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let json = value.py().import("json")?;
+    let data: String = json.call_method1("dumps", (value,))?.extract()?;
+    serde_json::from_str(&data).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Same fields as the JSON config, anything not given falls back to its default.
+#[pyclass(name = "MhfConfig", module = "mhf_iel")]
+#[derive(Clone)]
+struct PyMhfConfig(MhfConfig);
+
+impl PyMhfConfig {
+    fn fields(&self) -> PyResult<Map<String, Value>> {
+        match serde_json::to_value(&self.0) {
+            Ok(Value::Object(fields)) => Ok(fields),
+            _ => Err(PyValueError::new_err("unable to serialize config")),
+        }
+    }
+}
+
+#[pymethods]
+impl PyMhfConfig {
+    #[new]
+    #[pyo3(signature = (**fields))]
+    fn new(fields: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        match fields {
+            Some(fields) => Ok(Self(from_py(fields.as_any())?)),
+            None => Ok(Self(MhfConfig::default())),
+        }
+    }
+
+    #[staticmethod]
+    fn from_json(data: &str) -> PyResult<Self> {
+        serde_json::from_str(data)
+            .map(Self)
+            .map_err(|e| PyValueError::new_err(format!("error parsing config data: {e}")))
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.0).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_py(py, &self.0)
+    }
+
+    /// Raises `MhfIelError` if the config can't be used to run the game.
+    fn validate(&self) -> PyResult<()> {
+        self.0.validate().map_err(lib_error)
+    }
+
+    /// What `run` would pass to the game, without running it.
+    fn plan(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let plan = mhf_iel::plan(&self.0).map_err(lib_error)?;
+        to_py(py, &plan)
+    }
+
+    /// Runs the game, blocking until it exits and returning its exit code. Only works on Windows.
+    fn run(&self, py: Python<'_>) -> PyResult<isize> {
+        let config = self.0.clone();
+        py.allow_threads(|| mhf_iel::run(config)).map_err(lib_error)
+    }
+
+    fn __getattr__(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        let value = self
+            .fields()?
+            .remove(name)
+            .ok_or_else(|| PyAttributeError::new_err(format!("no config field '{name}'")))?;
+        to_py(py, &value)
+    }
+
+    fn __setattr__(&mut self, name: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        let mut fields = self.fields()?;
+        if !fields.contains_key(name) {
+            return Err(PyAttributeError::new_err(format!(
+                "no config field '{name}'"
+            )));
+        }
+        fields.insert(name.to_owned(), from_py(value)?);
+        self.0 = serde_json::from_value(Value::Object(fields))
+            .map_err(|e| PyValueError::new_err(format!("invalid '{name}': {e}")))?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MhfConfig(char_id={}, char_name={:?}, server_host={:?}, server_port={}, version={:?})",
+            self.0.char_id,
+            self.0.char_name,
+            self.0.server_host,
+            self.0.server_port,
+            self.0.version
+        )
+    }
+}
+
+/// Parses the contents of an `mhf.ini` file.
+#[pyfunction]
+fn parse_settings(py: Python<'_>, data: &[u8]) -> PyResult<PyObject> {
+    to_py(py, &MhfSettings::parse(data))
+}
+
+/// Reads `mhf.ini` from a game folder, a missing file gives the defaults.
+#[pyfunction]
+fn load_settings(py: Python<'_>, mhf_folder: PathBuf) -> PyResult<PyObject> {
+    to_py(py, &MhfSettings::load(&mhf_folder))
+}
+
+/// JSON Schema of the config, as a dict.
+#[pyfunction]
+fn config_schema(py: Python<'_>) -> PyResult<PyObject> {
+    to_py(py, &mhf_iel::config_schema())
+}
+
+#[pymodule]
+#[pyo3(name = "mhf_iel")]
+fn py_mhf_iel(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("MhfIelError", m.py().get_type::<MhfIelError>())?;
+    m.add_class::<PyMhfConfig>()?;
+    m.add_function(wrap_pyfunction!(parse_settings, m)?)?;
+    m.add_function(wrap_pyfunction!(load_settings, m)?)?;
+    m.add_function(wrap_pyfunction!(config_schema, m)?)?;
+    Ok(())
+}
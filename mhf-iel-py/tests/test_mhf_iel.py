@@ -0,0 +1,124 @@
+# Runs anywhere the module builds, none of these start the game.
+import json
+
+import pytest
+
+import mhf_iel
+
+TOKEN = "0123456789abcdef"
+
+
+def config(**fields):
+    return mhf_iel.MhfConfig(char_id=1, user_token=TOKEN, **fields)
+
+
+def test_construction_defaults():
+    c = config()
+    assert c.char_id == 1
+    assert c.user_token == TOKEN
+    assert c.server_host == "127.0.0.1"
+    assert c.server_port == 53312
+    assert c.version == "ZZ"
+    assert c.notices == []
+    assert c.mhf_folder is None
+
+
+def test_construction_fields():
+    c = config(char_name="Hunter", char_ids=[1, 2], version="F5", mez_stalls=["Pachinko"])
+    assert c.char_name == "Hunter"
+    assert c.char_ids == [1, 2]
+    assert c.version == "F5"
+    assert c.mez_stalls == ["Pachinko"]
+    assert repr(c).startswith("MhfConfig(char_id=1, char_name=\"Hunter\"")
+
+
+def test_invalid_field_values():
+    with pytest.raises(ValueError):
+        mhf_iel.MhfConfig(char_id="one")
+    with pytest.raises(ValueError):
+        config(version="F4")
+
+
+def test_attributes():
+    c = config()
+    c.server_host = "example.com"
+    c.char_ids = [3]
+    assert c.server_host == "example.com"
+    assert c.char_ids == [3]
+    with pytest.raises(AttributeError):
+        c.no_such_field
+    with pytest.raises(AttributeError):
+        c.no_such_field = 1
+    with pytest.raises(ValueError):
+        c.server_port = "port"
+    # A failed set leaves the config as it was
+    assert c.server_port == 53312
+
+
+def test_json_round_trip():
+    c = config(char_name="Hunter", notices=[{"flags": 1, "data": "<BODY>Hi"}])
+    data = c.to_json()
+    back = mhf_iel.MhfConfig.from_json(data)
+    assert back.to_dict() == c.to_dict()
+    assert json.loads(data) == c.to_dict()
+    assert c.to_dict()["notices"] == [{"flags": 1, "data": "<BODY>Hi"}]
+    with pytest.raises(ValueError):
+        mhf_iel.MhfConfig.from_json("{")
+
+
+def test_validate():
+    config().validate()
+    with pytest.raises(mhf_iel.MhfIelError, match="char_id"):
+        mhf_iel.MhfConfig(user_token=TOKEN).validate()
+    with pytest.raises(mhf_iel.MhfIelError, match="16"):
+        mhf_iel.MhfConfig(char_id=1, user_token="short").validate()
+    with pytest.raises(mhf_iel.MhfIelError):
+        config(char_ids=list(range(1, 18))).validate()
+    with pytest.raises(mhf_iel.MhfIelError):
+        config(notices=[{"flags": 0, "data": "x"}] * 5).validate()
+    assert issubclass(mhf_iel.MhfIelError, Exception)
+
+
+def test_plan(tmp_path):
+    (tmp_path / "mhf.ini").write_text("[VIDEO]\nGRAPHICS_VER=0\n")
+    plan = config(server_host="10.0.0.2", mhf_folder=str(tmp_path), mhf_flags=["Npge"]).plan()
+    assert plan["version"] == "ZZ"
+    assert plan["mhf_folder"] == str(tmp_path)
+    assert plan["dll"] == "mhfo.dll"
+    assert plan["graphics_ver"] == 0
+    assert plan["remote_addr"] == "10.0.0.2:53312"
+    assert plan["remote_host"] == "10.0.0.2"
+    assert plan["alt_ip_address"] == "10.0.0.2:8080"
+    assert (plan["cmd_flags_1"], plan["cmd_flags_2"]) == (9, 6)
+    assert plan["settings"]["graphics_ver"] == 0
+
+    plan = config(version="F5", mhf_folder=str(tmp_path)).plan()
+    assert plan["alt_ip_address"] is None
+    with pytest.raises(mhf_iel.MhfIelError):
+        mhf_iel.MhfConfig(char_id=1).plan()
+
+
+def test_parse_settings():
+    settings = mhf_iel.parse_settings(b"[SCREEN]\nFULLSCREEN_MODE=0\nWINDOW_RESOLUTION_W=1280\n")
+    assert settings["fullscreen_mode"] == 0
+    assert settings["window_resolution_w"] == 1280
+    # Missing keys get the original launcher's defaults
+    assert settings["window_resolution_h"] == 1080
+    assert settings["sound_frequency"] == 48000
+    assert mhf_iel.parse_settings(b"") == mhf_iel.load_settings("/nonexistent")
+
+
+def test_load_settings(tmp_path):
+    (tmp_path / "mhf.ini").write_bytes(b"[LAUNCH]\nPROXY_PORT=1234\n")
+    assert mhf_iel.load_settings(str(tmp_path))["proxy_port"] == 1234
+
+
+def test_config_schema():
+    schema = mhf_iel.config_schema()
+    assert schema["title"] == "MhfConfig"
+    assert schema["required"] == ["char_id", "user_token"]
+    properties = schema["properties"]
+    assert properties["notices"]["maxItems"] == 4
+    assert properties["mez_stalls"]["maxItems"] == 8
+    assert properties["char_ids"]["maxItems"] == 16
+    assert set(config().to_dict()) <= set(properties)
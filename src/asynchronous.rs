@@ -0,0 +1,295 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::signv2::{Signv2Client, DEFAULT_ENDPOINT, DEFAULT_SERVER_PORT};
+use crate::{Error, Event, MhfConfig, MhfVersion, Result};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reported through the progress channel of `run` and `launch`, in the order it happens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress {
+    LoggingIn,
+    /// Checking that the sign server accepts connections
+    Probing,
+    Game(Event),
+}
+
+pub type ProgressSender = mpsc::UnboundedSender<Progress>;
+
+/// What `launch` needs to log in through signv2server.
+#[derive(Debug, Clone)]
+pub struct Login {
+    pub endpoint: String,
+    pub username: String,
+    pub password: String,
+    /// Defaults to the first character
+    pub char_id: Option<u32>,
+    /// Defaults to the endpoint's host
+    pub server_host: Option<String>,
+    pub server_port: u32,
+    pub version: MhfVersion,
+}
+
+impl Default for Login {
+    fn default() -> Self {
+        Self {
+            endpoint: DEFAULT_ENDPOINT.to_owned(),
+            username: String::new(),
+            password: String::new(),
+            char_id: None,
+            server_host: None,
+            server_port: DEFAULT_SERVER_PORT,
+            version: MhfVersion::default(),
+        }
+    }
+}
+
+// Dropping the future cancels the launch, as long as the game didn't get control yet
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+fn check_cancelled(cancelled: &AtomicBool) -> Result<()> {
+    match cancelled.load(Ordering::SeqCst) {
+        true => Err(Error::Cancelled),
+        false => Ok(()),
+    }
+}
+
+fn probe(host: &str, port: u32) -> Result<()> {
+    let unreachable = || Error::Server(format!("'{host}:{port}' is unreachable"));
+    let port = u16::try_from(port).map_err(|_| unreachable())?;
+    let addrs = (host, port).to_socket_addrs().map_err(|_| unreachable())?;
+    for addr in addrs {
+        if TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_ok() {
+            return Ok(());
+        }
+    }
+    Err(unreachable())
+}
+
+fn run_blocking(
+    config: MhfConfig,
+    report: &dyn Fn(Progress),
+    cancelled: &AtomicBool,
+) -> Result<isize> {
+    config.validate()?;
+    report(Progress::Probing);
+    probe(&config.server_host, config.server_port)?;
+    check_cancelled(cancelled)?;
+    crate::run_cancellable(
+        config,
+        &mut |event| report(Progress::Game(event)),
+        cancelled,
+    )
+}
+
+fn login_blocking(login: Login, report: &dyn Fn(Progress)) -> Result<MhfConfig> {
+    report(Progress::LoggingIn);
+    let client = Signv2Client::new(&login.endpoint);
+    let auth = client.login(&login.username, &login.password)?;
+    let character = match login.char_id {
+        Some(id) => auth.characters.iter().find(|c| c.id == id),
+        None => auth.characters.first(),
+    }
+    .ok_or_else(|| Error::Server("character not found".to_owned()))?;
+    Ok(MhfConfig {
+        server_host: login
+            .server_host
+            .clone()
+            .unwrap_or_else(|| client.host().to_owned()),
+        server_port: login.server_port,
+        version: login.version,
+        ..auth.to_config(character, false, &login.username, &login.password)
+    })
+}
+
+// The game blocks until it exits, so it gets its own thread instead of the runtime's blocking pool
+async fn on_thread<F>(progress: Option<ProgressSender>, f: F) -> Result<isize>
+where
+    F: FnOnce(&dyn Fn(Progress), &AtomicBool) -> Result<isize> + Send + 'static,
+{
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let _guard = CancelOnDrop(cancelled.clone());
+    let (sender, receiver) = oneshot::channel();
+    std::thread::Builder::new()
+        .name("mhf-iel".to_owned())
+        .spawn(move || {
+            let report = |p| {
+                if let Some(progress) = &progress {
+                    let _ = progress.send(p);
+                }
+            };
+            let _ = sender.send(f(&report, &cancelled));
+        })
+        .map_err(|e| Error::Launch(e.to_string()))?;
+    receiver
+        .await
+        .unwrap_or_else(|_| Err(Error::Launch("launch thread panicked".to_owned())))
+}
+
+/// Validates the config, checks the sign server is reachable and runs the game, resolving once it
+/// exits. Dropping the future cancels the launch up until `mhDLL_Main` is entered.
+pub async fn run(config: MhfConfig, progress: Option<ProgressSender>) -> Result<isize> {
+    on_thread(progress, move |report, cancelled| {
+        run_blocking(config, report, cancelled)
+    })
+    .await
+}
+
+/// Same as `run`, logging in through signv2server first to build the config.
+pub async fn launch(login: Login, progress: Option<ProgressSender>) -> Result<isize> {
+    on_thread(progress, move |report, cancelled| {
+        let config = login_blocking(login, report)?;
+        check_cancelled(cancelled)?;
+        run_blocking(config, report, cancelled)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::net::TcpListener;
+    use std::pin::pin;
+    use std::sync::mpsc as std_mpsc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::Thread;
+
+    use super::*;
+
+    // Enough of an executor for these futures, which only wait on a oneshot channel
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+
+    fn config(server_port: u32) -> MhfConfig {
+        MhfConfig {
+            char_id: 1,
+            user_token: "0123456789abcdef".to_owned(),
+            server_port,
+            ..Default::default()
+        }
+    }
+
+    fn progress() -> (Option<ProgressSender>, impl FnMut() -> Vec<Progress>) {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let collect = move || std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+        (Some(sender), collect)
+    }
+
+    #[test]
+    fn reports_progress() {
+        let (sender, mut collect) = progress();
+        let code = block_on(on_thread(sender, |report, _| {
+            report(Progress::LoggingIn);
+            report(Progress::Game(Event::GameExited { code: 3 }));
+            Ok(3)
+        }));
+        assert_eq!(code.unwrap(), 3);
+        assert_eq!(
+            collect(),
+            [
+                Progress::LoggingIn,
+                Progress::Game(Event::GameExited { code: 3 })
+            ]
+        );
+        // Reporting without a channel is fine too
+        let code = block_on(on_thread(None, |report, _| {
+            report(Progress::Probing);
+            Ok(0)
+        }));
+        assert_eq!(code.unwrap(), 0);
+    }
+
+    #[test]
+    fn cancels_on_drop() {
+        let (resume, wait) = std_mpsc::channel::<()>();
+        let (sender, cancelled) = std_mpsc::channel();
+        let future = on_thread(None, move |_, is_cancelled| {
+            wait.recv().unwrap();
+            sender.send(is_cancelled.load(Ordering::SeqCst)).unwrap();
+            check_cancelled(is_cancelled).map(|_| 0)
+        });
+        {
+            // Started by the first poll, then dropped while the thread still runs
+            let mut future = pin!(future);
+            let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+            let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+            assert!(poll.is_pending());
+        }
+        resume.send(()).unwrap();
+        assert!(cancelled.recv().unwrap());
+
+        // Not when it runs to the end
+        let code = block_on(on_thread(None, |_, is_cancelled| {
+            check_cancelled(is_cancelled).map(|_| 0)
+        }));
+        assert_eq!(code.unwrap(), 0);
+    }
+
+    #[test]
+    fn panics() {
+        let code = block_on(on_thread(None, |_, _| panic!("crashed")));
+        assert!(matches!(code, Err(Error::Launch(e)) if e == "launch thread panicked"));
+    }
+
+    #[test]
+    fn probes_before_running() {
+        // Nothing listens on a port that was just freed
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (sender, mut collect) = progress();
+        let code = block_on(run(config(port as u32), sender));
+        assert!(matches!(code, Err(Error::Server(_))), "{code:?}");
+        assert_eq!(collect(), [Progress::Probing]);
+
+        // Invalid configs never get that far
+        let (sender, mut collect) = progress();
+        let invalid = MhfConfig {
+            user_token: "short".to_owned(),
+            ..config(port as u32)
+        };
+        assert!(block_on(run(invalid, sender)).is_err());
+        assert_eq!(collect(), []);
+
+        // Cancelled right after the probe, before the game is loaded
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port() as u32;
+        let (sender, mut collect) = progress();
+        let report = |p| {
+            let _ = sender.as_ref().unwrap().send(p);
+        };
+        let code = run_blocking(config(port), &report, &AtomicBool::new(true));
+        assert!(matches!(code, Err(Error::Cancelled)), "{code:?}");
+        assert_eq!(collect(), [Progress::Probing]);
+    }
+}
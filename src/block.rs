@@ -0,0 +1,439 @@
+use crate::research::{self, Target};
+use crate::{MhfConfig, MhfVersion, Plan};
+
+// Handles, pointers and procs are all 32 bits wide since the game is a 32 bit process, which keeps
+// the layout the same when encoding an image on other platforms.
+
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct DataZZ {
+    pub common1: Common1,        // 447178
+    pub char_ids: [u32; 0x10],   // 448e8c
+    pub common2: Common2,        // 448ecc
+    pub graphics_ver: u32,       // 448f0c
+    pub common3: Common3,        // 448f10
+    pub unk_setting_448f98: u32, // 448f98
+    pub common4: Common4,
+    pub _pad_44903c: [u8; 0x40], // 44903c // the 'alt_ip_address' load happens here, with 0x100 width
+    pub alt_ip_address: [u8; 0xC0], // 44907c
+    pub _pad_44913c: [u8; 0x40], // 44913c
+    pub server_expiry_ts: u32,   // 44917c
+    pub remote_16e: u32,         // 449180
+    pub fixed_449184_0x1: u32,   // 449184 // 2 if 100812B0 == 9
+    pub _pad_449188: [u8; 0x8],  // 449188
+    pub common5: Common5,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct DataF5 {
+    pub common1: Common1,
+    pub common2: Common2,
+    pub common3: Common3,
+    pub common4: Common4,
+    pub _pad_4406cc: [u8; 0xc],
+    pub common5: Common5,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct Common1 {
+    pub main_module: u32,       // 447178
+    pub _pad_44717c: [u8; 0x8], // 44717c
+    pub cmd_flags_1: u32,       // 447184 // set based on CLI flags
+    pub cmd_flags_2: u32,       // 447188 // set based on CLI flags
+
+    pub path1: [u8; 0x400],         // 44718c
+    pub path2: [u8; 0x400],         // 44758c
+    pub user_name: [u8; 0x800],     // 44798c
+    pub user_password: [u8; 0x800], // 44818c
+
+    pub cmd_number: u32,  // 44898c // CLI argument stuff // 1 in ZZ, 2 in F5
+    pub cmd_netfcup: u32, // 448990 // CLI '-NETFCUP'
+    pub cmd_dmm: u32,     // 448994 // set whenever a /DMM_<X> flag is specified // 7 in ZZ
+    pub _pad_448998: [u8; 0x4], // 448998 // set when mutex_master is already set?
+    pub mutex_master: u32, // 44899c
+    pub mutex_master_ready: u32, // 4489a0
+    pub mutex_master_name: [u8; 0x40], // 4489a4
+    pub ini_file: [u8; 0x40], // 4489e4
+    pub proc_1: u32,      // 448a24 // copied from 40187a
+    pub proc_2: u32,      // 448a28 // copied from 401868, probably gg_proc
+    pub proc_3: u32,      // 448a2c // copied from 40188b
+    pub _pad_448a30: [u8; 0xc], // 448a30
+
+    // Server data
+    pub selected_char_id_1: u32,    // 448a3c
+    pub selected_char_id_2: u32,    // 448a40
+    pub user_token_id: u32,         // 448a44
+    pub user_token: [u8; 0x10],     // 448a48
+    pub _pad_448a58: [u8; 0x8],     // 448a58
+    pub server_current_ts: u32,     // 448a60
+    pub fixed_448a64_0x0: u32,      // 448a64
+    pub _pad_448a68: [u8; 0x200],   // 448a68
+    pub remote_addr: [u8; 0x100],   // 448c68
+    pub remote_host: [u8; 0x100],   // 448d68
+    pub remote_patch_count: u32,    // 448e68
+    pub server_entrance_count: u32, // 448e6c
+    // 0 if existing, 2 if new. I can see from the disassembly it can also be 1, which the game seems to treat as 2,
+    // but that's based on a byte set on the individual character data that I couldn't find where to set.
+    pub selected_char_status: u32, // 448e70
+    pub user_rights: u32,          // 448e74 // Not read by F5, it seems
+    pub selected_char_hr: u32,     // 448e78
+    pub selected_char_name: [u8; 0x10], // 448e7c
+                                   // #
+} // 449188
+
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct Common2 {
+    pub global_alloc: u32,            // 448ecc
+    pub fixed_448ed0_0x1: u32,        // 448ed0
+    pub unk_448ed4: u32,              // 448ed4
+    pub selected_char_gr: u32, // 448ed8 // can be value before 'bool + name' if bool is false
+    pub _pad_448edc: [u8; 0x8], //448edc
+    pub preset_level: u32,     // 448ee4
+    pub custom: u32,           // 448ee8
+    pub fullscreen_mode: u32,  // 448eec
+    pub window_resolution_w: u32, // 448ef0
+    pub window_resolution_h: u32, // 448ef4
+    pub fullscreen_resolution_w: u32, // 448ef8
+    pub fullscreen_resolution_h: u32, // 448efc
+    pub disp_max_char: u32,    // 448f00
+    pub texture_dxt_use: u32,  // 448f04
+    pub now_monitor_wh: u32,   // 448f08
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct Common3 {
+    pub sound_notuse: u32,            // 448f10
+    pub sound_volume: u32,            // 448f14
+    pub sound_volume_inactivity: u32, // 448f18
+    pub sound_volume_minimize: u32,   // 448f1c
+    pub sound_frequency: u32,         // 448f20
+    pub sound_buffernum: u32,         // 448f24
+    pub language: u32,                // 448f28 -- 0x0
+    pub font_quality: u32,            // 448f2c -- 0x4
+    pub font_weight: u32,             // 448f30 -- 0x2bc
+    pub font_name: [u8; 0x60],        // 448f34 -- [0x3f20534d, 0x3f3f3f, 0x3f3f3f] is the default
+    pub unk_setting_448f94: u32,      // 448f94
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct Common4 {
+    pub drawskip: u32,           // 448f9c -- 0x1
+    pub clogdis: u32,            // 448fa0 -- 0x0
+    pub proxy_use: u32,          // 448fa4
+    pub proxy_ie: u32,           // 448fa8
+    pub proxy_set: u32,          // 448fac
+    pub proxy_addr: [u8; 0x40],  // 448fb0
+    pub proxy_port: u32,         // 448ff0
+    pub server_sel: u32,         // 448ff4
+    pub inner_ptr_1_4491a8: u32, // 448ff8
+    pub _pad_448ffc: [u8; 0x40], // 448ffc
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct Common5 {
+    pub data_ptr: u32,                        // 449190
+    pub keyboard_layout: u32,                 // 449194
+    pub inner_3: (),                          // 449198
+    pub _pad_449198: [u8; 0x10],              // 449198
+    pub inner_1: (),                          // 4491a8
+    pub _pad_4491a8: [u8; 0x4],               // 4491a8
+    pub fixed_4491ac_0x10: u32,               // 4491ac
+    pub inner_ptr_2_4491d4: u32,              // 4491b0
+    pub _pad_4491b4: [u8; 4],                 // 4491b4
+    pub fixed_4491b8_0x10: u32,               // 4491b8
+    pub inner_ptr_3_449198: u32,              // 4491bc
+    pub proc_4: u32,                          // 4491c0 // fixed 40605e
+    pub _pad_4491c4: [u8; 0x4],               // 4491c4
+    pub proc_5: u32,                          // 4491c8 // fixed 40609c
+    pub _pad_4491cc: [u8; 0x8],               // 4491cc
+    pub inner_2: (),                          // 4491d4
+    pub _pad_4491d4: [u8; 0x14],              // 4491d4
+    pub mhfo_module: u32,                     // 4491e8
+    pub _pad_4491ec: [u8; 0x4],               // 4491ec
+    pub _pad_4491f0: [u8; 0x520],             // 4491f0
+    pub mutex_master_ready_name: [u8; 0x100], // 449710
+    pub _pad_449810: [u8; 0x414],             // 449810
+    pub mhddl_main: u32,                      // 449c24
+}
+
+#[repr(C)]
+pub(crate) struct GlobalData {
+    pub _pad_0x0000: [u8; 0xa00],     // 0000
+    pub _pad_0x0a00: [u8; 0xc],       // 0a00
+    pub notices_count: [u32; 0x4],    // 0a0c
+    pub _pad_0x0a10: [u8; 0x8],       // 0a1c
+    pub notices_flags: [u16; 0x4],    // 0a24
+    pub notices: [[u8; 0x1000]; 0x4], // 0a2c
+    pub _filter: [u8; 0x3000],        // 4a2c
+    pub _pad_0x4a2c: [u8; 0x1080],    // 7a2c
+    pub mez_event_id: u32,            // 8aac
+    pub mez_start: u32,               // 8ab0
+    pub mez_end: u32,                 // 8ab4
+    pub mez_solo_tickets: u32,        // 8ab8
+    pub mez_group_tickets: u32,       // 8abc
+    pub mez_stalls: [u32; 0x8],       // 8ac0
+}
+
+pub const GLOBAL_ALLOC_SIZE: usize = 0x8ae0;
+const _: () = assert!(std::mem::size_of::<GlobalData>() == GLOBAL_ALLOC_SIZE);
+// 447178..449c28
+const _: () = assert!(std::mem::size_of::<DataZZ>() == 0x2ab0);
+
+pub fn bufcopy<T: Copy>(s: &mut [T], v: &[T]) {
+    let l = s.len().min(v.len());
+    s[..l].copy_from_slice(&v[..l])
+}
+
+// Every layout here is plain integers and byte arrays
+fn bytes_of<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>()) }
+}
+
+fn bytes_of_mut<T>(value: &mut T) -> &mut [u8] {
+    unsafe { std::slice::from_raw_parts_mut(value as *mut T as *mut u8, std::mem::size_of::<T>()) }
+}
+
+pub(crate) struct Commons {
+    pub common1: Common1,
+    pub common2: Common2,
+    pub common3: Common3,
+    pub common4: Common4,
+    pub common5: Common5,
+}
+
+pub(crate) enum Data {
+    ZZ(Box<DataZZ>),
+    F5(Box<DataF5>),
+}
+
+impl Data {
+    /// Reads a block back, `bytes` has to be at least as long as `version`'s.
+    pub fn from_bytes(version: MhfVersion, bytes: &[u8]) -> Option<Self> {
+        let mut data = match version {
+            MhfVersion::ZZ => Self::ZZ(unsafe { Box::<DataZZ>::new_zeroed().assume_init() }),
+            MhfVersion::F5 => Self::F5(unsafe { Box::<DataF5>::new_zeroed().assume_init() }),
+        };
+        let dest = data.as_bytes_mut();
+        dest.copy_from_slice(bytes.get(..dest.len())?);
+        Some(data)
+    }
+
+    pub fn size(version: MhfVersion) -> usize {
+        match version {
+            MhfVersion::ZZ => std::mem::size_of::<DataZZ>(),
+            MhfVersion::F5 => std::mem::size_of::<DataF5>(),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::ZZ(data) => bytes_of(data.as_ref()),
+            Self::F5(data) => bytes_of(data.as_ref()),
+        }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        match self {
+            Self::ZZ(data) => bytes_of_mut(data.as_mut()),
+            Self::F5(data) => bytes_of_mut(data.as_mut()),
+        }
+    }
+}
+
+impl GlobalData {
+    pub fn from_bytes(bytes: &[u8]) -> Option<Box<Self>> {
+        let mut data = unsafe { Box::<Self>::new_zeroed().assume_init() };
+        data.as_bytes_mut()
+            .copy_from_slice(bytes.get(..GLOBAL_ALLOC_SIZE)?);
+        Some(data)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        bytes_of(self)
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        bytes_of_mut(self)
+    }
+}
+
+/// Everything in the common parts that comes from the config and plan. Handles, procs, mutex names
+/// and pointers are left zeroed for `run` to fill in.
+pub(crate) fn encode_commons(config: &MhfConfig, plan: &Plan) -> Commons {
+    let mut mhf_folder_name = plan.mhf_folder.display().to_string();
+    if !mhf_folder_name.ends_with(['/', '\\']) {
+        mhf_folder_name.push('/');
+    }
+    let mut c: Commons = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+    let (common1, common2, common3, common4, common5) = (
+        &mut c.common1,
+        &mut c.common2,
+        &mut c.common3,
+        &mut c.common4,
+        &mut c.common5,
+    );
+    common1.fixed_448a64_0x0 = 0x0;
+    common2.fixed_448ed0_0x1 = 0x1;
+    common5.fixed_4491ac_0x10 = 0x10;
+    common5.fixed_4491b8_0x10 = 0x10;
+
+    let settings = &plan.settings;
+    common2.preset_level = settings.preset_level;
+    common2.custom = settings.custom;
+    common2.fullscreen_mode = settings.fullscreen_mode;
+    common2.window_resolution_w = settings.window_resolution_w;
+    common2.window_resolution_h = settings.window_resolution_h;
+    common2.fullscreen_resolution_w = settings.fullscreen_resolution_w;
+    common2.fullscreen_resolution_h = settings.fullscreen_resolution_h;
+    common2.disp_max_char = settings.disp_max_char;
+    common2.texture_dxt_use = settings.texture_dxt_use;
+    common2.now_monitor_wh = settings.now_monitor_wh;
+    common3.sound_notuse = settings.sound_notuse;
+    common3.sound_volume = settings.sound_volume;
+    common3.sound_volume_inactivity = settings.sound_volume_inactivity;
+    common3.sound_volume_minimize = settings.sound_volume_minimize;
+    common3.sound_frequency = settings.sound_frequency;
+    common3.sound_buffernum = settings.sound_buffernum;
+    common3.language = settings.language;
+    common3.font_quality = settings.font_quality;
+    common3.font_weight = settings.font_weight;
+    // Leave room for the null byte, like GetPrivateProfileStringA does
+    bufcopy(
+        &mut common3.font_name[..0x5f],
+        settings.font_name.as_slice(),
+    );
+    common4.drawskip = settings.drawskip;
+    common4.clogdis = settings.clogdis;
+    common4.proxy_use = settings.proxy_use;
+    common4.proxy_ie = settings.proxy_ie;
+    common4.proxy_set = settings.proxy_set;
+    bufcopy(
+        &mut common4.proxy_addr[..0x3f],
+        settings.proxy_addr.as_bytes(),
+    );
+    common4.proxy_port = settings.proxy_port;
+    common4.server_sel = settings.server_sel;
+
+    common1.cmd_flags_1 = plan.cmd_flags_1;
+    common1.cmd_flags_2 = plan.cmd_flags_2;
+    common1.cmd_dmm = plan.cmd_dmm;
+
+    // Char
+    common1.selected_char_id_1 = config.char_id;
+    common1.selected_char_id_2 = config.char_id;
+    bufcopy(&mut common1.selected_char_name, config.char_name.as_bytes());
+    common1.selected_char_hr = config.char_hr;
+    common2.selected_char_gr = config.char_gr;
+    common1.selected_char_status = plan.selected_char_status;
+
+    // User
+    bufcopy(&mut common1.user_name, config.user_name.as_bytes());
+    bufcopy(&mut common1.user_password, config.user_password.as_bytes());
+    common1.user_token_id = config.user_token_id;
+    bufcopy(&mut common1.user_token, config.user_token.as_bytes());
+    common1.user_rights = config.user_rights.unwrap_or_default().bits();
+
+    // Server
+    common1.server_entrance_count = config.entrance_count;
+    common1.server_current_ts = config.current_ts;
+
+    // Meta
+    bufcopy(&mut common1.path1, mhf_folder_name.as_bytes());
+    bufcopy(&mut common1.path2, mhf_folder_name.as_bytes());
+    bufcopy(&mut common1.ini_file, b"mhf.ini");
+    bufcopy(&mut common1.remote_addr, plan.remote_addr.as_bytes());
+    bufcopy(&mut common1.remote_host, plan.remote_host.as_bytes());
+    c
+}
+
+/// Puts the common parts together into the block for `plan.version`.
+pub(crate) fn assemble(config: &MhfConfig, plan: &Plan, commons: Commons) -> Data {
+    let Commons {
+        common1,
+        common2,
+        common3,
+        common4,
+        common5,
+    } = commons;
+    match plan.version {
+        MhfVersion::ZZ => {
+            let mut data = unsafe { Box::<DataZZ>::new_zeroed().assume_init() };
+
+            if config.char_ids.is_empty() {
+                data.char_ids[0] = config.char_id;
+            } else {
+                bufcopy(&mut data.char_ids, &config.char_ids);
+            }
+            data.graphics_ver = plan.graphics_ver;
+            bufcopy(
+                &mut data.alt_ip_address,
+                plan.alt_ip_address
+                    .as_deref()
+                    .unwrap_or_default()
+                    .as_bytes(),
+            );
+            data.server_expiry_ts = config.expiry_ts;
+            data.fixed_449184_0x1 = 0x1;
+
+            data.common1 = common1;
+            data.common2 = common2;
+            data.common3 = common3;
+            data.common4 = common4;
+            data.common5 = common5;
+            Data::ZZ(data)
+        }
+        MhfVersion::F5 => {
+            let mut data = unsafe { Box::<DataF5>::new_zeroed().assume_init() };
+
+            data.common1 = common1;
+            data.common2 = common2;
+            data.common3 = common3;
+            data.common4 = common4;
+            data.common5 = common5;
+            Data::F5(data)
+        }
+    }
+}
+
+pub(crate) fn encode_global(config: &MhfConfig) -> Box<GlobalData> {
+    let mut global_data = unsafe { Box::<GlobalData>::new_zeroed().assume_init() };
+    for (i, notice) in config.notices.iter().enumerate() {
+        global_data.notices_count[i] = notice.data.len() as u32;
+        global_data.notices_flags[i] = notice.flags;
+        bufcopy(&mut global_data.notices[i], notice.data.as_bytes());
+    }
+    global_data.mez_event_id = config.mez_event_id;
+    global_data.mez_start = config.mez_start;
+    global_data.mez_end = config.mez_end;
+    global_data.mez_solo_tickets = config.mez_solo_tickets;
+    global_data.mez_group_tickets = config.mez_group_tickets;
+    for (i, stall) in config.mez_stalls.iter().enumerate() {
+        global_data.mez_stalls[i] = *stall as u32;
+    }
+    global_data
+}
+
+/// The launcher block `run` passes to `mhDLL_Main`, minus what's only known once it runs: handles,
+/// procs, mutex names and pointers are zeroed.
+pub fn launcher_block(config: &MhfConfig, plan: &Plan) -> Vec<u8> {
+    let mut data = assemble(config, plan, encode_commons(config, plan));
+    research::apply(&plan.research, Target::Block, data.as_bytes_mut());
+    data.as_bytes().to_vec()
+}
+
+/// The global alloc `run` fills in for the game, with the notices and MezFes data.
+pub fn global_alloc(config: &MhfConfig, plan: &Plan) -> Vec<u8> {
+    let mut global_data = encode_global(config);
+    research::apply(
+        &plan.research,
+        Target::GlobalAlloc,
+        global_data.as_bytes_mut(),
+    );
+    global_data.as_bytes().to_vec()
+}
@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Seek, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::plan::plan_with_settings;
+use crate::settings::MhfSettings;
+use crate::{block, detect_version, plan, update, Error, MhfConfig, MhfVersion, Plan, Result};
+
+// Bumped whenever a file changes meaning, so `replay` can refuse bundles it doesn't understand
+pub const FORMAT: u32 = 1;
+
+const INFO_FILE: &str = "bundle.json";
+const CONFIG_FILE: &str = "config.json";
+const SETTINGS_FILE: &str = "mhf.ini";
+const PLAN_FILE: &str = "plan.json";
+const BLOCK_FILE: &str = "block.bin";
+const GLOBAL_FILE: &str = "global.bin";
+const LOG_FILE: &str = "launch.log";
+
+const HASHED_FILES: &[&str] = &["mhfo.dll", "mhfo-hd.dll", "mhf.ini"];
+// Same length as a real token, so the redacted config still validates
+const REDACTED_TOKEN: &str = "<redacted-token>";
+const REDACTED: &str = "<redacted>";
+
+/// What's known about the machine a bundle was made on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleInfo {
+    pub format: u32,
+    pub mhf_iel_version: String,
+    pub created_ts: u64,
+    pub os: String,
+    pub detected_version: Option<MhfVersion>,
+    pub configured_version: MhfVersion,
+    // Lowercase hex SHA-256 of the game files that were found
+    pub files: BTreeMap<String, String>,
+}
+
+/// A run of bytes that differ between the bundled and the replayed image.
+#[derive(Debug, Clone, Serialize)]
+pub struct Difference {
+    pub offset: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    pub matches: bool,
+    pub differences: Vec<Difference>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Replay {
+    pub info: BundleInfo,
+    pub plan: Plan,
+    pub plan_matches: bool,
+    pub block: Comparison,
+    pub global: Comparison,
+}
+
+fn zip_err(e: ZipError) -> Error {
+    Error::Bundle(e.to_string())
+}
+
+fn io_err(e: std::io::Error) -> Error {
+    Error::Bundle(e.to_string())
+}
+
+fn to_json(value: &impl Serialize) -> Result<Vec<u8>> {
+    serde_json::to_vec_pretty(value).map_err(|e| Error::Bundle(e.to_string()))
+}
+
+fn redacted(config: &MhfConfig) -> MhfConfig {
+    let mut config = config.clone();
+    if !config.user_token.is_empty() {
+        config.user_token = REDACTED_TOKEN.to_owned();
+    }
+    // The account name is often the same as the server login, so it goes too
+    for secret in [&mut config.user_name, &mut config.user_password] {
+        if !secret.is_empty() {
+            *secret = REDACTED.to_owned();
+        }
+    }
+    config
+}
+
+/// Writes a zip with the redacted config, `mhf.ini`, the plan, the launcher block and global alloc
+/// images, and `log` when given. The images are encoded from the redacted config, so they have no
+/// secrets either.
+pub fn create(
+    config: &MhfConfig,
+    log: Option<&[u8]>,
+    writer: impl Write + Seek,
+) -> Result<BundleInfo> {
+    let plan = plan(config)?;
+    let mut config = redacted(config);
+    // So the replay encodes the same paths, wherever it runs
+    config.mhf_folder = Some(plan.mhf_folder.clone());
+    let files = HASHED_FILES
+        .iter()
+        .filter_map(|name| {
+            let hash = update::hash_file(&plan.mhf_folder.join(name)).ok()?;
+            Some((name.to_string(), hash))
+        })
+        .collect();
+    let info = BundleInfo {
+        format: FORMAT,
+        mhf_iel_version: env!("CARGO_PKG_VERSION").to_owned(),
+        created_ts: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        os: std::env::consts::OS.to_owned(),
+        detected_version: detect_version(&plan.mhf_folder),
+        configured_version: config.version,
+        files,
+    };
+
+    let mut entries = vec![
+        (INFO_FILE, to_json(&info)?),
+        (CONFIG_FILE, to_json(&config)?),
+        (PLAN_FILE, to_json(&plan)?),
+        (BLOCK_FILE, block::launcher_block(&config, &plan)),
+        (GLOBAL_FILE, block::global_alloc(&config, &plan)),
+    ];
+    if let Ok(settings) = std::fs::read(plan.mhf_folder.join(SETTINGS_FILE)) {
+        entries.push((SETTINGS_FILE, settings));
+    }
+    if let Some(log) = log {
+        entries.push((LOG_FILE, log.to_vec()));
+    }
+
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, data) in entries {
+        zip.start_file(name, options).map_err(zip_err)?;
+        zip.write_all(&data).map_err(io_err)?;
+    }
+    zip.finish().map_err(zip_err)?;
+    Ok(info)
+}
+
+fn read_file(zip: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Option<Vec<u8>>> {
+    let mut file = match zip.by_name(name) {
+        Ok(file) => file,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(zip_err(e)),
+    };
+    let mut data = vec![];
+    file.read_to_end(&mut data).map_err(io_err)?;
+    Ok(Some(data))
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(
+    zip: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> Result<T> {
+    let data = read_file(zip, name)?.ok_or_else(|| Error::Bundle(format!("missing '{name}'")))?;
+    serde_json::from_slice(&data).map_err(|e| Error::Bundle(format!("invalid '{name}': {e}")))
+}
+
+fn compare(expected: Option<&[u8]>, actual: &[u8]) -> Comparison {
+    let expected = expected.unwrap_or_default();
+    let mut differences: Vec<Difference> = vec![];
+    for offset in 0..expected.len().max(actual.len()) {
+        if expected.get(offset) == actual.get(offset) {
+            continue;
+        }
+        match differences.last_mut() {
+            Some(last) if last.offset + last.len == offset => last.len += 1,
+            _ => differences.push(Difference { offset, len: 1 }),
+        }
+    }
+    Comparison {
+        matches: differences.is_empty(),
+        differences,
+    }
+}
+
+/// Runs a bundle's config through `plan` with its `mhf.ini`, without touching the local game
+/// folder, and compares what it encodes to the bundled images.
+pub fn replay(data: &[u8]) -> Result<Replay> {
+    let mut zip = ZipArchive::new(Cursor::new(data)).map_err(zip_err)?;
+    let info: BundleInfo = read_json(&mut zip, INFO_FILE)?;
+    if info.format != FORMAT {
+        return Err(Error::Bundle(format!(
+            "unsupported format {}, expected {FORMAT}",
+            info.format
+        )));
+    }
+    let config: MhfConfig = read_json(&mut zip, CONFIG_FILE)?;
+    let settings = MhfSettings::parse(&read_file(&mut zip, SETTINGS_FILE)?.unwrap_or_default());
+    let plan = plan_with_settings(&config, |_| settings)?;
+
+    let bundled_plan: serde_json::Value = read_json(&mut zip, PLAN_FILE)?;
+    let plan_matches = serde_json::to_value(&plan).ok() == Some(bundled_plan);
+    let block = compare(
+        read_file(&mut zip, BLOCK_FILE)?.as_deref(),
+        &block::launcher_block(&config, &plan),
+    );
+    let global = compare(
+        read_file(&mut zip, GLOBAL_FILE)?.as_deref(),
+        &block::global_alloc(&config, &plan),
+    );
+    Ok(Replay {
+        info,
+        plan,
+        plan_matches,
+        block,
+        global,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::Notice;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("mhf-iel-bundle-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn config(mhf_folder: &std::path::Path) -> MhfConfig {
+        MhfConfig {
+            char_id: 1,
+            char_name: "Hunter".to_owned(),
+            user_token: "0123456789abcdef".to_owned(),
+            user_name: "account".to_owned(),
+            user_password: "hunter2".to_owned(),
+            notices: vec![Notice {
+                flags: 1,
+                data: "<BODY>Hi".to_owned(),
+            }],
+            mhf_folder: Some(mhf_folder.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn bundle(config: &MhfConfig) -> Vec<u8> {
+        let mut data = Cursor::new(vec![]);
+        create(config, Some(b"log"), &mut data).unwrap();
+        data.into_inner()
+    }
+
+    fn entry(data: &[u8], name: &str) -> Option<Vec<u8>> {
+        let mut zip = ZipArchive::new(Cursor::new(data)).unwrap();
+        read_file(&mut zip, name).unwrap()
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        let dir = TempDir::new("redacts");
+        let data = bundle(&config(&dir.0));
+        let config: MhfConfig =
+            serde_json::from_slice(&entry(&data, CONFIG_FILE).unwrap()).unwrap();
+        assert_eq!(config.user_token, REDACTED_TOKEN);
+        assert_eq!(config.user_name, REDACTED);
+        assert_eq!(config.user_password, REDACTED);
+        assert_eq!(config.char_name, "Hunter");
+        for name in [CONFIG_FILE, PLAN_FILE, BLOCK_FILE, GLOBAL_FILE] {
+            let file = entry(&data, name).unwrap();
+            for secret in ["0123456789abcdef", "account", "hunter2"] {
+                assert!(
+                    !file.windows(secret.len()).any(|w| w == secret.as_bytes()),
+                    "'{secret}' is in '{name}'"
+                );
+            }
+        }
+        assert_eq!(entry(&data, LOG_FILE).unwrap(), b"log");
+    }
+
+    #[test]
+    fn replays() {
+        let dir = TempDir::new("replays");
+        fs::write(dir.0.join(SETTINGS_FILE), "[VIDEO]\nGRAPHICS_VER=0\n").unwrap();
+        fs::write(dir.0.join("mhfo.dll"), b"dll").unwrap();
+        let data = bundle(&config(&dir.0));
+        // Replayed without the game folder
+        drop(dir);
+        let replay = replay(&data).unwrap();
+        assert!(replay.plan_matches);
+        assert!(replay.block.matches);
+        assert!(replay.global.matches);
+        assert_eq!(replay.plan.dll, "mhfo.dll");
+        assert_eq!(replay.info.format, FORMAT);
+        assert_eq!(
+            replay.info.files.keys().collect::<Vec<_>>(),
+            ["mhf.ini", "mhfo.dll"]
+        );
+    }
+
+    #[test]
+    fn replay_finds_differences() {
+        let dir = TempDir::new("differences");
+        let data = bundle(&config(&dir.0));
+        let block = entry(&data, BLOCK_FILE).unwrap();
+        let mut changed = block.clone();
+        changed[8] ^= 0xff;
+        changed[9] ^= 0xff;
+        changed.push(0);
+        let comparison = compare(Some(&block), &changed);
+        assert!(!comparison.matches);
+        let differences: Vec<_> = comparison
+            .differences
+            .iter()
+            .map(|d| (d.offset, d.len))
+            .collect();
+        assert_eq!(differences, [(8, 2), (block.len(), 1)]);
+
+        assert!(matches!(replay(b"not a zip"), Err(Error::Bundle(_))));
+    }
+}
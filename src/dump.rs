@@ -0,0 +1,456 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::block::{Data, GlobalData, GLOBAL_ALLOC_SIZE};
+use crate::registry::{self, Confidence, Kind, Part};
+use crate::rights::UserRights;
+use crate::settings::MhfSettings;
+use crate::{CliFlags, Error, MezFesStall, MhfConfig, MhfVersion, Notice, Result};
+
+// Filled in by `run` right before the game starts, so they never match between two launches
+const RUNTIME_FIELDS: &[&str] = &["mutex_master_name", "mutex_master_ready_name"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    U32(u32),
+    Array(Vec<u32>),
+    String(String),
+    /// Space separated hex
+    Bytes(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedField {
+    pub name: &'static str,
+    pub part: Part,
+    pub offset: usize,
+    pub kind: Kind,
+    pub confidence: Confidence,
+    pub value: FieldValue,
+}
+
+/// A launcher block, and optionally a global alloc, read back into something readable.
+#[derive(Debug, Clone, Serialize)]
+pub struct Decoded {
+    pub version: MhfVersion,
+    /// Roughly the config that would encode the same data. Without a global alloc there are no
+    /// notices or MezFes data
+    pub config: MhfConfig,
+    pub settings: MhfSettings,
+    pub fields: Vec<DecodedField>,
+    /// Unknown fields that aren't all zeros
+    pub unknown: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldDiff {
+    pub name: &'static str,
+    pub part: Part,
+    pub offset: usize,
+    pub left: FieldValue,
+    pub right: FieldValue,
+    /// Handles, pointers and the like, which differ between any two launches
+    pub runtime: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DumpDiff {
+    /// Whether everything but the runtime fields is the same
+    pub matches: bool,
+    pub differences: Vec<FieldDiff>,
+}
+
+fn cstr(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&c| c == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn value(kind: Kind, bytes: &[u8]) -> FieldValue {
+    let words = || {
+        bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+    };
+    match kind {
+        Kind::U32 | Kind::Handle | Kind::Pointer | Kind::Proc => {
+            FieldValue::U32(words().next().unwrap_or_default())
+        }
+        Kind::U32Array => FieldValue::Array(words().collect()),
+        Kind::U16Array => FieldValue::Array(
+            bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]) as u32)
+                .collect(),
+        ),
+        Kind::String => FieldValue::String(cstr(bytes)),
+        Kind::Bytes | Kind::Marker => FieldValue::Bytes(hex(bytes)),
+    }
+}
+
+fn fields(version: MhfVersion, block: &[u8], global: Option<&[u8]>) -> Vec<DecodedField> {
+    registry::fields()
+        .into_iter()
+        .filter(|f| f.kind != Kind::Marker)
+        .filter_map(|f| {
+            let offset = f.offset(version)?;
+            let bytes = match f.part {
+                Part::GlobalData => global?,
+                _ => block,
+            };
+            Some(DecodedField {
+                name: f.name,
+                part: f.part,
+                offset,
+                kind: f.kind,
+                confidence: f.confidence,
+                value: value(f.kind, &bytes[offset..offset + f.size]),
+            })
+        })
+        .collect()
+}
+
+/// `mhf_flags` as `plan` would have turned them into `cmd_flags_1` and `cmd_flags_2`.
+fn mhf_flags(cmd_flags_1: u32, cmd_flags_2: u32) -> Option<Vec<CliFlags>> {
+    let mut flags: Vec<CliFlags> = CliFlags::try_from(cmd_flags_1 as u8)
+        .ok()
+        .filter(|_| cmd_flags_1 <= 9)
+        .into_iter()
+        .collect();
+    if cmd_flags_2 & 4 != 0 && cmd_flags_1 != 9 {
+        flags.push(CliFlags::NpMhfoTest);
+    }
+    (!flags.is_empty()).then_some(flags)
+}
+
+/// Decodes a `version` launcher block, as passed to `mhDLL_Main`, and the global alloc it points
+/// to. Both can be longer than needed, as when dumped from a debugger.
+pub fn decode(version: MhfVersion, block: &[u8], global: Option<&[u8]>) -> Result<Decoded> {
+    let data = Data::from_bytes(version, block).ok_or_else(|| {
+        Error::Dump(format!(
+            "the {version} launcher block has {:#x} bytes, got {:#x}",
+            Data::size(version),
+            block.len()
+        ))
+    })?;
+    let global_data = global
+        .map(|global| {
+            GlobalData::from_bytes(global).ok_or_else(|| {
+                Error::Dump(format!(
+                    "the global alloc has {GLOBAL_ALLOC_SIZE:#x} bytes, got {:#x}",
+                    global.len()
+                ))
+            })
+        })
+        .transpose()?;
+
+    let (c1, c2, c3, c4) = match &data {
+        Data::ZZ(d) => (&d.common1, &d.common2, &d.common3, &d.common4),
+        Data::F5(d) => (&d.common1, &d.common2, &d.common3, &d.common4),
+    };
+    let settings = MhfSettings {
+        preset_level: c2.preset_level,
+        custom: c2.custom,
+        fullscreen_mode: c2.fullscreen_mode,
+        window_resolution_w: c2.window_resolution_w,
+        window_resolution_h: c2.window_resolution_h,
+        fullscreen_resolution_w: c2.fullscreen_resolution_w,
+        fullscreen_resolution_h: c2.fullscreen_resolution_h,
+        graphics_ver: match &data {
+            Data::ZZ(d) => d.graphics_ver,
+            Data::F5(_) => 0,
+        },
+        disp_max_char: c2.disp_max_char,
+        texture_dxt_use: c2.texture_dxt_use,
+        now_monitor_wh: c2.now_monitor_wh,
+        sound_notuse: c3.sound_notuse,
+        sound_volume: c3.sound_volume,
+        sound_volume_inactivity: c3.sound_volume_inactivity,
+        sound_volume_minimize: c3.sound_volume_minimize,
+        sound_frequency: c3.sound_frequency,
+        sound_buffernum: c3.sound_buffernum,
+        language: c3.language,
+        font_quality: c3.font_quality,
+        font_weight: c3.font_weight,
+        font_name: {
+            let len = c3.font_name.iter().position(|&c| c == 0).unwrap_or(0x60);
+            c3.font_name[..len].to_vec()
+        },
+        drawskip: c4.drawskip,
+        clogdis: c4.clogdis,
+        proxy_use: c4.proxy_use,
+        proxy_ie: c4.proxy_ie,
+        proxy_set: c4.proxy_set,
+        proxy_addr: cstr(&c4.proxy_addr),
+        proxy_port: c4.proxy_port,
+        server_sel: c4.server_sel,
+    };
+
+    let remote_addr = cstr(&c1.remote_addr);
+    let (server_host, server_port) = match remote_addr.rsplit_once(':') {
+        Some((host, port)) => (host.to_owned(), port.parse().unwrap_or_default()),
+        None => (cstr(&c1.remote_host), 0),
+    };
+    let path = cstr(&c1.path1);
+    let mut config = MhfConfig {
+        char_id: c1.selected_char_id_1,
+        char_name: cstr(&c1.selected_char_name),
+        char_gr: c2.selected_char_gr,
+        char_hr: c1.selected_char_hr,
+        char_ids: vec![],
+        char_new: c1.selected_char_status != 0,
+        user_token_id: c1.user_token_id,
+        user_token: String::from_utf8_lossy(&c1.user_token).into_owned(),
+        user_name: cstr(&c1.user_name),
+        user_password: cstr(&c1.user_password),
+        // `run` writes the default when it's unset
+        user_rights: Some(UserRights::from_bits_retain(c1.user_rights))
+            .filter(|rights| *rights != UserRights::default()),
+        server_host,
+        server_port,
+        entrance_count: c1.server_entrance_count,
+        current_ts: c1.server_current_ts,
+        version,
+        mhf_folder: (!path.is_empty()).then(|| PathBuf::from(path.trim_end_matches(['/', '\\']))),
+        mhf_flags: mhf_flags(c1.cmd_flags_1, c1.cmd_flags_2),
+        ..Default::default()
+    };
+    if let Data::ZZ(d) = &data {
+        // `run` puts `char_id` there by itself when `char_ids` is empty
+        config.char_ids = d.char_ids.iter().copied().filter(|&id| id != 0).collect();
+        if config.char_ids == [config.char_id] {
+            config.char_ids.clear();
+        }
+        config.expiry_ts = d.server_expiry_ts;
+    }
+    if let Some(g) = &global_data {
+        config.notices = (0..g.notices.len())
+            .filter(|&i| g.notices_count[i] != 0)
+            .map(|i| {
+                let len = (g.notices_count[i] as usize).min(g.notices[i].len());
+                Notice {
+                    flags: g.notices_flags[i],
+                    data: String::from_utf8_lossy(&g.notices[i][..len]).into_owned(),
+                }
+            })
+            .collect();
+        config.mez_event_id = g.mez_event_id;
+        config.mez_start = g.mez_start;
+        config.mez_end = g.mez_end;
+        config.mez_solo_tickets = g.mez_solo_tickets;
+        config.mez_group_tickets = g.mez_group_tickets;
+        config.mez_stalls = g
+            .mez_stalls
+            .iter()
+            .filter_map(|&s| MezFesStall::try_from(s).ok())
+            .collect();
+    }
+
+    let fields = fields(
+        version,
+        data.as_bytes(),
+        global_data.as_ref().map(|g| g.as_bytes()),
+    );
+    let unknown = fields
+        .iter()
+        .filter(|f| f.confidence == Confidence::Unknown)
+        .filter(|f| match &f.value {
+            FieldValue::U32(v) => *v != 0,
+            FieldValue::Bytes(v) => v.split(' ').any(|b| b != "00"),
+            _ => true,
+        })
+        .map(|f| f.name)
+        .collect();
+    Ok(Decoded {
+        version,
+        config,
+        settings,
+        fields,
+        unknown,
+    })
+}
+
+/// Compares two decoded dumps field by field. The global alloc is only compared when both have
+/// one.
+pub fn diff(left: &Decoded, right: &Decoded) -> Result<DumpDiff> {
+    if left.version != right.version {
+        return Err(Error::Dump(format!(
+            "can't compare a {} dump with a {} one",
+            left.version, right.version
+        )));
+    }
+    let differences: Vec<FieldDiff> = left
+        .fields
+        .iter()
+        .filter_map(|l| {
+            let r = right.fields.iter().find(|r| r.name == l.name)?;
+            (l.value != r.value).then(|| FieldDiff {
+                name: l.name,
+                part: l.part,
+                offset: l.offset,
+                left: l.value.clone(),
+                right: r.value.clone(),
+                runtime: matches!(l.kind, Kind::Handle | Kind::Pointer | Kind::Proc)
+                    || RUNTIME_FIELDS.contains(&l.name),
+            })
+        })
+        .collect();
+    Ok(DumpDiff {
+        matches: differences.iter().all(|d| d.runtime),
+        differences,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{global_alloc, launcher_block};
+    use crate::plan::plan_with_settings;
+
+    fn config(version: MhfVersion) -> MhfConfig {
+        let mut config = MhfConfig {
+            char_id: 7,
+            char_name: "Hunter".to_owned(),
+            char_gr: 100,
+            char_hr: 999,
+            char_new: true,
+            user_token_id: 3,
+            user_token: "0123456789abcdef".to_owned(),
+            user_name: "account".to_owned(),
+            user_password: "password".to_owned(),
+            user_rights: Some(UserRights::PREMIUM),
+            server_host: "10.0.0.2".to_owned(),
+            server_port: 54001,
+            entrance_count: 2,
+            current_ts: 1_700_000_000,
+            notices: vec![Notice {
+                flags: 1,
+                data: "<BODY>Hi".to_owned(),
+            }],
+            mez_event_id: 5,
+            mez_start: 1_700_000_000,
+            mez_end: 1_700_600_000,
+            mez_solo_tickets: 10,
+            mez_group_tickets: 4,
+            mez_stalls: vec![MezFesStall::Pachinko, MezFesStall::StallMap],
+            version,
+            mhf_folder: Some(PathBuf::from("C:/MHF")),
+            mhf_flags: Some(vec![CliFlags::Selfup, CliFlags::NpMhfoTest]),
+            ..Default::default()
+        };
+        // Only ZZ has these
+        if version == MhfVersion::ZZ {
+            config.char_ids = vec![7, 8];
+            config.expiry_ts = 1_800_000_000;
+        }
+        config
+    }
+
+    fn encode(config: &MhfConfig) -> (Vec<u8>, Vec<u8>, MhfSettings) {
+        let plan = plan_with_settings(config, |_| MhfSettings::default()).unwrap();
+        (
+            launcher_block(config, &plan),
+            global_alloc(config, &plan),
+            plan.settings,
+        )
+    }
+
+    #[test]
+    fn decodes_what_was_encoded() {
+        for version in [MhfVersion::ZZ, MhfVersion::F5] {
+            let config = config(version);
+            let (block, global, mut settings) = encode(&config);
+            // F5 has no graphics version to write it to
+            if version == MhfVersion::F5 {
+                settings.graphics_ver = 0;
+            }
+            let decoded = decode(version, &block, Some(&global)).unwrap();
+            assert_eq!(
+                serde_json::to_value(&decoded.config).unwrap(),
+                serde_json::to_value(&config).unwrap(),
+                "{version}"
+            );
+            assert_eq!(
+                serde_json::to_value(&decoded.settings).unwrap(),
+                serde_json::to_value(&settings).unwrap(),
+            );
+            assert!(decoded.unknown.is_empty(), "{:?}", decoded.unknown);
+
+            // Without the global alloc, there are no notices or MezFes data
+            let decoded = decode(version, &block, None).unwrap();
+            assert!(decoded.config.notices.is_empty());
+            assert_eq!(decoded.config.mez_start, 0);
+            assert!(decoded.fields.iter().all(|f| f.part != Part::GlobalData));
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let (block, global, _) = encode(&config(MhfVersion::ZZ));
+        // Dumps can be longer
+        let mut longer = block.clone();
+        longer.extend([0; 16]);
+        assert!(decode(MhfVersion::ZZ, &longer, Some(&global)).is_ok());
+        assert!(matches!(
+            decode(MhfVersion::ZZ, &block[..block.len() - 1], None),
+            Err(Error::Dump(_))
+        ));
+        assert!(matches!(
+            decode(MhfVersion::ZZ, &block, Some(&global[..16])),
+            Err(Error::Dump(_))
+        ));
+    }
+
+    #[test]
+    fn diff_ignores_runtime_fields() {
+        let version = MhfVersion::ZZ;
+        let (block, global, _) = encode(&config(version));
+        let left = decode(version, &block, Some(&global)).unwrap();
+
+        let mut runtime = block.clone();
+        let mut changed = vec![];
+        for f in registry::fields() {
+            let is_runtime = matches!(f.kind, Kind::Handle | Kind::Pointer | Kind::Proc)
+                || RUNTIME_FIELDS.contains(&f.name);
+            match f.offset(version) {
+                Some(offset) if is_runtime && f.part != Part::GlobalData => {
+                    runtime[offset] ^= 0x41;
+                    changed.push(f.name);
+                }
+                _ => {}
+            }
+        }
+        assert!(changed.contains(&"mutex_master_name"));
+        let right = decode(version, &runtime, Some(&global)).unwrap();
+        let same = diff(&left, &right).unwrap();
+        assert!(same.matches);
+        let mut names: Vec<_> = same.differences.iter().map(|d| d.name).collect();
+        names.sort();
+        changed.sort();
+        assert_eq!(names, changed);
+        assert!(same.differences.iter().all(|d| d.runtime));
+
+        let mut other = config(version);
+        other.char_id = 8;
+        let (block, global, _) = encode(&other);
+        let right = decode(version, &block, Some(&global)).unwrap();
+        let different = diff(&left, &right).unwrap();
+        assert!(!different.matches);
+        assert!(different
+            .differences
+            .iter()
+            .any(|d| d.name == "selected_char_id_1" && !d.runtime));
+
+        let f5 = decode(MhfVersion::F5, &encode(&config(MhfVersion::F5)).0, None).unwrap();
+        assert!(matches!(diff(&left, &f5), Err(Error::Dump(_))));
+    }
+}
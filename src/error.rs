@@ -8,6 +8,21 @@ pub enum Error {
     Dll,
     ProcNotFound,
     TokenLength,
+    MissingField(&'static str),
+    TooMany(&'static str, usize),
+    TooLong(&'static str, usize),
+    Update(String),
+    Server(String),
+    Unsupported,
+    Launch(String),
+    Cancelled,
+    Ipc(String),
+    NotAllowed(String),
+    Ticket(String),
+    Bundle(String),
+    Research(String),
+    Dump(String),
+    MezFes(String),
 }
 
 impl Display for Error {
@@ -19,6 +34,21 @@ impl Display for Error {
             Self::Dll => write!(f, "unable to load or free mhfo dll"),
             Self::ProcNotFound => write!(f, "unable to find mhDLL_Main proc in mhfo-hd.dll"),
             Self::TokenLength => write!(f, "user token must have a length of 16"),
+            Self::MissingField(field) => write!(f, "'{field}' is required"),
+            Self::TooMany(field, max) => write!(f, "'{field}' can't have more than {max} entries"),
+            Self::TooLong(field, max) => write!(f, "'{field}' can't be longer than {max} bytes"),
+            Self::Update(e) => write!(f, "unable to update game files: {e}"),
+            Self::Server(e) => write!(f, "unable to communicate with server: {e}"),
+            Self::Unsupported => write!(f, "unable to run the game outside of Windows"),
+            Self::Launch(e) => write!(f, "unable to start launcher process: {e}"),
+            Self::Cancelled => write!(f, "launch was cancelled"),
+            Self::Ipc(e) => write!(f, "unable to use event channel: {e}"),
+            Self::NotAllowed(server) => write!(f, "'{server}' isn't an allowed server"),
+            Self::Ticket(e) => write!(f, "invalid launch ticket: {e}"),
+            Self::Bundle(e) => write!(f, "unable to read or write bundle: {e}"),
+            Self::Research(e) => write!(f, "invalid research write: {e}"),
+            Self::Dump(e) => write!(f, "unable to decode dump: {e}"),
+            Self::MezFes(e) => write!(f, "invalid MezFes event: {e}"),
         }
     }
 }
@@ -0,0 +1,225 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::{Error, Event, Result};
+
+const PIPE_PREFIX: &str = r"\\.\pipe\";
+
+/// Where `EventWriter` sends events. The caller listens on it before starting `mhf-iel`.
+///
+/// Parsed from `unix:<path>`, `pipe:<name>` or `\\.\pipe\<name>`. Anything else is a pipe name on
+/// Windows and a socket path elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpcAddress {
+    Pipe(String),
+    Unix(PathBuf),
+}
+
+impl FromStr for IpcAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            return Err(Error::Ipc("empty address".to_owned()));
+        }
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(Self::Unix(path.into()));
+        }
+        if let Some(name) = s.strip_prefix("pipe:") {
+            return Ok(Self::Pipe(format!("{PIPE_PREFIX}{name}")));
+        }
+        if s.starts_with(PIPE_PREFIX) || cfg!(windows) {
+            let name = s.strip_prefix(PIPE_PREFIX).unwrap_or(s);
+            return Ok(Self::Pipe(format!("{PIPE_PREFIX}{name}")));
+        }
+        Ok(Self::Unix(s.into()))
+    }
+}
+
+// std has no AF_UNIX sockets on Windows, but both Windows 10 and Wine support them through Winsock
+#[cfg(windows)]
+mod winsock {
+    use std::io::{self, Write};
+    use std::path::Path;
+
+    use windows::Win32::Networking::WinSock::{
+        closesocket, connect, send, socket, WSAGetLastError, WSAStartup, ADDRESS_FAMILY, AF_UNIX,
+        INVALID_SOCKET, SEND_RECV_FLAGS, SOCKADDR, SOCKADDR_UN, SOCKET, SOCK_STREAM, WSADATA,
+    };
+
+    pub struct UnixStream(SOCKET);
+
+    fn last_error() -> io::Error {
+        io::Error::from_raw_os_error(unsafe { WSAGetLastError() }.0)
+    }
+
+    impl UnixStream {
+        pub fn connect(path: &Path) -> io::Result<Self> {
+            let path = path.to_str().ok_or(io::ErrorKind::InvalidInput)?.as_bytes();
+            let mut addr = SOCKADDR_UN {
+                sun_family: ADDRESS_FAMILY(AF_UNIX),
+                sun_path: [0; 108],
+            };
+            // Keeps the terminating null byte
+            if path.len() >= addr.sun_path.len() {
+                return Err(io::ErrorKind::InvalidInput.into());
+            }
+            addr.sun_path[..path.len()].copy_from_slice(path);
+            let mut data = WSADATA::default();
+            if unsafe { WSAStartup(0x202, &mut data) } != 0 {
+                return Err(last_error());
+            }
+            let s = unsafe { socket(AF_UNIX as i32, SOCK_STREAM, 0) };
+            if s == INVALID_SOCKET {
+                return Err(last_error());
+            }
+            let addr_ptr = &addr as *const SOCKADDR_UN as *const SOCKADDR;
+            let addr_len = std::mem::size_of::<SOCKADDR_UN>() as i32;
+            if unsafe { connect(s, addr_ptr, addr_len) } != 0 {
+                let e = last_error();
+                unsafe { closesocket(s) };
+                return Err(e);
+            }
+            Ok(Self(s))
+        }
+    }
+
+    impl Write for UnixStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let sent = unsafe { send(self.0, buf, SEND_RECV_FLAGS(0)) };
+            match sent {
+                n if n < 0 => Err(last_error()),
+                n => Ok(n as usize),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Drop for UnixStream {
+        fn drop(&mut self) {
+            unsafe { closesocket(self.0) };
+        }
+    }
+}
+
+/// Sends events as JSON lines, the same ones `mhf-iel-cli --events` prints.
+pub struct EventWriter(Box<dyn Write + Send>);
+
+impl EventWriter {
+    pub fn connect(address: &IpcAddress) -> Result<Self> {
+        let ipc_error = |e: std::io::Error| Error::Ipc(format!("{address:?}: {e}"));
+        let stream: Box<dyn Write + Send> = match address {
+            // Pipes are opened like any other file on the client side
+            #[cfg(windows)]
+            IpcAddress::Pipe(name) => Box::new(
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(name)
+                    .map_err(ipc_error)?,
+            ),
+            #[cfg(not(windows))]
+            IpcAddress::Pipe(_) => {
+                return Err(Error::Ipc(
+                    "named pipes are only available on Windows".to_owned(),
+                ))
+            }
+            #[cfg(windows)]
+            IpcAddress::Unix(path) => {
+                Box::new(winsock::UnixStream::connect(path).map_err(ipc_error)?)
+            }
+            #[cfg(unix)]
+            IpcAddress::Unix(path) => {
+                Box::new(std::os::unix::net::UnixStream::connect(path).map_err(ipc_error)?)
+            }
+        };
+        Ok(Self(stream))
+    }
+
+    pub fn send(&mut self, event: &Event) -> Result<()> {
+        let mut line = serde_json::to_vec(event).map_err(|e| Error::Ipc(e.to_string()))?;
+        line.push(b'\n');
+        self.0
+            .write_all(&line)
+            .and_then(|_| self.0.flush())
+            .map_err(|e| Error::Ipc(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses() {
+        let parse = |s: &str| s.parse::<IpcAddress>().unwrap();
+        let pipe = |name: &str| IpcAddress::Pipe(name.to_owned());
+        assert_eq!(
+            parse("unix:/tmp/mhf.sock"),
+            IpcAddress::Unix("/tmp/mhf.sock".into())
+        );
+        assert_eq!(parse("pipe:mhf"), pipe(r"\\.\pipe\mhf"));
+        assert_eq!(parse(r"\\.\pipe\mhf"), pipe(r"\\.\pipe\mhf"));
+        let bare = if cfg!(windows) {
+            pipe(r"\\.\pipe\mhf")
+        } else {
+            IpcAddress::Unix("mhf".into())
+        };
+        assert_eq!(parse("mhf"), bare);
+        assert!(matches!("".parse::<IpcAddress>(), Err(Error::Ipc(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sends_json_lines() {
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("mhf-iel-ipc-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let address = format!("unix:{}", path.display()).parse().unwrap();
+        let events = [
+            Event::ConfigValidated,
+            Event::DllLoaded,
+            Event::MainEntered,
+            Event::GameExited { code: -1 },
+        ];
+        let mut writer = EventWriter::connect(&address).unwrap();
+        for event in &events {
+            writer.send(event).unwrap();
+        }
+        drop(writer);
+
+        let (stream, _) = listener.accept().unwrap();
+        let lines: Vec<String> = BufReader::new(stream).lines().map(|l| l.unwrap()).collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"event":"config_validated"}"#,
+                r#"{"event":"dll_loaded"}"#,
+                r#"{"event":"main_entered"}"#,
+                r#"{"event":"game_exited","code":-1}"#,
+            ]
+        );
+        let decoded: Vec<Event> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(decoded, events);
+        std::fs::remove_file(&path).unwrap();
+
+        // Nobody listening any more
+        assert!(matches!(EventWriter::connect(&address), Err(Error::Ipc(_))));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn no_pipes() {
+        let address = "pipe:mhf".parse().unwrap();
+        assert!(matches!(EventWriter::connect(&address), Err(Error::Ipc(_))));
+    }
+}
@@ -1,16 +1,43 @@
 #![feature(generic_arg_infer)]
 #![feature(new_uninit)]
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod block;
+pub mod bundle;
+pub mod dump;
 mod error;
+pub mod ipc;
+pub mod mezfes;
+#[cfg_attr(not(windows), path = "unsupported.rs")]
 mod mhf;
+pub mod notice;
+mod plan;
+pub mod policy;
+pub mod registry;
+pub mod research;
+pub mod rights;
+pub mod session;
+pub mod settings;
+pub mod signv2;
+pub mod update;
+#[cfg(windows)]
 mod utils;
 
 pub use error::Error;
 pub use error::Result;
+pub use plan::{detect_version, plan, Plan};
 use serde::Serialize;
+pub use session::{launch_detached, GameSession};
 
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use num_enum::TryFromPrimitive;
+use research::ResearchWrite;
+use rights::UserRights;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 #[repr(u8)]
@@ -21,6 +48,7 @@ use serde::Deserialize;
     Copy,
     Serialize,
     Deserialize,
+    JsonSchema,
     TryFromPrimitive,
     PartialEq,
     Eq,
@@ -33,8 +61,29 @@ pub enum MhfVersion {
     F5 = 2,
 }
 
+impl FromStr for MhfVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "ZZ" => Ok(Self::ZZ),
+            "F5" => Ok(Self::F5),
+            _ => Err(format!("unknown version '{s}', expected 'ZZ' or 'F5'")),
+        }
+    }
+}
+
+impl std::fmt::Display for MhfVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ZZ => write!(f, "ZZ"),
+            Self::F5 => write!(f, "F5"),
+        }
+    }
+}
+
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, Deserialize, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, TryFromPrimitive)]
 pub enum CliFlags {
     Selfup = 1,
     Restat = 2,
@@ -49,7 +98,9 @@ pub enum CliFlags {
 }
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy, Deserialize, TryFromPrimitive)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, TryFromPrimitive,
+)]
 pub enum MezFesStall {
     TokotokoPartnya = 2,
     Pachinko = 3,
@@ -62,47 +113,389 @@ pub enum MezFesStall {
     StallMap = 10,
 }
 
-#[derive(Debug, Deserialize)]
+/// A notice as the game reads it, `notice::NoticeBuilder` writes the markup in `data`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Notice {
+    /// `notice::NoticeFlags` bits
     pub flags: u16,
+    #[schemars(length(max = "MAX_NOTICE_LENGTH"))]
     pub data: String,
 }
 
-#[derive(Debug, Deserialize, Default)]
+/// Only `char_id` and `user_token` are required, everything else falls back to `MhfConfig::default()`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct MhfConfig {
+    #[schemars(range(min = 1))]
     pub char_id: u32,
+    #[schemars(length(max = 0xf))]
     pub char_name: String,
     pub char_gr: u32,
     pub char_hr: u32,
+    #[schemars(length(max = "MAX_CHAR_IDS"))]
     pub char_ids: Vec<u32>,
     pub char_new: bool,
     pub user_token_id: u32,
+    #[schemars(length(equal = 16))]
     pub user_token: String,
+    #[schemars(length(max = 0x7ff))]
     pub user_name: String,
+    #[schemars(length(max = 0x7ff))]
     pub user_password: String,
-    pub user_rights: u32,
+    /// Defaults to `UserRights::default()` when unset
+    pub user_rights: Option<UserRights>,
+    // Limited by ZZ, F5 allows a few more bytes
+    #[schemars(length(max = 186))]
     pub server_host: String,
     pub server_port: u32,
     pub entrance_count: u32,
+    /// Defaults to the current time
     pub current_ts: u32,
     pub expiry_ts: u32,
+    #[schemars(length(max = "MAX_NOTICES"))]
     pub notices: Vec<Notice>,
     pub mez_event_id: u32,
     pub mez_start: u32,
     pub mez_end: u32,
     pub mez_solo_tickets: u32,
     pub mez_group_tickets: u32,
+    #[schemars(length(max = "MAX_MEZ_STALLS"))]
     pub mez_stalls: Vec<MezFesStall>,
     pub version: MhfVersion,
-
-    // Optional
+    #[schemars(length(max = 0x3fe))]
     pub mhf_folder: Option<PathBuf>,
     pub mhf_flags: Option<Vec<CliFlags>>,
+    /// Raw writes into the launcher data for reverse engineering, applied right before the game
+    /// starts. Nothing checks what they overwrite, so never use them to play
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub research: Vec<ResearchWrite>,
+}
+
+impl Default for MhfConfig {
+    fn default() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32);
+        Self {
+            char_id: 0,
+            char_name: String::new(),
+            char_gr: 0,
+            char_hr: 0,
+            char_ids: vec![],
+            char_new: false,
+            user_token_id: 0,
+            user_token: String::new(),
+            user_name: String::new(),
+            user_password: String::new(),
+            user_rights: None,
+            server_host: "127.0.0.1".to_owned(),
+            server_port: 53312,
+            entrance_count: 1,
+            current_ts: now,
+            expiry_ts: u32::MAX,
+            notices: vec![],
+            mez_event_id: 0,
+            mez_start: 0,
+            mez_end: 0,
+            mez_solo_tickets: 0,
+            mez_group_tickets: 0,
+            mez_stalls: vec![],
+            version: MhfVersion::default(),
+            mhf_folder: None,
+            mhf_flags: None,
+            research: vec![],
+        }
+    }
+}
+
+pub const MAX_CHAR_IDS: usize = 0x10;
+pub const MAX_NOTICES: usize = 0x4;
+pub const MAX_NOTICE_LENGTH: usize = 0x1000;
+pub const MAX_MEZ_STALLS: usize = 0x8;
+
+impl MhfConfig {
+    /// Checks that every field fits where `run` writes it. String limits leave room for the
+    /// terminating null byte.
+    pub fn validate(&self) -> Result<()> {
+        if self.char_id == 0 {
+            return Err(Error::MissingField("char_id"));
+        }
+        if self.user_token.len() != 16 {
+            return Err(Error::TokenLength);
+        }
+        if self.char_ids.len() > MAX_CHAR_IDS {
+            return Err(Error::TooMany("char_ids", MAX_CHAR_IDS));
+        }
+        if self.notices.len() > MAX_NOTICES {
+            return Err(Error::TooMany("notices", MAX_NOTICES));
+        }
+        if self.mez_stalls.len() > MAX_MEZ_STALLS {
+            return Err(Error::TooMany("mez_stalls", MAX_MEZ_STALLS));
+        }
+        if self
+            .notices
+            .iter()
+            .any(|n| n.data.len() > MAX_NOTICE_LENGTH)
+        {
+            return Err(Error::TooLong("notices.data", MAX_NOTICE_LENGTH));
+        }
+        // 'server_host:server_port' and ZZ's 'server_host:8080' have to fit too
+        let max_server_host = match self.version {
+            MhfVersion::ZZ => 0xc0 - 1 - ":8080".len(),
+            MhfVersion::F5 => 0x100 - 1 - ":4294967295".len(),
+        };
+        let strings = [
+            ("char_name", self.char_name.len(), 0x10 - 1),
+            ("user_name", self.user_name.len(), 0x800 - 1),
+            ("user_password", self.user_password.len(), 0x800 - 1),
+            ("server_host", self.server_host.len(), max_server_host),
+            (
+                "mhf_folder",
+                self.mhf_folder.as_ref().map_or(0, |f| f.as_os_str().len()),
+                // A trailing slash might be added
+                0x400 - 2,
+            ),
+        ];
+        for (field, len, max) in strings {
+            if len > max {
+                return Err(Error::TooLong(field, max));
+            }
+        }
+        if let Some(event) = mezfes::MezFesEvent::from_config(self) {
+            event.validate()?;
+        }
+        research::patches(&self.research, self.version)?;
+        Ok(())
+    }
+
+    /// Things that are valid, but probably don't do what was meant.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if let (MhfVersion::F5, Some(user_rights)) = (self.version, self.user_rights) {
+            warnings.push(format!(
+                "'user_rights' is set to {user_rights}, but F5 is thought not to read it"
+            ));
+        }
+        warnings
+    }
+}
+
+/// JSON Schema describing `MhfConfig`, including the limits checked by `MhfConfig::validate`.
+///
+/// `validate` limits strings in bytes, while `maxLength` counts characters. The schema can't
+/// express bytes, so it lets some non-ASCII strings through and each limited string's description
+/// gives the real limit.
+pub fn config_schema() -> serde_json::Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(MhfConfig)).unwrap_or_default();
+    // `serde(default)` makes every field optional, but these defaults never validate. The
+    // `current_ts` one would otherwise be whenever the schema was generated
+    for field in ["char_id", "user_token", "current_ts"] {
+        if let Some(property) = schema
+            .pointer_mut(&format!("/properties/{field}"))
+            .and_then(|p| p.as_object_mut())
+        {
+            property.remove("default");
+        }
+    }
+    schema["required"] = serde_json::json!(["char_id", "user_token"]);
+    for pointer in [
+        "/properties/char_name",
+        "/properties/user_token",
+        "/properties/user_name",
+        "/properties/user_password",
+        "/properties/server_host",
+        "/properties/mhf_folder",
+        "/definitions/Notice/properties/data",
+    ] {
+        if let Some(property) = schema.pointer_mut(pointer).and_then(|p| p.as_object_mut()) {
+            let description = match (&property.get("minLength"), &property["maxLength"]) {
+                (Some(min), max) if *min == max => format!("Exactly {max} bytes"),
+                (_, max) => {
+                    format!("At most {max} bytes, which is fewer characters outside of ASCII")
+                }
+            };
+            property.insert("description".to_owned(), description.into());
+        }
+    }
+    schema
+}
+
+/// Progress reported by `run_with_events`, in the order it happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ConfigValidated,
+    DllLoaded,
+    /// Sent right before control is handed to the game, `run` blocks until it exits
+    MainEntered,
+    GameExited {
+        code: isize,
+    },
 }
 
 pub fn run(config: MhfConfig) -> Result<isize> {
-    if config.user_token.len() != 16 {
-        return Err(Error::TokenLength);
+    run_with_events(config, |_| {})
+}
+
+pub fn run_with_events(config: MhfConfig, mut on_event: impl FnMut(Event)) -> Result<isize> {
+    run_cancellable(config, &mut on_event, &AtomicBool::new(false))
+}
+
+// `cancelled` is checked until right before `mhDLL_Main`, after that the game has to exit by itself
+fn run_cancellable(
+    config: MhfConfig,
+    on_event: &mut dyn FnMut(Event),
+    cancelled: &AtomicBool,
+) -> Result<isize> {
+    let plan = plan(&config)?;
+    if cancelled.load(Ordering::SeqCst) {
+        return Err(Error::Cancelled);
+    }
+    on_event(Event::ConfigValidated);
+    let code = mhf::run_mhf(config, plan, on_event, cancelled)?;
+    on_event(Event::GameExited { code });
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(value: &T) -> Value {
+        let json = serde_json::to_value(value).unwrap();
+        let back: T = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&back).unwrap(), json);
+        json
+    }
+
+    fn config() -> MhfConfig {
+        MhfConfig {
+            char_id: 1,
+            char_name: "Hunter".to_owned(),
+            char_ids: vec![1, 2],
+            user_token: "0123456789abcdef".to_owned(),
+            user_rights: Some(UserRights::TRIAL | UserRights::N_BOOST),
+            notices: vec![Notice {
+                flags: 3,
+                data: "<BODY><CENTER>Hi".to_owned(),
+            }],
+            mez_start: 1,
+            mez_end: 2,
+            mez_stalls: vec![MezFesStall::Pachinko, MezFesStall::StallMap],
+            version: MhfVersion::F5,
+            mhf_folder: Some("C:/MHF".into()),
+            mhf_flags: Some(vec![CliFlags::Selfup, CliFlags::NpMhfoTest]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let json = round_trip(&config());
+        assert_eq!(json["version"], "F5");
+        assert_eq!(json["user_rights"], (1 << 1) | (1 << 12));
+        assert_eq!(json["mez_stalls"], json!(["Pachinko", "StallMap"]));
+        assert_eq!(json["mhf_flags"], json!(["Selfup", "NpMhfoTest"]));
+        // Left out when empty, so configs without it stay the same
+        assert!(json.get("research").is_none());
+        round_trip(&MhfConfig::default());
+    }
+
+    #[test]
+    fn config_defaults() {
+        let config: MhfConfig =
+            serde_json::from_value(json!({ "char_id": 1, "user_token": "0123456789abcdef" }))
+                .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.server_host, "127.0.0.1");
+        assert_eq!(config.server_port, 53312);
+        assert_eq!(config.version, MhfVersion::ZZ);
+        assert!(config.current_ts > 0);
+    }
+
+    #[test]
+    fn notice_and_flags_round_trip() {
+        let json = round_trip(&Notice {
+            flags: 1,
+            data: "<BODY>テスト".to_owned(),
+        });
+        assert_eq!(json, json!({ "flags": 1, "data": "<BODY>テスト" }));
+        for flag in 1..=10 {
+            let flag = CliFlags::try_from(flag).unwrap();
+            let json = round_trip(&flag);
+            let back: CliFlags = serde_json::from_value(json).unwrap();
+            assert_eq!(back as u8, flag as u8);
+        }
+        assert!(serde_json::from_value::<CliFlags>(json!("Unknown")).is_err());
+    }
+
+    #[test]
+    fn warnings() {
+        let mut config = config();
+        assert_eq!(config.warnings().len(), 1);
+        // Even set to the default, since F5 doesn't read it either way
+        config.user_rights = Some(UserRights::default());
+        assert_eq!(
+            config.warnings(),
+            ["'user_rights' is set to HunterLife, Extra, but F5 is thought not to read it"]
+        );
+        config.user_rights = None;
+        assert!(config.warnings().is_empty());
+        config.user_rights = Some(UserRights::PREMIUM);
+        config.version = MhfVersion::ZZ;
+        assert!(config.warnings().is_empty());
+
+        let config: MhfConfig = serde_json::from_value(json!({ "user_rights": 12 })).unwrap();
+        assert_eq!(config.user_rights, Some(UserRights::default()));
+        let config: MhfConfig = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(config.user_rights, None);
+    }
+
+    #[test]
+    fn schema_has_the_limits() {
+        let schema = config_schema();
+        let properties = &schema["properties"];
+        assert_eq!(properties["notices"]["maxItems"], MAX_NOTICES);
+        assert_eq!(properties["mez_stalls"]["maxItems"], MAX_MEZ_STALLS);
+        assert_eq!(properties["char_ids"]["maxItems"], MAX_CHAR_IDS);
+        assert_eq!(properties["user_token"]["minLength"], 16);
+        assert_eq!(properties["user_token"]["maxLength"], 16);
+        assert_eq!(properties["char_id"]["minimum"], 1.0);
+        assert_eq!(
+            schema["definitions"]["Notice"]["properties"]["data"]["maxLength"],
+            MAX_NOTICE_LENGTH
+        );
+        let required = schema["required"].as_array().unwrap();
+        assert!(required.contains(&json!("char_id")));
+        assert!(required.contains(&json!("user_token")));
+        for field in ["char_id", "user_token", "current_ts"] {
+            assert!(properties[field].get("default").is_none(), "{field}");
+        }
+        assert_eq!(
+            properties["char_name"]["description"],
+            "At most 15 bytes, which is fewer characters outside of ASCII"
+        );
+        assert_eq!(properties["user_token"]["description"], "Exactly 16 bytes");
+        assert!(
+            schema["definitions"]["Notice"]["properties"]["data"]["description"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("At most {MAX_NOTICE_LENGTH} bytes"))
+        );
+    }
+
+    #[test]
+    fn string_limits_are_bytes() {
+        let mut config = config();
+        // 5 characters, but 15 bytes
+        config.char_name = "ハンター名".to_owned();
+        config.validate().unwrap();
+        config.char_name = "ハンターの名".to_owned();
+        assert!(config.char_name.chars().count() <= 15);
+        assert!(matches!(
+            config.validate(),
+            Err(Error::TooLong("char_name", 15))
+        ));
     }
-    mhf::run_mhf(config)
 }
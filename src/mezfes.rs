@@ -0,0 +1,350 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Error, MezFesStall, MhfConfig, Result, MAX_MEZ_STALLS};
+
+const DAY: u64 = 24 * 60 * 60;
+// Weeks start on Monday at midnight in Japan, like Erupe's
+const JST_OFFSET: u64 = 9 * 60 * 60;
+// The stalls Erupe opens, in its order
+const WEEKLY_STALLS: [MezFesStall; 8] = [
+    MezFesStall::StallMap,
+    MezFesStall::Pachinko,
+    MezFesStall::Nyanrendo,
+    MezFesStall::PointStall,
+    MezFesStall::VolpakkunTogether,
+    MezFesStall::DokkanBattleCats,
+    MezFesStall::GoocooScoop,
+    MezFesStall::HoneyPanic,
+];
+
+/// A MezFes event, as `MhfConfig`'s `mez_*` fields describe it. Deserializes from either this
+/// form, with RFC 3339 times, or `RawMezFes`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "MezFesRepr")]
+pub struct MezFesEvent {
+    pub id: u32,
+    #[serde(with = "rfc3339")]
+    pub start: SystemTime,
+    #[serde(with = "rfc3339")]
+    pub end: SystemTime,
+    pub solo_tickets: u32,
+    pub group_tickets: u32,
+    pub stalls: Vec<MezFesStall>,
+}
+
+/// The `mez_*` fields of `MhfConfig`, with Unix timestamps.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawMezFes {
+    #[serde(default)]
+    pub mez_event_id: u32,
+    pub mez_start: u32,
+    pub mez_end: u32,
+    #[serde(default)]
+    pub mez_solo_tickets: u32,
+    #[serde(default)]
+    pub mez_group_tickets: u32,
+    #[serde(default)]
+    pub mez_stalls: Vec<MezFesStall>,
+}
+
+#[derive(Deserialize)]
+struct TypedMezFes {
+    id: u32,
+    #[serde(with = "rfc3339")]
+    start: SystemTime,
+    #[serde(with = "rfc3339")]
+    end: SystemTime,
+    #[serde(default)]
+    solo_tickets: u32,
+    #[serde(default)]
+    group_tickets: u32,
+    #[serde(default)]
+    stalls: Vec<MezFesStall>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MezFesRepr {
+    Typed(TypedMezFes),
+    Raw(RawMezFes),
+}
+
+impl TryFrom<MezFesRepr> for MezFesEvent {
+    type Error = Error;
+
+    fn try_from(repr: MezFesRepr) -> Result<Self> {
+        let event = match repr {
+            MezFesRepr::Typed(t) => Self {
+                id: t.id,
+                start: t.start,
+                end: t.end,
+                solo_tickets: t.solo_tickets,
+                group_tickets: t.group_tickets,
+                stalls: t.stalls,
+            },
+            MezFesRepr::Raw(raw) => raw.into(),
+        };
+        event.validate()?;
+        Ok(event)
+    }
+}
+
+mod rfc3339 {
+    use std::result::Result;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&humantime::format_rfc3339_seconds(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid RFC 3339 time '{s}'")))
+    }
+
+    // humantime only takes UTC, so offsets like '+09:00' are applied here
+    fn parse(s: &str) -> Option<SystemTime> {
+        let (time, offset) = match s.len().checked_sub(6).and_then(|i| s.split_at_checked(i)) {
+            Some((time, offset))
+                if offset.starts_with(['+', '-']) && offset.as_bytes()[3] == b':' =>
+            {
+                (time, offset)
+            }
+            _ => return humantime::parse_rfc3339_weak(s).ok(),
+        };
+        let hours: u64 = offset[1..3].parse().ok()?;
+        let minutes: u64 = offset[4..].parse().ok()?;
+        let offset = Duration::from_secs(hours * 60 * 60 + minutes * 60);
+        let time = humantime::parse_rfc3339_weak(&format!("{time}Z")).ok()?;
+        match s.as_bytes()[s.len() - 6] {
+            b'+' => time.checked_sub(offset),
+            _ => time.checked_add(offset),
+        }
+    }
+}
+
+fn from_ts(ts: u32) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(ts as u64)
+}
+
+fn to_ts(time: SystemTime) -> Result<u32> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|d| u32::try_from(d.as_secs()).ok())
+        .ok_or_else(|| {
+            Error::MezFes(format!(
+                "{} can't be passed to the game",
+                humantime::format_rfc3339_seconds(time)
+            ))
+        })
+}
+
+impl From<RawMezFes> for MezFesEvent {
+    fn from(raw: RawMezFes) -> Self {
+        Self {
+            id: raw.mez_event_id,
+            start: from_ts(raw.mez_start),
+            end: from_ts(raw.mez_end),
+            solo_tickets: raw.mez_solo_tickets,
+            group_tickets: raw.mez_group_tickets,
+            stalls: raw.mez_stalls,
+        }
+    }
+}
+
+impl MezFesEvent {
+    /// Runs from the Monday of `current_ts`'s week to the next one, with every stall open. The
+    /// week's start doubles as the event ID, so it changes every week.
+    pub fn weekly(current_ts: u32) -> Self {
+        let days = (current_ts as u64 + JST_OFFSET) / DAY;
+        // 1970-01-01 was a Thursday
+        let monday = days - (days + 3) % 7;
+        let start = (monday * DAY).saturating_sub(JST_OFFSET);
+        Self {
+            id: start as u32,
+            start: UNIX_EPOCH + Duration::from_secs(start),
+            end: UNIX_EPOCH + Duration::from_secs(start + 7 * DAY),
+            solo_tickets: 10,
+            group_tickets: 4,
+            stalls: WEEKLY_STALLS.to_vec(),
+        }
+    }
+
+    /// The event in `config`, or none when it has no start or end.
+    pub fn from_config(config: &MhfConfig) -> Option<Self> {
+        if config.mez_start == 0 && config.mez_end == 0 {
+            return None;
+        }
+        Some(Self::from(RawMezFes {
+            mez_event_id: config.mez_event_id,
+            mez_start: config.mez_start,
+            mez_end: config.mez_end,
+            mez_solo_tickets: config.mez_solo_tickets,
+            mez_group_tickets: config.mez_group_tickets,
+            mez_stalls: config.mez_stalls.clone(),
+        }))
+    }
+
+    /// Checks that the event ends after it starts, fits in a `u32` and has at most 8 unique stalls.
+    pub fn validate(&self) -> Result<()> {
+        self.raw().map(|_| ())
+    }
+
+    pub fn raw(&self) -> Result<RawMezFes> {
+        let (start, end) = (to_ts(self.start)?, to_ts(self.end)?);
+        if start >= end {
+            return Err(Error::MezFes("it has to end after it starts".to_owned()));
+        }
+        if self.stalls.len() > MAX_MEZ_STALLS {
+            return Err(Error::TooMany("mez_stalls", MAX_MEZ_STALLS));
+        }
+        for (i, stall) in self.stalls.iter().enumerate() {
+            if self.stalls[..i].contains(stall) {
+                return Err(Error::MezFes(format!("{stall:?} is open more than once")));
+            }
+        }
+        Ok(RawMezFes {
+            mez_event_id: self.id,
+            mez_start: start,
+            mez_end: end,
+            mez_solo_tickets: self.solo_tickets,
+            mez_group_tickets: self.group_tickets,
+            mez_stalls: self.stalls.clone(),
+        })
+    }
+
+    /// Sets `config`'s `mez_*` fields, after validating the event.
+    pub fn apply(&self, config: &mut MhfConfig) -> Result<()> {
+        let raw = self.raw()?;
+        config.mez_event_id = raw.mez_event_id;
+        config.mez_start = raw.mez_start;
+        config.mez_end = raw.mez_end;
+        config.mez_solo_tickets = raw.mez_solo_tickets;
+        config.mez_group_tickets = raw.mez_group_tickets;
+        config.mez_stalls = raw.mez_stalls;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // 2024-01-01, a Monday, at midnight in Japan
+    const MONDAY: u32 = 1704034800;
+
+    fn typed(start: &str, end: &str) -> serde_json::Value {
+        json!({ "id": 1, "start": start, "end": end, "stalls": ["Pachinko"] })
+    }
+
+    #[test]
+    fn rfc3339_offsets() {
+        for start in [
+            "2024-01-01T00:00:00+09:00",
+            "2023-12-31T10:00:00-05:00",
+            "2023-12-31T15:00:00Z",
+            "2023-12-31T15:00:00+00:00",
+        ] {
+            let event: MezFesEvent =
+                serde_json::from_value(typed(start, "2024-01-08T00:00:00+09:00")).unwrap();
+            assert_eq!(event.raw().unwrap().mez_start, MONDAY, "{start}");
+            assert_eq!(event.raw().unwrap().mez_end, MONDAY + 7 * DAY as u32);
+        }
+        for start in ["2024-01-01", "2024-01-01T00:00:00+9:00", "yesterday"] {
+            let e = serde_json::from_value::<MezFesEvent>(typed(start, "2024-01-08T00:00:00Z"))
+                .unwrap_err();
+            assert!(e.to_string().contains("did not match"), "{start}: {e}");
+        }
+    }
+
+    #[test]
+    fn serde_forms() {
+        let event = MezFesEvent::weekly(MONDAY);
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["start"], "2023-12-31T15:00:00Z");
+        assert_eq!(serde_json::from_value::<MezFesEvent>(json).unwrap(), event);
+
+        let raw = event.raw().unwrap();
+        let json = serde_json::to_value(&raw).unwrap();
+        assert_eq!(json["mez_start"], MONDAY);
+        assert_eq!(serde_json::from_value::<MezFesEvent>(json).unwrap(), event);
+        let minimal: MezFesEvent =
+            serde_json::from_value(json!({ "mez_start": 1, "mez_end": 2 })).unwrap();
+        assert_eq!(minimal.id, 0);
+        assert!(minimal.stalls.is_empty());
+    }
+
+    #[test]
+    fn validation() {
+        let raw = |start, end, stalls| {
+            MezFesEvent::from(RawMezFes {
+                mez_start: start,
+                mez_end: end,
+                mez_stalls: stalls,
+                ..Default::default()
+            })
+        };
+        assert!(raw(1, 2, vec![]).validate().is_ok());
+        for (start, end) in [(2, 2), (3, 2)] {
+            assert!(matches!(
+                raw(start, end, vec![]).validate(),
+                Err(Error::MezFes(_))
+            ));
+        }
+        let e = raw(1, 2, vec![MezFesStall::Pachinko, MezFesStall::Pachinko])
+            .validate()
+            .unwrap_err();
+        assert!(
+            e.to_string().contains("Pachinko is open more than once"),
+            "{e}"
+        );
+        let mut stalls = WEEKLY_STALLS.to_vec();
+        assert!(raw(1, 2, stalls.clone()).validate().is_ok());
+        stalls.push(MezFesStall::TokotokoPartnya);
+        assert!(matches!(
+            raw(1, 2, stalls).validate(),
+            Err(Error::TooMany("mez_stalls", MAX_MEZ_STALLS))
+        ));
+        // Deserializing validates too
+        assert!(serde_json::from_value::<MezFesEvent>(typed(
+            "2024-01-08T00:00:00Z",
+            "2024-01-01T00:00:00Z"
+        ))
+        .is_err());
+        let too_late = MezFesEvent {
+            end: UNIX_EPOCH + Duration::from_secs(u32::MAX as u64 + 1),
+            ..raw(1, 2, vec![])
+        };
+        assert!(matches!(too_late.validate(), Err(Error::MezFes(_))));
+    }
+
+    #[test]
+    fn weekly() {
+        let week = MONDAY as u64 + 7 * DAY;
+        for ts in [MONDAY, MONDAY + 1, week as u32 - 1] {
+            let event = MezFesEvent::weekly(ts);
+            assert_eq!(event.id, MONDAY);
+            assert_eq!(event.start, from_ts(MONDAY));
+            assert_eq!(event.end, UNIX_EPOCH + Duration::from_secs(week));
+            assert_eq!(event.stalls, WEEKLY_STALLS);
+        }
+        assert_eq!(MezFesEvent::weekly(MONDAY - 1).id, MONDAY - 7 * DAY as u32);
+        assert_eq!(MezFesEvent::weekly(week as u32).id, week as u32);
+        assert!(MezFesEvent::weekly(MONDAY).validate().is_ok());
+    }
+
+    #[test]
+    fn config() {
+        let mut config = MhfConfig::default();
+        assert_eq!(MezFesEvent::from_config(&config), None);
+        let event = MezFesEvent::weekly(MONDAY);
+        event.apply(&mut config).unwrap();
+        assert_eq!(config.mez_start, MONDAY);
+        assert_eq!(config.mez_stalls, WEEKLY_STALLS);
+        assert_eq!(MezFesEvent::from_config(&config), Some(event));
+    }
+}
@@ -0,0 +1,316 @@
+use std::fmt::Write;
+
+use bitflags::bitflags;
+
+use crate::{Error, Notice, Result, MAX_NOTICE_LENGTH};
+
+bitflags! {
+    /// `Notice::flags`. Other bits are kept as they are, but what they do isn't known.
+    ///
+    /// Both flags are guesses, neither has been confirmed in the game.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct NoticeFlags: u16 {
+        /// Thought to show the notice as new
+        const NEW = 0x1;
+        /// Thought to show the notice as important
+        const IMPORTANT = 0x2;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// A piece of the game's notice markup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// `<BODY>`, starts a section
+    Body,
+    /// `<BR>`
+    Break,
+    /// `<LEFT>`, `<CENTER>` or `<RIGHT>`
+    Align(Align),
+    /// `<SIZE_n>`
+    Size(u8),
+    /// `<C_n>`, an index into the game's text colours
+    Color(u8),
+    Text(String),
+    /// Any other tag, without the angle brackets
+    Tag(String),
+}
+
+impl Token {
+    fn from_tag(tag: &str) -> Self {
+        let number = |prefix| {
+            let n: u8 = tag.strip_prefix(prefix)?.parse().ok()?;
+            // So tags like '<SIZE_03>' come back out the same
+            (format!("{prefix}{n}") == tag).then_some(n)
+        };
+        match tag {
+            "BODY" => Self::Body,
+            "BR" => Self::Break,
+            "LEFT" => Self::Align(Align::Left),
+            "CENTER" => Self::Align(Align::Center),
+            "RIGHT" => Self::Align(Align::Right),
+            _ => match (number("SIZE_"), number("C_")) {
+                (Some(n), _) => Self::Size(n),
+                (_, Some(n)) => Self::Color(n),
+                _ => Self::Tag(tag.to_owned()),
+            },
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        let _ = match self {
+            Self::Body => write!(out, "<BODY>"),
+            Self::Break => write!(out, "<BR>"),
+            Self::Align(Align::Left) => write!(out, "<LEFT>"),
+            Self::Align(Align::Center) => write!(out, "<CENTER>"),
+            Self::Align(Align::Right) => write!(out, "<RIGHT>"),
+            Self::Size(n) => write!(out, "<SIZE_{n}>"),
+            Self::Color(n) => write!(out, "<C_{n}>"),
+            Self::Text(text) => write!(out, "{text}"),
+            Self::Tag(tag) => write!(out, "<{tag}>"),
+        };
+    }
+}
+
+/// Splits notice markup into tokens. Anything that isn't an uppercase tag is text, so rendering the
+/// tokens gives back the same string.
+pub fn parse(data: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut rest = data;
+    while !rest.is_empty() {
+        let tag = rest.strip_prefix('<').and_then(|r| {
+            let end = r.find('>')?;
+            let tag = &r[..end];
+            let valid = !tag.is_empty()
+                && tag
+                    .bytes()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == b'_');
+            valid.then_some(tag)
+        });
+        if let Some(tag) = tag {
+            tokens.push(Token::from_tag(tag));
+            rest = &rest[tag.len() + 2..];
+            continue;
+        }
+        // Up to the next '<', skipping the one this might start with
+        let first = rest.chars().next().map_or(0, char::len_utf8);
+        let end = rest[first..].find('<').map_or(rest.len(), |i| i + first);
+        match tokens.last_mut() {
+            Some(Token::Text(text)) => text.push_str(&rest[..end]),
+            _ => tokens.push(Token::Text(rest[..end].to_owned())),
+        }
+        rest = &rest[end..];
+    }
+    tokens
+}
+
+pub fn render(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        token.write(&mut out);
+    }
+    out
+}
+
+/// The text of `tokens` without the markup, with a line per `<BR>` and an empty line between
+/// sections.
+pub fn preview(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        match token {
+            Token::Body if !out.is_empty() => {
+                out.truncate(out.trim_end_matches('\n').len());
+                out.push_str("\n\n");
+            }
+            Token::Break => out.push('\n'),
+            Token::Text(text) => out.push_str(text),
+            _ => {}
+        }
+    }
+    out.lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+fn sections(tokens: &[Token]) -> Vec<&[Token]> {
+    tokens
+        .split(|t| *t == Token::Body)
+        .filter(|s| s.iter().any(|t| matches!(t, Token::Text(_))))
+        .collect()
+}
+
+impl Notice {
+    pub fn notice_flags(&self) -> NoticeFlags {
+        NoticeFlags::from_bits_retain(self.flags)
+    }
+
+    pub fn tokens(&self) -> Vec<Token> {
+        parse(&self.data)
+    }
+
+    /// The first section, when there's more than one, as `NoticeBuilder::title` writes it.
+    pub fn title(&self) -> Option<String> {
+        let tokens = self.tokens();
+        match sections(&tokens).as_slice() {
+            [title, _, ..] => Some(preview(title)),
+            _ => None,
+        }
+    }
+
+    /// Everything after the title.
+    pub fn body(&self) -> String {
+        let tokens = self.tokens();
+        let sections = sections(&tokens);
+        let body = match sections.as_slice() {
+            [_, body @ ..] if !body.is_empty() => body,
+            all => all,
+        };
+        body.iter()
+            .map(|s| preview(s))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Plain text for launchers to show, with the title on its own line.
+    pub fn preview(&self) -> String {
+        preview(&self.tokens())
+    }
+}
+
+/// Writes notice markup the way the official notices are laid out: a centred title section
+/// followed by left aligned body sections. Text isn't escaped, so anything looking like a tag stays
+/// one.
+#[derive(Debug, Clone, Default)]
+pub struct NoticeBuilder {
+    flags: NoticeFlags,
+    tokens: Vec<Token>,
+}
+
+impl NoticeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn flags(mut self, flags: NoticeFlags) -> Self {
+        self.flags |= flags;
+        self
+    }
+
+    pub fn title(self, title: &str) -> Self {
+        self.token(Token::Body)
+            .token(Token::Align(Align::Center))
+            .token(Token::Size(3))
+            .text(title)
+            .token(Token::Break)
+    }
+
+    /// Starts a section with `body`, with a `<BR>` for each new line.
+    pub fn body(self, body: &str) -> Self {
+        self.token(Token::Body)
+            .token(Token::Align(Align::Left))
+            .token(Token::Size(2))
+            .text(body)
+    }
+
+    pub fn color(self, color: u8) -> Self {
+        self.token(Token::Color(color))
+    }
+
+    pub fn size(self, size: u8) -> Self {
+        self.token(Token::Size(size))
+    }
+
+    pub fn align(self, align: Align) -> Self {
+        self.token(Token::Align(align))
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        for (i, line) in text.lines().enumerate() {
+            if i > 0 {
+                self.tokens.push(Token::Break);
+            }
+            self.tokens.push(Token::Text(line.to_owned()));
+        }
+        self
+    }
+
+    pub fn token(mut self, token: Token) -> Self {
+        self.tokens.push(token);
+        self
+    }
+
+    /// Fails when the markup doesn't fit in a notice slot.
+    pub fn build(self) -> Result<Notice> {
+        let data = render(&self.tokens);
+        if data.len() > MAX_NOTICE_LENGTH {
+            return Err(Error::TooLong("notices.data", MAX_NOTICE_LENGTH));
+        }
+        Ok(Notice {
+            flags: self.flags.bits(),
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_render() {
+        for data in [
+            "",
+            "<BODY><CENTER><SIZE_3>Title<BR><BODY><LEFT><SIZE_2>Body",
+            "<C_7>colour</C_7> <SIZE_03> <BLINK>",
+            "a < b > c <lower> <> <<BR>>",
+            "日本語<BR>テキスト",
+        ] {
+            assert_eq!(render(&parse(data)), data);
+        }
+        assert_eq!(
+            parse("<SIZE_03><C_7>a <b><X_1>"),
+            [
+                Token::Tag("SIZE_03".to_owned()),
+                Token::Color(7),
+                Token::Text("a <b>".to_owned()),
+                Token::Tag("X_1".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn builder_round_trip() {
+        let notice = NoticeBuilder::new()
+            .flags(NoticeFlags::NEW)
+            .flags(NoticeFlags::IMPORTANT)
+            .title("Maintenance")
+            .body("Servers are down\nfor 2 hours")
+            .body("Sorry")
+            .build()
+            .unwrap();
+        assert_eq!(notice.flags, 3);
+        assert_eq!(notice.title().as_deref(), Some("Maintenance"));
+        assert_eq!(notice.body(), "Servers are down\nfor 2 hours\n\nSorry");
+        assert_eq!(
+            notice.preview(),
+            "Maintenance\n\nServers are down\nfor 2 hours\n\nSorry"
+        );
+
+        // Without a title, everything is the body
+        let notice = NoticeBuilder::new().body("Just this").build().unwrap();
+        assert_eq!(notice.title(), None);
+        assert_eq!(notice.body(), "Just this");
+
+        let long = "a".repeat(MAX_NOTICE_LENGTH);
+        assert!(NoticeBuilder::new().body(&long).build().is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::policy;
+use crate::research::{self, Patch};
+use crate::settings::MhfSettings;
+use crate::{CliFlags, Error, MhfConfig, MhfVersion, Result};
+
+/// Everything `run` derives from the config and `mhf.ini` before handing control to the game.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub version: MhfVersion,
+    pub mhf_folder: PathBuf,
+    pub dll: &'static str,
+    pub graphics_ver: u32,
+    pub cmd_flags_1: u32,
+    pub cmd_flags_2: u32,
+    pub cmd_dmm: u32,
+    pub remote_addr: String,
+    pub remote_host: String,
+    // ZZ only
+    pub alt_ip_address: Option<String>,
+    pub selected_char_status: u32,
+    pub settings: MhfSettings,
+    pub research: Vec<Patch>,
+    /// `MhfConfig::warnings`
+    pub warnings: Vec<String>,
+}
+
+fn cmd_flags(mhf_flags: &[CliFlags]) -> (u32, u32, u32) {
+    let (mut cmd_flags_1, mut cmd_flags_2, mut cmd_dmm) = (0, 0, 0);
+    for flag in mhf_flags {
+        match flag {
+            CliFlags::Selfup => cmd_flags_1 = 1,
+            CliFlags::Restat => cmd_flags_1 = 2,
+            CliFlags::Autolc => cmd_flags_1 = 3,
+            CliFlags::Hanres => cmd_flags_1 = 4,
+            CliFlags::DmmBoot => {
+                cmd_flags_1 = 5;
+                cmd_dmm = 1;
+            }
+            CliFlags::DmmSelfup => {
+                cmd_flags_1 = 6;
+                cmd_dmm = 1;
+            }
+            CliFlags::DmmAutolc => {
+                cmd_flags_1 = 7;
+                cmd_dmm = 1;
+            }
+            CliFlags::DmmReboot => {
+                cmd_flags_1 = 8;
+                cmd_dmm = 1;
+            }
+            CliFlags::Npge => {
+                cmd_flags_1 = 9;
+                cmd_flags_2 |= 6;
+            }
+            CliFlags::NpMhfoTest => cmd_flags_2 |= 4,
+        }
+    }
+    (cmd_flags_1, cmd_flags_2, cmd_dmm)
+}
+
+/// Guesses the version from the DLLs in `mhf_folder`, since only ZZ ships `mhfo-hd.dll`.
+pub fn detect_version(mhf_folder: &Path) -> Option<MhfVersion> {
+    if mhf_folder.join("mhfo-hd.dll").is_file() {
+        Some(MhfVersion::ZZ)
+    } else if mhf_folder.join("mhfo.dll").is_file() {
+        Some(MhfVersion::F5)
+    } else {
+        None
+    }
+}
+
+/// Validates `config` and works out what `run` would do with it, without touching the game.
+pub fn plan(config: &MhfConfig) -> Result<Plan> {
+    plan_with_settings(config, MhfSettings::load)
+}
+
+// Bundles are replayed with the 'mhf.ini' they carry instead of the one in the game folder
+pub(crate) fn plan_with_settings(
+    config: &MhfConfig,
+    load_settings: impl FnOnce(&Path) -> MhfSettings,
+) -> Result<Plan> {
+    config.validate()?;
+    // Checked here so nothing reaches `remote_addr` or `remote_host` without going through it
+    policy::policy().check(config)?;
+    let mhf_folder = match &config.mhf_folder {
+        Some(mhf_folder) => mhf_folder.clone(),
+        None => std::env::current_dir().or(Err(Error::GamePath))?,
+    };
+    let settings = load_settings(&mhf_folder);
+    let (dll, graphics_ver) = match config.version {
+        MhfVersion::F5 => ("mhfo.dll", 0),
+        MhfVersion::ZZ => (
+            if settings.graphics_ver == 1 {
+                "mhfo-hd.dll"
+            } else {
+                "mhfo.dll"
+            },
+            settings.graphics_ver,
+        ),
+    };
+    let (cmd_flags_1, cmd_flags_2, cmd_dmm) = cmd_flags(config.mhf_flags.as_deref().unwrap_or(&[]));
+    Ok(Plan {
+        version: config.version,
+        mhf_folder,
+        dll,
+        graphics_ver,
+        cmd_flags_1,
+        cmd_flags_2,
+        cmd_dmm,
+        remote_addr: format!("{}:{}", config.server_host, config.server_port),
+        remote_host: config.server_host.clone(),
+        alt_ip_address: match config.version {
+            MhfVersion::ZZ => Some(format!("{}:8080", config.server_host)),
+            MhfVersion::F5 => None,
+        },
+        selected_char_status: if config.char_new { 2 } else { 0 },
+        settings,
+        research: research::patches(&config.research, config.version)?,
+        warnings: config.warnings(),
+    })
+}
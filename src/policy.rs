@@ -0,0 +1,273 @@
+use std::sync::RwLock;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{Error, MhfConfig, Result};
+
+static POLICY: RwLock<Option<Policy>> = RwLock::new(None);
+
+/// Environment variable `launch_detached` passes the caller's policy to the CLI in, as JSON.
+pub const POLICY_ENV: &str = "MHF_IEL_POLICY";
+
+/// A server `run` may connect to. No port allows every port on the host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllowedServer {
+    pub host: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u32>,
+}
+
+impl AllowedServer {
+    pub fn allows(&self, host: &str, port: u32) -> bool {
+        self.host.eq_ignore_ascii_case(host) && self.port.is_none_or(|p| p == port)
+    }
+}
+
+/// An Ed25519 public key, base64 encoded, that tickets can be signed with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedKey {
+    pub name: String,
+    pub public_key: String,
+}
+
+impl TrustedKey {
+    fn verifying_key(&self) -> Option<VerifyingKey> {
+        let bytes = STANDARD.decode(&self.public_key).ok()?;
+        VerifyingKey::from_bytes(&bytes.try_into().ok()?).ok()
+    }
+
+    /// Whether `public_key` is a base64 Ed25519 key. `SignedTicket::verify` skips invalid ones.
+    pub fn is_valid(&self) -> bool {
+        self.verifying_key().is_some()
+    }
+}
+
+/// What the local user trusts, set once by the launcher with `set_policy`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// `run` refuses every other server when set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_servers: Option<Vec<AllowedServer>>,
+    pub trusted_keys: Vec<TrustedKey>,
+}
+
+impl Policy {
+    pub fn allows(&self, host: &str, port: u32) -> bool {
+        self.allowed_servers
+            .as_ref()
+            .is_none_or(|servers| servers.iter().any(|s| s.allows(host, port)))
+    }
+
+    pub fn check(&self, config: &MhfConfig) -> Result<()> {
+        match self.allows(&config.server_host, config.server_port) {
+            true => Ok(()),
+            false => Err(Error::NotAllowed(format!(
+                "{}:{}",
+                config.server_host, config.server_port
+            ))),
+        }
+    }
+}
+
+/// Replaces the policy `plan` and `run` check every config against.
+pub fn set_policy(policy: Policy) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = Some(policy);
+}
+
+/// The policy from `set_policy`, which allows every server until it's called.
+pub fn policy() -> Policy {
+    POLICY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+// Sorted keys and no whitespace, so the signer and the launcher hash the same bytes no matter how
+// the JSON was written
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(value, out);
+            }
+            out.push(']');
+        }
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+/// Config fields signed by a server's key, so a launcher can tell they weren't changed on the way.
+///
+/// The signature covers `canonical(config)`, the fields as compact JSON with sorted keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTicket {
+    pub config: Map<String, Value>,
+    /// Base64 Ed25519 signature
+    pub signature: String,
+}
+
+impl SignedTicket {
+    pub fn canonical(config: &Map<String, Value>) -> Vec<u8> {
+        let mut out = String::new();
+        write_canonical(&Value::Object(config.clone()), &mut out);
+        out.into_bytes()
+    }
+
+    /// For servers handing out tickets, `secret_key` being the 32 byte Ed25519 seed.
+    pub fn sign(config: Map<String, Value>, secret_key: &[u8; 32]) -> Self {
+        let signature = SigningKey::from_bytes(secret_key).sign(&Self::canonical(&config));
+        Self {
+            config,
+            signature: STANDARD.encode(signature.to_bytes()),
+        }
+    }
+
+    /// Returns the name of the key that signed the ticket. Invalid keys are skipped, so one of them
+    /// doesn't stop the others from verifying.
+    pub fn verify<'a>(&self, keys: &'a [TrustedKey]) -> Result<&'a str> {
+        let signature = STANDARD
+            .decode(&self.signature)
+            .ok()
+            .and_then(|b| Signature::from_slice(&b).ok())
+            .ok_or_else(|| Error::Ticket("malformed signature".to_owned()))?;
+        let message = Self::canonical(&self.config);
+        let mut invalid = vec![];
+        for key in keys {
+            match key.verifying_key() {
+                Some(verifying_key) => {
+                    if verifying_key.verify_strict(&message, &signature).is_ok() {
+                        return Ok(&key.name);
+                    }
+                }
+                None => invalid.push(format!("'{}'", key.name)),
+            }
+        }
+        match invalid.is_empty() {
+            true => Err(Error::Ticket("not signed by a trusted key".to_owned())),
+            false => Err(Error::Ticket(format!(
+                "not signed by a trusted key, skipped invalid keys {}",
+                invalid.join(", ")
+            ))),
+        }
+    }
+
+    /// Verifies the ticket and builds its config, which is still checked against the policy when
+    /// it runs.
+    pub fn to_config(&self, keys: &[TrustedKey]) -> Result<MhfConfig> {
+        self.verify(keys)?;
+        let config: MhfConfig = serde_json::from_value(Value::Object(self.config.clone()))
+            .map_err(|e| Error::Ticket(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(name: &str, secret: &[u8; 32]) -> TrustedKey {
+        TrustedKey {
+            name: name.to_owned(),
+            public_key: STANDARD.encode(SigningKey::from_bytes(secret).verifying_key().to_bytes()),
+        }
+    }
+
+    fn invalid(name: &str, public_key: &str) -> TrustedKey {
+        TrustedKey {
+            name: name.to_owned(),
+            public_key: public_key.to_owned(),
+        }
+    }
+
+    fn ticket() -> SignedTicket {
+        let config = json!({ "char_id": 1, "user_token": "0123456789abcdef" });
+        SignedTicket::sign(config.as_object().unwrap().clone(), &[1; 32])
+    }
+
+    #[test]
+    fn invalid_keys_are_skipped() {
+        let keys = [
+            invalid("short", "AAAA"),
+            invalid("not base64", "!"),
+            key("other", &[2; 32]),
+            key("signer", &[1; 32]),
+        ];
+        assert!(!keys[0].is_valid() && !keys[1].is_valid());
+        assert!(keys[2].is_valid() && keys[3].is_valid());
+        assert_eq!(ticket().verify(&keys).unwrap(), "signer");
+
+        let e = ticket().verify(&keys[..3]).unwrap_err().to_string();
+        assert!(e.contains("not signed by a trusted key"), "{e}");
+        assert!(e.contains("'short', 'not base64'"), "{e}");
+        let e = ticket().verify(&keys[2..3]).unwrap_err().to_string();
+        assert!(!e.contains("skipped"), "{e}");
+    }
+
+    #[test]
+    fn tickets_verify() {
+        let keys = [key("signer", &[1; 32])];
+        let config = ticket().to_config(&keys).unwrap();
+        assert_eq!(config.char_id, 1);
+
+        // Same fields written in another order
+        let mut reordered = ticket();
+        reordered.config =
+            serde_json::from_str(r#"{ "user_token": "0123456789abcdef", "char_id": 1 }"#).unwrap();
+        assert_eq!(reordered.verify(&keys).unwrap(), "signer");
+
+        let mut changed = ticket();
+        changed.config.insert("char_id".to_owned(), json!(2));
+        assert!(changed.verify(&keys).is_err());
+        let mut malformed = ticket();
+        malformed.signature = "AAAA".to_owned();
+        assert!(malformed.verify(&keys).is_err());
+    }
+
+    #[test]
+    fn allowed_servers() {
+        let policy = Policy {
+            allowed_servers: Some(vec![
+                AllowedServer {
+                    host: "mhf.example.com".to_owned(),
+                    port: Some(53312),
+                },
+                AllowedServer {
+                    host: "10.0.0.2".to_owned(),
+                    port: None,
+                },
+            ]),
+            trusted_keys: vec![],
+        };
+        assert!(policy.allows("MHF.example.com", 53312));
+        assert!(!policy.allows("mhf.example.com", 53313));
+        assert!(policy.allows("10.0.0.2", 1));
+        assert!(!policy.allows("127.0.0.1", 53312));
+        assert!(Policy::default().allows("127.0.0.1", 53312));
+    }
+}
@@ -0,0 +1,607 @@
+use std::fmt::Write;
+use std::mem::{offset_of, size_of};
+
+use serde::Serialize;
+
+use crate::block::{DataF5, DataZZ, GlobalData};
+use crate::MhfVersion;
+
+/// Where the original launcher keeps the block in ZZ's `mhf.exe`.
+pub const ZZ_BASE: usize = 0x447178;
+// F5's base is worked out from '_pad_4406cc', the only F5 address known
+pub const F5_BASE: usize = 0x4406cc - offset_of!(DataF5, _pad_4406cc);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Part {
+    Common1,
+    Common2,
+    Common3,
+    Common4,
+    Common5,
+    DataZZ,
+    DataF5,
+    GlobalData,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    U32,
+    U32Array,
+    U16Array,
+    Handle,
+    Pointer,
+    Proc,
+    String,
+    Bytes,
+    /// Zero sized, marks where a pointer points to
+    Marker,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confidence {
+    /// What `run` puts there is known to work
+    Known,
+    /// Named from the disassembly, but not confirmed
+    Likely,
+    Unknown,
+}
+
+/// A field of the launcher block or the global alloc.
+#[derive(Debug, Clone, Serialize)]
+pub struct Field {
+    pub name: &'static str,
+    pub part: Part,
+    /// From the start of the block, or of the global alloc
+    pub zz_offset: Option<usize>,
+    pub f5_offset: Option<usize>,
+    /// In the original launcher, the global alloc is on the heap so it has none
+    pub zz_address: Option<usize>,
+    pub f5_address: Option<usize>,
+    pub size: usize,
+    pub kind: Kind,
+    pub confidence: Confidence,
+    pub notes: &'static str,
+}
+
+impl Field {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        name: &'static str,
+        part: Part,
+        zz_offset: Option<usize>,
+        f5_offset: Option<usize>,
+        size: usize,
+        kind: Kind,
+        confidence: Confidence,
+        notes: &'static str,
+    ) -> Self {
+        let block = part != Part::GlobalData;
+        Self {
+            name,
+            part,
+            zz_offset,
+            f5_offset,
+            zz_address: zz_offset.filter(|_| block).map(|o| ZZ_BASE + o),
+            f5_address: f5_offset.filter(|_| block).map(|o| F5_BASE + o),
+            size,
+            kind,
+            confidence,
+            notes,
+        }
+    }
+
+    pub fn offset(&self, version: MhfVersion) -> Option<usize> {
+        match version {
+            MhfVersion::ZZ => self.zz_offset,
+            MhfVersion::F5 => self.f5_offset,
+        }
+    }
+
+    pub fn address(&self, version: MhfVersion) -> Option<usize> {
+        match version {
+            MhfVersion::ZZ => self.zz_address,
+            MhfVersion::F5 => self.f5_address,
+        }
+    }
+}
+
+fn size_of_field<T, F>(_: impl Fn(&T) -> &F) -> usize {
+    size_of::<F>()
+}
+
+// Every address is checked against the structs when building, so the two can't drift apart
+macro_rules! fields {
+    // A common part, at ZZ's address
+    (common $part:ident . $common:ident {
+        $($name:ident @ $address:literal : $kind:ident, $confidence:ident, $notes:literal;)+
+    }) => {
+        [$({
+            const _: () = assert!(ZZ_BASE + offset_of!(DataZZ, $common.$name) == $address);
+            Field::new(
+                stringify!($name),
+                Part::$part,
+                Some(offset_of!(DataZZ, $common.$name)),
+                Some(offset_of!(DataF5, $common.$name)),
+                size_of_field(|d: &DataZZ| &d.$common.$name),
+                Kind::$kind,
+                Confidence::$confidence,
+                $notes,
+            )
+        }),+]
+    };
+    (zz {
+        $($name:ident @ $address:literal : $kind:ident, $confidence:ident, $notes:literal;)+
+    }) => {
+        [$({
+            const _: () = assert!(ZZ_BASE + offset_of!(DataZZ, $name) == $address);
+            Field::new(
+                stringify!($name),
+                Part::DataZZ,
+                Some(offset_of!(DataZZ, $name)),
+                None,
+                size_of_field(|d: &DataZZ| &d.$name),
+                Kind::$kind,
+                Confidence::$confidence,
+                $notes,
+            )
+        }),+]
+    };
+    (f5 {
+        $($name:ident @ $address:literal : $kind:ident, $confidence:ident, $notes:literal;)+
+    }) => {
+        [$({
+            const _: () = assert!(F5_BASE + offset_of!(DataF5, $name) == $address);
+            Field::new(
+                stringify!($name),
+                Part::DataF5,
+                None,
+                Some(offset_of!(DataF5, $name)),
+                size_of_field(|d: &DataF5| &d.$name),
+                Kind::$kind,
+                Confidence::$confidence,
+                $notes,
+            )
+        }),+]
+    };
+    // Addresses are offsets in the global alloc
+    (global {
+        $($name:ident @ $address:literal : $kind:ident, $confidence:ident, $notes:literal;)+
+    }) => {
+        [$({
+            const _: () = assert!(offset_of!(GlobalData, $name) == $address);
+            Field::new(
+                stringify!($name),
+                Part::GlobalData,
+                Some($address),
+                Some($address),
+                size_of_field(|d: &GlobalData| &d.$name),
+                Kind::$kind,
+                Confidence::$confidence,
+                $notes,
+            )
+        }),+]
+    };
+}
+
+/// Every field of the launcher block and the global alloc, in the order they're laid out.
+pub fn fields() -> Vec<Field> {
+    let mut fields = vec![];
+    fields.extend(fields!(common Common1.common1 {
+        main_module @ 0x447178: Handle, Known, "Module handle of the launcher";
+        _pad_44717c @ 0x44717c: Bytes, Unknown, "";
+        cmd_flags_1 @ 0x447184: U32, Known, "Set from 'mhf_flags'";
+        cmd_flags_2 @ 0x447188: U32, Known, "Set from 'mhf_flags'";
+        path1 @ 0x44718c: String, Known, "Game folder, with a trailing slash";
+        path2 @ 0x44758c: String, Known, "Game folder, with a trailing slash";
+        user_name @ 0x44798c: String, Known, "";
+        user_password @ 0x44818c: String, Known, "";
+        cmd_number @ 0x44898c: U32, Likely, "Command line argument count, 1 in ZZ and 2 in F5";
+        cmd_netfcup @ 0x448990: U32, Likely, "Set by the '-NETFCUP' argument";
+        cmd_dmm @ 0x448994: U32, Known, "Set whenever a /DMM_<X> flag is given, 7 in ZZ";
+        _pad_448998 @ 0x448998: Bytes, Unknown, "Set when mutex_master already exists?";
+        mutex_master @ 0x44899c: Handle, Known, "";
+        mutex_master_ready @ 0x4489a0: Handle, Known, "";
+        mutex_master_name @ 0x4489a4: String, Known, "";
+        ini_file @ 0x4489e4: String, Known, "Always 'mhf.ini'";
+        proc_1 @ 0x448a24: Proc, Likely, "Copied from 40187a";
+        proc_2 @ 0x448a28: Proc, Likely, "Copied from 401868, probably the GameGuard check";
+        proc_3 @ 0x448a2c: Proc, Likely, "Copied from 40188b";
+        _pad_448a30 @ 0x448a30: Bytes, Unknown, "";
+        selected_char_id_1 @ 0x448a3c: U32, Known, "";
+        selected_char_id_2 @ 0x448a40: U32, Known, "";
+        user_token_id @ 0x448a44: U32, Known, "";
+        user_token @ 0x448a48: Bytes, Known, "16 characters, without a null byte";
+        _pad_448a58 @ 0x448a58: Bytes, Unknown, "";
+        server_current_ts @ 0x448a60: U32, Known, "";
+        fixed_448a64_0x0 @ 0x448a64: U32, Likely, "Always 0";
+        _pad_448a68 @ 0x448a68: Bytes, Unknown, "";
+        remote_addr @ 0x448c68: String, Known, "Sign server as 'host:port'";
+        remote_host @ 0x448d68: String, Known, "";
+        remote_patch_count @ 0x448e68: U32, Unknown, "";
+        server_entrance_count @ 0x448e6c: U32, Known, "";
+        selected_char_status @ 0x448e70: U32, Known, "0 for existing characters, 2 for new ones. 1 seems to act like 2";
+        user_rights @ 0x448e74: U32, Likely, "Still in the F5 block, but F5 is thought not to read it";
+        selected_char_hr @ 0x448e78: U32, Known, "";
+        selected_char_name @ 0x448e7c: String, Known, "";
+    }));
+    fields.extend(fields!(zz {
+        char_ids @ 0x448e8c: U32Array, Known, "";
+    }));
+    fields.extend(fields!(common Common2.common2 {
+        global_alloc @ 0x448ecc: Handle, Known, "Global alloc with the notices and MezFes data";
+        fixed_448ed0_0x1 @ 0x448ed0: U32, Likely, "Always 1";
+        unk_448ed4 @ 0x448ed4: U32, Unknown, "";
+        selected_char_gr @ 0x448ed8: U32, Known, "Can be the value before 'bool + name' if the bool is false";
+        _pad_448edc @ 0x448edc: Bytes, Unknown, "";
+        preset_level @ 0x448ee4: U32, Known, "From 'mhf.ini'";
+        custom @ 0x448ee8: U32, Known, "From 'mhf.ini'";
+        fullscreen_mode @ 0x448eec: U32, Known, "From 'mhf.ini'";
+        window_resolution_w @ 0x448ef0: U32, Known, "From 'mhf.ini'";
+        window_resolution_h @ 0x448ef4: U32, Known, "From 'mhf.ini'";
+        fullscreen_resolution_w @ 0x448ef8: U32, Known, "From 'mhf.ini'";
+        fullscreen_resolution_h @ 0x448efc: U32, Known, "From 'mhf.ini'";
+        disp_max_char @ 0x448f00: U32, Known, "From 'mhf.ini'";
+        texture_dxt_use @ 0x448f04: U32, Known, "From 'mhf.ini'";
+        now_monitor_wh @ 0x448f08: U32, Known, "From 'mhf.ini'";
+    }));
+    fields.extend(fields!(zz {
+        graphics_ver @ 0x448f0c: U32, Known, "From 'mhf.ini', picks 'mhfo-hd.dll' when 1";
+    }));
+    fields.extend(fields!(common Common3.common3 {
+        sound_notuse @ 0x448f10: U32, Known, "From 'mhf.ini'";
+        sound_volume @ 0x448f14: U32, Known, "From 'mhf.ini'";
+        sound_volume_inactivity @ 0x448f18: U32, Known, "From 'mhf.ini'";
+        sound_volume_minimize @ 0x448f1c: U32, Known, "From 'mhf.ini'";
+        sound_frequency @ 0x448f20: U32, Known, "From 'mhf.ini'";
+        sound_buffernum @ 0x448f24: U32, Known, "From 'mhf.ini'";
+        language @ 0x448f28: U32, Known, "From 'mhf.ini'";
+        font_quality @ 0x448f2c: U32, Known, "From 'mhf.ini'";
+        font_weight @ 0x448f30: U32, Known, "From 'mhf.ini'";
+        font_name @ 0x448f34: String, Known, "From 'mhf.ini', 'MS ????' by default";
+        unk_setting_448f94 @ 0x448f94: U32, Unknown, "Next to the 'mhf.ini' settings";
+    }));
+    fields.extend(fields!(zz {
+        unk_setting_448f98 @ 0x448f98: U32, Unknown, "Next to the 'mhf.ini' settings";
+    }));
+    fields.extend(fields!(common Common4.common4 {
+        drawskip @ 0x448f9c: U32, Known, "From 'mhf.ini'";
+        clogdis @ 0x448fa0: U32, Known, "From 'mhf.ini'";
+        proxy_use @ 0x448fa4: U32, Known, "From 'mhf.ini'";
+        proxy_ie @ 0x448fa8: U32, Known, "From 'mhf.ini'";
+        proxy_set @ 0x448fac: U32, Known, "From 'mhf.ini'";
+        proxy_addr @ 0x448fb0: String, Known, "From 'mhf.ini'";
+        proxy_port @ 0x448ff0: U32, Known, "From 'mhf.ini'";
+        server_sel @ 0x448ff4: U32, Known, "From 'mhf.ini'";
+        inner_ptr_1_4491a8 @ 0x448ff8: Pointer, Known, "Points at inner_1";
+        _pad_448ffc @ 0x448ffc: Bytes, Unknown, "";
+    }));
+    fields.extend(fields!(zz {
+        _pad_44903c @ 0x44903c: Bytes, Unknown, "The 'alt_ip_address' load happens here, with 0x100 width";
+        alt_ip_address @ 0x44907c: String, Known, "'host:8080'";
+        _pad_44913c @ 0x44913c: Bytes, Unknown, "";
+        server_expiry_ts @ 0x44917c: U32, Known, "";
+        remote_16e @ 0x449180: U32, Unknown, "";
+        fixed_449184_0x1 @ 0x449184: U32, Likely, "Always 1, 2 if 100812B0 == 9";
+        _pad_449188 @ 0x449188: Bytes, Unknown, "";
+    }));
+    fields.extend(fields!(f5 {
+        _pad_4406cc @ 0x4406cc: Bytes, Unknown, "";
+    }));
+    fields.extend(fields!(common Common5.common5 {
+        data_ptr @ 0x449190: Pointer, Known, "Points at the start of the block";
+        keyboard_layout @ 0x449194: Handle, Known, "";
+        inner_3 @ 0x449198: Marker, Likely, "Where inner_ptr_3_449198 points";
+        _pad_449198 @ 0x449198: Bytes, Unknown, "";
+        inner_1 @ 0x4491a8: Marker, Likely, "Where inner_ptr_1_4491a8 points";
+        _pad_4491a8 @ 0x4491a8: Bytes, Unknown, "";
+        fixed_4491ac_0x10 @ 0x4491ac: U32, Likely, "Always 0x10";
+        inner_ptr_2_4491d4 @ 0x4491b0: Pointer, Known, "Points at inner_2";
+        _pad_4491b4 @ 0x4491b4: Bytes, Unknown, "";
+        fixed_4491b8_0x10 @ 0x4491b8: U32, Likely, "Always 0x10";
+        inner_ptr_3_449198 @ 0x4491bc: Pointer, Known, "Points at inner_3";
+        proc_4 @ 0x4491c0: Proc, Likely, "Fixed 40605e";
+        _pad_4491c4 @ 0x4491c4: Bytes, Unknown, "";
+        proc_5 @ 0x4491c8: Proc, Likely, "Fixed 40609c";
+        _pad_4491cc @ 0x4491cc: Bytes, Unknown, "";
+        inner_2 @ 0x4491d4: Marker, Likely, "Where inner_ptr_2_4491d4 points";
+        _pad_4491d4 @ 0x4491d4: Bytes, Unknown, "";
+        mhfo_module @ 0x4491e8: Handle, Known, "Module handle of 'mhfo.dll' or 'mhfo-hd.dll'";
+        _pad_4491ec @ 0x4491ec: Bytes, Unknown, "";
+        _pad_4491f0 @ 0x4491f0: Bytes, Unknown, "";
+        mutex_master_ready_name @ 0x449710: String, Known, "";
+        _pad_449810 @ 0x449810: Bytes, Unknown, "";
+        mhddl_main @ 0x449c24: Proc, Known, "mhDLL_Main from the game DLL";
+    }));
+    fields.extend(fields!(global {
+        _pad_0x0000 @ 0x0: Bytes, Unknown, "";
+        _pad_0x0a00 @ 0xa00: Bytes, Unknown, "";
+        notices_count @ 0xa0c: U32Array, Known, "Length of each notice";
+        _pad_0x0a10 @ 0xa1c: Bytes, Unknown, "";
+        notices_flags @ 0xa24: U16Array, Known, "";
+        notices @ 0xa2c: Bytes, Known, "4 notices of up to 0x1000 bytes";
+        _filter @ 0x4a2c: Bytes, Unknown, "";
+        _pad_0x4a2c @ 0x7a2c: Bytes, Unknown, "";
+        mez_event_id @ 0x8aac: U32, Known, "";
+        mez_start @ 0x8ab0: U32, Known, "";
+        mez_end @ 0x8ab4: U32, Known, "";
+        mez_solo_tickets @ 0x8ab8: U32, Known, "";
+        mez_group_tickets @ 0x8abc: U32, Known, "";
+        mez_stalls @ 0x8ac0: U32Array, Known, "";
+    }));
+    fields
+}
+
+pub fn find(name: &str) -> Option<Field> {
+    fields().into_iter().find(|f| f.name == name)
+}
+
+fn hex(value: Option<usize>) -> String {
+    value.map_or_else(|| "-".to_owned(), |v| format!("{v:#x}"))
+}
+
+/// A Markdown reference with a table for each part.
+pub fn markdown() -> String {
+    let mut out = "# Launcher data fields\n\nGenerated by `mhf-iel-cli fields --format markdown`. \
+                   Addresses are in the original launcher, offsets from the start of the block or \
+                   the global alloc.\n"
+        .to_owned();
+    let fields = fields();
+    let mut parts: Vec<Part> = vec![];
+    for field in &fields {
+        if !parts.contains(&field.part) {
+            parts.push(field.part);
+        }
+    }
+    for part in parts {
+        let _ = write!(
+            out,
+            "\n## {part:?}\n\n| Field | ZZ | F5 | Size | Type | Confidence | Notes |\n\
+             | ----- | -- | -- | ---- | ---- | ---------- | ----- |\n"
+        );
+        for field in fields.iter().filter(|f| f.part == part) {
+            let location = |version| match field.address(version) {
+                Some(address) => format!("{address:#x}"),
+                None => hex(field.offset(version)),
+            };
+            let _ = writeln!(
+                out,
+                "| `{}` | {} | {} | {:#x} | {:?} | {:?} | {} |",
+                field.name,
+                location(MhfVersion::ZZ),
+                location(MhfVersion::F5),
+                field.size,
+                field.kind,
+                field.confidence,
+                field.notes
+            );
+        }
+    }
+    out
+}
+
+// Labels only go on the block, the global alloc has no fixed address
+fn labels(version: MhfVersion) -> impl Iterator<Item = (usize, Field)> {
+    fields()
+        .into_iter()
+        .filter_map(move |f| Some((f.address(version)?, f)))
+}
+
+/// A Ghidra script labelling the block in `version`'s `mhf.exe`.
+pub fn ghidra_script(version: MhfVersion) -> String {
+    let mut out = format!(
+        "# Labels the launcher data block in {version}'s mhf.exe, generated by mhf-iel\n\
+         # @category mhf-iel\n\
+         from ghidra.program.model.symbol import SourceType\n\n\
+         def label(address, name, comment):\n\
+         \x20   address = toAddr(address)\n\
+         \x20   createLabel(address, name, True, SourceType.USER_DEFINED)\n\
+         \x20   if comment:\n\
+         \x20       setEOLComment(address, comment)\n\n"
+    );
+    for (address, field) in labels(version) {
+        let _ = writeln!(
+            out,
+            "label({address:#x}, {:?}, {:?})",
+            field.name,
+            comment(&field)
+        );
+    }
+    out
+}
+
+/// An IDAPython script labelling the block in `version`'s `mhf.exe`.
+pub fn ida_script(version: MhfVersion) -> String {
+    let mut out = format!(
+        "# Labels the launcher data block in {version}'s mhf.exe, generated by mhf-iel\n\
+         import idc\n\n\
+         def label(address, name, comment):\n\
+         \x20   idc.set_name(address, name, idc.SN_NOWARN | idc.SN_NOCHECK)\n\
+         \x20   if comment:\n\
+         \x20       idc.set_cmt(address, comment, 0)\n\n"
+    );
+    for (address, field) in labels(version) {
+        let _ = writeln!(
+            out,
+            "label({address:#x}, {:?}, {:?})",
+            field.name,
+            comment(&field)
+        );
+    }
+    out
+}
+
+fn comment(field: &Field) -> String {
+    let mut comment = format!("{:?}, {:#x} bytes", field.part, field.size);
+    match field.confidence {
+        Confidence::Known => {}
+        Confidence::Likely => comment.push_str(", likely"),
+        Confidence::Unknown => comment.push_str(", unknown"),
+    }
+    if !field.notes.is_empty() {
+        let _ = write!(comment, ". {}", field.notes);
+    }
+    comment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Data, GLOBAL_ALLOC_SIZE};
+
+    // Sorted by offset, without the markers
+    fn layout(version: MhfVersion, global: bool) -> Vec<(usize, Field)> {
+        let mut fields: Vec<_> = fields()
+            .into_iter()
+            .filter(|f| f.kind != Kind::Marker && (f.part == Part::GlobalData) == global)
+            .filter_map(|f| Some((f.offset(version)?, f)))
+            .collect();
+        fields.sort_by_key(|(offset, _)| *offset);
+        fields
+    }
+
+    #[test]
+    fn fields_cover_everything_without_overlapping() {
+        for (version, global, size) in [
+            (MhfVersion::ZZ, false, Data::size(MhfVersion::ZZ)),
+            (MhfVersion::F5, false, Data::size(MhfVersion::F5)),
+            (MhfVersion::ZZ, true, GLOBAL_ALLOC_SIZE),
+        ] {
+            let mut end = 0;
+            for (offset, field) in layout(version, global) {
+                assert_eq!(offset, end, "{version} {} doesn't follow on", field.name);
+                assert!(field.size > 0, "{}", field.name);
+                end = offset + field.size;
+            }
+            assert_eq!(end, size, "{version} global: {global}");
+        }
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let fields = fields();
+        for (i, field) in fields.iter().enumerate() {
+            assert!(
+                fields[..i].iter().all(|f| f.name != field.name),
+                "{}",
+                field.name
+            );
+        }
+    }
+
+    #[test]
+    fn markers_point_into_the_block() {
+        for field in fields().iter().filter(|f| f.kind == Kind::Marker) {
+            assert_eq!(field.size, 0);
+            // Where the matching pointer field says it points
+            let pointer = format!("inner_ptr_{}_", &field.name["inner_".len()..]);
+            let pointer = fields()
+                .into_iter()
+                .find(|f| f.kind == Kind::Pointer && f.name.starts_with(&pointer))
+                .unwrap();
+            let address =
+                usize::from_str_radix(&pointer.name[pointer.name.len() - 6..], 16).unwrap();
+            assert_eq!(field.zz_address, Some(address), "{}", field.name);
+        }
+    }
+
+    #[test]
+    fn f5_addresses() {
+        assert_eq!(F5_BASE, 0x43e850);
+        assert_eq!(find("_pad_4406cc").unwrap().f5_address, Some(0x4406cc));
+        for field in fields() {
+            match field.part {
+                Part::GlobalData => {
+                    assert_eq!(field.zz_address, None);
+                    assert_eq!(field.f5_address, None);
+                    assert_eq!(field.zz_offset, field.f5_offset);
+                }
+                _ => {
+                    assert_eq!(field.zz_address, field.zz_offset.map(|o| ZZ_BASE + o));
+                    assert_eq!(field.f5_address, field.f5_offset.map(|o| F5_BASE + o));
+                }
+            }
+        }
+        // F5 has no 'char_ids', 'graphics_ver' or 'unk_setting_448f98' to shift the rest by
+        let zz = find("drawskip").unwrap();
+        assert_eq!(zz.zz_address, Some(0x448f9c));
+        assert_eq!(zz.f5_offset, Some(zz.zz_offset.unwrap() - 0x40 - 4 - 4));
+        assert_eq!(find("char_ids").unwrap().f5_offset, None);
+        // Written for F5 too, since the common part is shared
+        let user_rights = find("user_rights").unwrap();
+        assert_eq!(user_rights.f5_offset, user_rights.zz_offset);
+        assert_eq!(
+            user_rights.f5_offset.map(|o| o + 4),
+            find("selected_char_hr").unwrap().f5_offset
+        );
+        assert_eq!(find("_pad_4406cc").unwrap().zz_offset, None);
+    }
+
+    #[test]
+    fn markdown_has_a_row_per_field() {
+        let markdown = markdown();
+        assert!(markdown.starts_with("# Launcher data fields\n"));
+        for part in ["Common1", "Common5", "DataZZ", "DataF5", "GlobalData"] {
+            assert!(markdown.contains(&format!("\n## {part}\n")), "{part}");
+        }
+        assert!(markdown.contains(
+            "| `remote_addr` | 0x448c68 | 0x440340 | 0x100 | String | Known | Sign server as 'host:port' |\n"
+        ));
+        assert!(markdown.contains("| `char_ids` | 0x448e8c | - | 0x40 | U32Array | Known |  |\n"));
+        assert!(markdown.contains("| `mez_start` | 0x8ab0 | 0x8ab0 | 0x4 | U32 | Known |  |\n"));
+        let rows = markdown.lines().filter(|l| l.starts_with("| `")).count();
+        assert_eq!(rows, fields().len());
+    }
+
+    #[test]
+    fn scripts_label_the_block() {
+        for version in [MhfVersion::ZZ, MhfVersion::F5] {
+            let ghidra = ghidra_script(version);
+            let ida = ida_script(version);
+            assert!(ghidra.contains("createLabel(address, name, True, SourceType.USER_DEFINED)"));
+            assert!(ida.contains("idc.set_name(address, name, idc.SN_NOWARN | idc.SN_NOCHECK)"));
+            for script in [&ghidra, &ida] {
+                assert!(script.contains(&format!("in {version}'s mhf.exe")));
+                let labels = script.lines().filter(|l| l.starts_with("label(0x")).count();
+                let block = fields()
+                    .iter()
+                    .filter(|f| f.address(version).is_some())
+                    .count();
+                assert_eq!(labels, block);
+                // The global alloc has no fixed address
+                assert!(!script.contains("\"notices\""));
+            }
+        }
+        let ghidra = ghidra_script(MhfVersion::ZZ);
+        assert!(ghidra.contains(
+            "label(0x448e74, \"user_rights\", \"Common1, 0x4 bytes, likely. Still in the F5 block, but F5 is thought not to read it\")\n"
+        ));
+        assert!(
+            ghidra.contains("label(0x448ed4, \"unk_448ed4\", \"Common2, 0x4 bytes, unknown\")\n")
+        );
+        let ida = ida_script(MhfVersion::F5);
+        assert!(ida.contains("label(0x4406cc, \"_pad_4406cc\", \"DataF5, "));
+        assert!(!ida.contains("\"char_ids\""));
+    }
+
+    #[test]
+    fn json() {
+        let json = serde_json::to_value(find("mez_stalls").unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "mez_stalls",
+                "part": "GlobalData",
+                "zz_offset": 0x8ac0,
+                "f5_offset": 0x8ac0,
+                "zz_address": null,
+                "f5_address": null,
+                "size": 0x20,
+                "kind": "u32_array",
+                "confidence": "known",
+                "notes": "",
+            })
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::block::{Data, GLOBAL_ALLOC_SIZE};
+use crate::registry::{self, Confidence, Part};
+use crate::{Error, MhfVersion, Result};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    /// The launcher block passed to `mhDLL_Main`
+    #[default]
+    Block,
+    GlobalAlloc,
+}
+
+/// Written little endian, strings with a terminating null byte.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Value {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    Bytes(Vec<u8>),
+    String(String),
+}
+
+impl Value {
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::U8(v) => vec![*v],
+            Self::U16(v) => v.to_le_bytes().to_vec(),
+            Self::U32(v) => v.to_le_bytes().to_vec(),
+            Self::Bytes(v) => v.clone(),
+            Self::String(v) => [v.as_bytes(), &[0]].concat(),
+        }
+    }
+}
+
+/// A raw write into the launcher data, for testing what the unknown fields do without rebuilding.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ResearchWrite {
+    #[serde(default)]
+    pub target: Target,
+    /// One of the unknown or padding fields, such as `unk_448ed4` or `_filter`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// From the start of `field`, or of the target when there's no field
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(flatten)]
+    pub value: Value,
+}
+
+/// A `ResearchWrite` resolved for a version, as `run` applies it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Patch {
+    pub target: Target,
+    pub offset: usize,
+    pub data: Vec<u8>,
+}
+
+fn target(part: Part) -> Target {
+    match part {
+        Part::GlobalData => Target::GlobalAlloc,
+        _ => Target::Block,
+    }
+}
+
+fn target_size(target: Target, version: MhfVersion) -> usize {
+    match target {
+        Target::Block => Data::size(version),
+        Target::GlobalAlloc => GLOBAL_ALLOC_SIZE,
+    }
+}
+
+impl ResearchWrite {
+    pub fn patch(&self, version: MhfVersion) -> Result<Patch> {
+        let data = self.value.to_bytes();
+        let (start, size, name) = match &self.field {
+            Some(name) => {
+                // Only what `run` leaves alone, the fields it fills in can be changed through the config
+                let field = registry::find(name)
+                    .filter(|f| {
+                        f.confidence == Confidence::Unknown && target(f.part) == self.target
+                    })
+                    .ok_or_else(|| Error::Research(format!("unknown field '{name}'")))?;
+                let offset = field
+                    .offset(version)
+                    .ok_or_else(|| Error::Research(format!("{version} has no '{name}'")))?;
+                (offset, field.size, format!("'{name}'"))
+            }
+            None => (
+                0,
+                target_size(self.target, version),
+                match self.target {
+                    Target::Block => format!("the {version} launcher block"),
+                    Target::GlobalAlloc => "the global alloc".to_owned(),
+                },
+            ),
+        };
+        if self.offset.saturating_add(data.len()) > size {
+            return Err(Error::Research(format!(
+                "{} bytes at {:#x} don't fit in {name}, which has {size:#x}",
+                data.len(),
+                self.offset
+            )));
+        }
+        let offset = start + self.offset;
+        if self.field.is_none() {
+            // Otherwise this could change what the policy already checked, like the server address
+            let end = offset + data.len();
+            if let Some(field) = registry::fields().into_iter().find(|f| {
+                f.confidence != Confidence::Unknown
+                    && target(f.part) == self.target
+                    && f.offset(version)
+                        .is_some_and(|o| o < end && offset < o + f.size)
+            }) {
+                return Err(Error::Research(format!(
+                    "{} bytes at {offset:#x} overlap '{}', which is set from the config",
+                    data.len(),
+                    field.name
+                )));
+            }
+        }
+        Ok(Patch {
+            target: self.target,
+            offset,
+            data,
+        })
+    }
+}
+
+pub fn patches(writes: &[ResearchWrite], version: MhfVersion) -> Result<Vec<Patch>> {
+    writes.iter().map(|write| write.patch(version)).collect()
+}
+
+/// Writes the patches for `target` over `bytes`, in order, so later ones win.
+pub fn apply(patches: &[Patch], target: Target, bytes: &mut [u8]) {
+    for patch in patches.iter().filter(|p| p.target == target) {
+        if let Some(dest) = bytes.get_mut(patch.offset..patch.offset + patch.data.len()) {
+            dest.copy_from_slice(&patch.data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(target: Target, offset: usize, value: Value) -> ResearchWrite {
+        ResearchWrite {
+            target,
+            field: None,
+            offset,
+            value,
+        }
+    }
+
+    #[test]
+    fn raw_writes_cant_touch_known_fields() {
+        for version in [MhfVersion::ZZ, MhfVersion::F5] {
+            for name in ["remote_addr", "remote_host", "alt_ip_address", "proc_1"] {
+                let Some(offset) = registry::find(name).unwrap().offset(version) else {
+                    continue;
+                };
+                let write = raw(Target::Block, offset, Value::String("evil:53310".into()));
+                assert!(write.patch(version).is_err(), "{version} {name}");
+                // Starting before the field and running into it
+                let write = raw(Target::Block, offset - 2, Value::U32(u32::MAX));
+                assert!(write.patch(version).is_err(), "{version} {name}");
+            }
+        }
+        let offset = registry::find("notices").unwrap().zz_offset.unwrap();
+        let write = raw(Target::GlobalAlloc, offset, Value::U8(1));
+        assert!(write.patch(MhfVersion::ZZ).is_err());
+    }
+
+    #[test]
+    fn raw_writes_into_unknown_fields() {
+        let field = registry::find("unk_448ed4").unwrap();
+        let offset = field.zz_offset.unwrap();
+        let patch = raw(Target::Block, offset, Value::U32(1))
+            .patch(MhfVersion::ZZ)
+            .unwrap();
+        assert_eq!(patch.offset, offset);
+        assert_eq!(patch.data, [1, 0, 0, 0]);
+        // One byte too many runs into the next field
+        let write = raw(Target::Block, offset, Value::Bytes(vec![0; field.size + 1]));
+        assert!(write.patch(MhfVersion::ZZ).is_err());
+    }
+
+    #[test]
+    fn field_writes() {
+        let write = ResearchWrite {
+            target: Target::Block,
+            field: Some("unk_448ed4".into()),
+            offset: 2,
+            value: Value::U16(0xffff),
+        };
+        let patch = write.patch(MhfVersion::ZZ).unwrap();
+        assert_eq!(patch.offset, 0x448ed4 - registry::ZZ_BASE + 2);
+        let write = ResearchWrite {
+            field: Some("remote_addr".into()),
+            ..write
+        };
+        assert!(write.patch(MhfVersion::ZZ).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use bitflags::bitflags;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+bitflags! {
+    /// The subscription courses a user has, bit `n` being Erupe's course ID `n`. Other bits are
+    /// kept as they are.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct UserRights: u32 {
+        const TRIAL = 1 << 1;
+        const HUNTER_LIFE = 1 << 2;
+        const EXTRA = 1 << 3;
+        const EXTRA_B = 1 << 4;
+        const MOBILE = 1 << 5;
+        const PREMIUM = 1 << 6;
+        const PALLONE = 1 << 7;
+        const ASSIST = 1 << 8;
+        const N = 1 << 9;
+        const HIDEN = 1 << 10;
+        const HUNTER_SUPPORT = 1 << 11;
+        const N_BOOST = 1 << 12;
+    }
+}
+
+// As written in configs and shown to users
+const NAMES: &[(&str, UserRights)] = &[
+    ("Trial", UserRights::TRIAL),
+    ("HunterLife", UserRights::HUNTER_LIFE),
+    ("Extra", UserRights::EXTRA),
+    ("ExtraB", UserRights::EXTRA_B),
+    ("Mobile", UserRights::MOBILE),
+    ("Premium", UserRights::PREMIUM),
+    ("Pallone", UserRights::PALLONE),
+    ("Assist", UserRights::ASSIST),
+    ("N", UserRights::N),
+    ("Hiden", UserRights::HIDEN),
+    ("HunterSupport", UserRights::HUNTER_SUPPORT),
+    ("NBoost", UserRights::N_BOOST),
+];
+
+impl Default for UserRights {
+    /// Same as a fresh account in Erupe
+    fn default() -> Self {
+        Self::HUNTER_LIFE | Self::EXTRA
+    }
+}
+
+impl UserRights {
+    pub fn names(&self) -> Vec<&'static str> {
+        NAMES
+            .iter()
+            .filter(|(_, course)| self.contains(*course))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    fn from_course(name: &str) -> Option<Self> {
+        NAMES
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, course)| *course)
+    }
+}
+
+/// The course names separated by commas, unknown bits in hex, or `none`.
+impl Display for UserRights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts: Vec<String> = self.names().into_iter().map(str::to_owned).collect();
+        let unknown = self.difference(Self::all()).bits();
+        if unknown != 0 {
+            parts.push(format!("{unknown:#x}"));
+        }
+        if parts.is_empty() {
+            return write!(f, "none");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Takes the raw integer, or course names separated by commas or `|`.
+impl FromStr for UserRights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(bits) = s.parse() {
+            return Ok(Self::from_bits_retain(bits));
+        }
+        if s.is_empty() || s.eq_ignore_ascii_case("none") {
+            return Ok(Self::empty());
+        }
+        s.split([',', '|'])
+            .map(|name| {
+                let name = name.trim();
+                Self::from_course(name).ok_or_else(|| {
+                    let names: Vec<&str> = NAMES.iter().map(|(n, _)| *n).collect();
+                    format!(
+                        "unknown course '{name}', expected one of {}",
+                        names.join(", ")
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+// Written as the raw integer, so other launchers can keep reading it
+impl Serialize for UserRights {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.bits())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UserRightsRepr {
+    Bits(u32),
+    Names(Vec<String>),
+    Text(String),
+}
+
+impl<'de> Deserialize<'de> for UserRights {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match UserRightsRepr::deserialize(deserializer)? {
+            UserRightsRepr::Bits(bits) => Ok(Self::from_bits_retain(bits)),
+            UserRightsRepr::Names(names) => names
+                .iter()
+                .map(|name| name.parse::<Self>())
+                .collect::<Result<Self, _>>()
+                .map_err(serde::de::Error::custom),
+            UserRightsRepr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl JsonSchema for UserRights {
+    fn schema_name() -> String {
+        "UserRights".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let names: Vec<&str> = NAMES.iter().map(|(n, _)| *n).collect();
+        serde_json::from_value(serde_json::json!({
+            "description": "Subscription courses, as the raw integer or a list of course names",
+            "anyOf": [
+                { "type": "integer", "format": "uint32", "minimum": 0 },
+                { "type": "array", "items": { "enum": names } },
+                { "type": "string" },
+            ],
+        }))
+        .unwrap_or(Schema::Bool(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn from_str() {
+        let rights = UserRights::HUNTER_LIFE | UserRights::PREMIUM;
+        assert_eq!("68".parse(), Ok(rights));
+        assert_eq!("HunterLife, Premium".parse(), Ok(rights));
+        assert_eq!("premium|hunterlife".parse(), Ok(rights));
+        assert_eq!(" none ".parse(), Ok(UserRights::empty()));
+        assert_eq!("".parse(), Ok(UserRights::empty()));
+        // Bits without a course are kept
+        assert_eq!("1".parse::<UserRights>().unwrap().bits(), 1);
+        let e = "HunterLife, Gold".parse::<UserRights>().unwrap_err();
+        assert!(
+            e.starts_with("unknown course 'Gold', expected one of Trial, "),
+            "{e}"
+        );
+        assert!("-1".parse::<UserRights>().is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(UserRights::default().to_string(), "HunterLife, Extra");
+        assert_eq!(UserRights::empty().to_string(), "none");
+        assert_eq!(UserRights::N_BOOST.to_string(), "NBoost");
+        let unknown = UserRights::from_bits_retain(1 | (1 << 20)) | UserRights::TRIAL;
+        assert_eq!(unknown.to_string(), "Trial, 0x100001");
+        assert_eq!(UserRights::from_bits_retain(1).to_string(), "0x1");
+        // Round trips through FromStr, unknown bits aside
+        for rights in [
+            UserRights::all(),
+            UserRights::default(),
+            UserRights::empty(),
+        ] {
+            assert_eq!(rights.to_string().parse(), Ok(rights));
+        }
+    }
+
+    #[test]
+    fn serde() {
+        let rights = UserRights::TRIAL | UserRights::EXTRA;
+        for json in [json!(10), json!(["Trial", "Extra"]), json!("Trial, Extra")] {
+            assert_eq!(serde_json::from_value::<UserRights>(json).unwrap(), rights);
+        }
+        assert_eq!(serde_json::to_value(rights).unwrap(), json!(10));
+        assert_eq!(
+            serde_json::to_value(UserRights::from_bits_retain(u32::MAX)).unwrap(),
+            json!(u32::MAX)
+        );
+        assert!(serde_json::from_value::<UserRights>(json!(["Gold"])).is_err());
+        assert!(serde_json::from_value::<UserRights>(json!("Gold")).is_err());
+        assert!(serde_json::from_value::<UserRights>(json!(-1)).is_err());
+    }
+}
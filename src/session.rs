@@ -0,0 +1,346 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::policy::{self, POLICY_ENV};
+use crate::{Error, Event, MhfConfig, Result};
+
+/// Environment variable overriding where `launch_detached` looks for the CLI.
+pub const CLI_ENV: &str = "MHF_IEL_CLI";
+
+#[cfg(windows)]
+const CLI_NAME: &str = "mhf-iel-cli.exe";
+#[cfg(not(windows))]
+const CLI_NAME: &str = "mhf-iel-cli";
+
+/// How a detached session ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SessionExit {
+    /// The game exited with code 0
+    Success,
+    /// The game exited with a non-zero code
+    GameError {
+        code: isize,
+    },
+    /// The game never ran, `code` is one of the CLI's exit codes
+    LaunchFailed {
+        code: i32,
+        message: String,
+    },
+    Killed,
+    /// The helper process died without reporting a result, usually the game crashing it
+    Crashed {
+        code: Option<i32>,
+    },
+}
+
+/// A game running in a separate `mhf-iel-cli` process, see `launch_detached`.
+pub struct GameSession {
+    child: Child,
+    events: Receiver<Event>,
+    reader: Option<JoinHandle<Option<Value>>>,
+    killed: bool,
+    exit: Option<SessionExit>,
+}
+
+/// Defaults to `MHF_IEL_CLI`, then the CLI next to the current executable, then `PATH`.
+pub fn default_cli_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(CLI_ENV) {
+        return path.into();
+    }
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(CLI_NAME)))
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| CLI_NAME.into())
+}
+
+/// Runs the game in a separate process, so it crashing doesn't take the caller down with it.
+pub fn launch_detached(config: MhfConfig) -> Result<GameSession> {
+    launch_detached_with(&default_cli_path(), config)
+}
+
+pub fn launch_detached_with(cli_path: &Path, config: MhfConfig) -> Result<GameSession> {
+    config.validate()?;
+    // The CLI only knows the policy from its profiles, so it's given this one to check as well
+    let policy = policy::policy();
+    policy.check(&config)?;
+    let policy = serde_json::to_string(&policy).map_err(|e| Error::Launch(e.to_string()))?;
+    let data = serde_json::to_vec(&config).map_err(|e| Error::Launch(e.to_string()))?;
+    let mut child = Command::new(cli_path)
+        .args(["run", "--config-stdin", "--output", "json", "--events"])
+        .env(POLICY_ENV, policy)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Launch(format!("{}: {e}", cli_path.display())))?;
+    // The pipe is only ever seen by the child, unlike argv or the environment
+    let written = child.stdin.take().map(|mut stdin| stdin.write_all(&data));
+    if let Some(Err(e)) = written {
+        let _ = child.kill();
+        return Err(Error::Launch(e.to_string()));
+    }
+    let (sender, events) = mpsc::channel();
+    let reader = child
+        .stdout
+        .take()
+        .map(|stdout| std::thread::spawn(move || read_output(stdout, sender)));
+    Ok(GameSession {
+        child,
+        events,
+        reader,
+        killed: false,
+        exit: None,
+    })
+}
+
+// Events come one per line, the final line is the CLI's JSON result
+fn read_output(stdout: ChildStdout, sender: Sender<Event>) -> Option<Value> {
+    let mut result = None;
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let Ok(value) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        if value.get("event").is_some() {
+            if let Ok(event) = serde_json::from_value(value) {
+                let _ = sender.send(event);
+            }
+        } else if value.get("ok").is_some() {
+            result = Some(value);
+        }
+    }
+    result
+}
+
+fn classify(status: ExitStatus, result: Option<Value>, killed: bool) -> SessionExit {
+    if killed {
+        return SessionExit::Killed;
+    }
+    let Some(result) = result else {
+        return SessionExit::Crashed {
+            code: status.code(),
+        };
+    };
+    if let Some(code) = result.pointer("/result/game_code").and_then(Value::as_i64) {
+        return match code {
+            0 => SessionExit::Success,
+            code => SessionExit::GameError {
+                code: code as isize,
+            },
+        };
+    }
+    SessionExit::LaunchFailed {
+        code: status.code().unwrap_or(-1),
+        message: result["error"].as_str().unwrap_or_default().to_owned(),
+    }
+}
+
+impl GameSession {
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Status events in the order they happen. Disconnects once the session ends.
+    pub fn events(&self) -> &Receiver<Event> {
+        &self.events
+    }
+
+    fn finish(&mut self, status: ExitStatus) -> SessionExit {
+        if let Some(exit) = &self.exit {
+            return exit.clone();
+        }
+        let result = self.reader.take().and_then(|r| r.join().ok()).flatten();
+        let exit = classify(status, result, self.killed);
+        self.exit = Some(exit.clone());
+        exit
+    }
+
+    /// Blocks until the session ends.
+    pub fn wait(&mut self) -> Result<SessionExit> {
+        let status = self
+            .child
+            .wait()
+            .map_err(|e| Error::Launch(e.to_string()))?;
+        Ok(self.finish(status))
+    }
+
+    /// Returns `None` while the session is still running.
+    pub fn try_wait(&mut self) -> Result<Option<SessionExit>> {
+        let status = self
+            .child
+            .try_wait()
+            .map_err(|e| Error::Launch(e.to_string()))?;
+        Ok(status.map(|status| self.finish(status)))
+    }
+
+    pub fn kill(&mut self) -> Result<()> {
+        if self.try_wait()?.is_some() {
+            return Ok(());
+        }
+        self.child
+            .kill()
+            .map_err(|e| Error::Launch(e.to_string()))?;
+        self.killed = true;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    // A CLI that keeps its arguments, stdin and policy, then prints `output` and exits with `code`
+    struct Stub(PathBuf);
+
+    impl Stub {
+        fn new(name: &str, output: &[&str], code: i32) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("mhf-iel-session-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let mut script = String::from("#!/bin/sh\ndir=\"$(dirname \"$0\")\"\n");
+            script += "printf '%s\\n' \"$*\" > \"$dir/args\"\n";
+            script += "printf '%s' \"$MHF_IEL_POLICY\" > \"$dir/policy\"\n";
+            script += "cat > \"$dir/stdin\"\n";
+            for line in output {
+                script += &format!("printf '%s\\n' '{line}'\n");
+            }
+            script += &format!("exit {code}\n");
+            std::fs::write(dir.join("cli"), script).unwrap();
+            std::fs::set_permissions(dir.join("cli"), std::fs::Permissions::from_mode(0o755))
+                .unwrap();
+            Self(dir)
+        }
+
+        fn launch(&self) -> Result<GameSession> {
+            launch_detached_with(&self.0.join("cli"), config())
+        }
+
+        fn read(&self, name: &str) -> String {
+            std::fs::read_to_string(self.0.join(name)).unwrap()
+        }
+    }
+
+    impl Drop for Stub {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn config() -> MhfConfig {
+        MhfConfig {
+            char_id: 1,
+            user_token: "0123456789abcdef".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn success() {
+        let stub = Stub::new(
+            "success",
+            &[
+                r#"{"event":"config_validated"}"#,
+                "not json",
+                r#"{"event":"main_entered"}"#,
+                r#"{"event":"game_exited","code":0}"#,
+                r#"{"ok":true,"result":{"update":null,"game_code":0}}"#,
+            ],
+            0,
+        );
+        let mut session = stub.launch().unwrap();
+        assert_eq!(session.wait().unwrap(), SessionExit::Success);
+        let events: Vec<Event> = session.events().try_iter().collect();
+        assert_eq!(
+            events,
+            [
+                Event::ConfigValidated,
+                Event::MainEntered,
+                Event::GameExited { code: 0 }
+            ]
+        );
+        // The config only goes through stdin
+        assert_eq!(
+            stub.read("args"),
+            "run --config-stdin --output json --events\n"
+        );
+        let sent: MhfConfig = serde_json::from_str(&stub.read("stdin")).unwrap();
+        assert_eq!(sent.user_token, "0123456789abcdef");
+        // Waiting again gives the same result
+        assert_eq!(session.try_wait().unwrap(), Some(SessionExit::Success));
+    }
+
+    #[test]
+    fn game_error() {
+        let stub = Stub::new(
+            "game-error",
+            &[r#"{"ok":true,"result":{"update":null,"game_code":70}}"#],
+            70,
+        );
+        let exit = stub.launch().unwrap().wait().unwrap();
+        assert_eq!(exit, SessionExit::GameError { code: 70 });
+    }
+
+    #[test]
+    fn launch_failed() {
+        let stub = Stub::new(
+            "launch-failed",
+            &[r#"{"ok":false,"exit_code":4,"error":"error running mhf: no dll"}"#],
+            4,
+        );
+        let exit = stub.launch().unwrap().wait().unwrap();
+        assert_eq!(
+            exit,
+            SessionExit::LaunchFailed {
+                code: 4,
+                message: "error running mhf: no dll".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn crashed() {
+        let stub = Stub::new("crashed", &[r#"{"event":"main_entered"}"#], 9);
+        let mut session = stub.launch().unwrap();
+        assert_eq!(
+            session.wait().unwrap(),
+            SessionExit::Crashed { code: Some(9) }
+        );
+        assert_eq!(session.events().try_iter().count(), 1);
+    }
+
+    #[test]
+    fn killed() {
+        let stub = Stub::new("killed", &[], 0);
+        std::fs::write(stub.0.join("cli"), "#!/bin/sh\nexec sleep 30\n").unwrap();
+        let mut session = stub.launch().unwrap();
+        assert_eq!(session.try_wait().unwrap(), None);
+        session.kill().unwrap();
+        assert_eq!(session.wait().unwrap(), SessionExit::Killed);
+    }
+
+    #[test]
+    fn launch_errors() {
+        let missing = launch_detached_with(Path::new("/nonexistent/mhf-iel-cli"), config());
+        assert!(matches!(missing, Err(Error::Launch(_))));
+        let stub = Stub::new("invalid", &[], 0);
+        let invalid = MhfConfig {
+            user_token: "short".to_owned(),
+            ..config()
+        };
+        assert!(launch_detached_with(&stub.0.join("cli"), invalid).is_err());
+        // Never started
+        assert!(!stub.0.join("args").exists());
+    }
+}
@@ -0,0 +1,185 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+/// Values read from `mhf.ini`, with the same defaults the original launcher uses for missing keys.
+#[derive(Debug, Clone, Serialize)]
+pub struct MhfSettings {
+    pub preset_level: u32,
+    pub custom: u32,
+    pub fullscreen_mode: u32,
+    pub window_resolution_w: u32,
+    pub window_resolution_h: u32,
+    pub fullscreen_resolution_w: u32,
+    pub fullscreen_resolution_h: u32,
+    pub graphics_ver: u32,
+    pub disp_max_char: u32,
+    pub texture_dxt_use: u32,
+    pub now_monitor_wh: u32,
+    pub sound_notuse: u32,
+    pub sound_volume: u32,
+    pub sound_volume_inactivity: u32,
+    pub sound_volume_minimize: u32,
+    pub sound_frequency: u32,
+    pub sound_buffernum: u32,
+    pub language: u32,
+    pub font_quality: u32,
+    pub font_weight: u32,
+    // Usually Shift-JIS, kept as raw bytes since it's copied as-is
+    pub font_name: Vec<u8>,
+    pub drawskip: u32,
+    pub clogdis: u32,
+    pub proxy_use: u32,
+    pub proxy_ie: u32,
+    pub proxy_set: u32,
+    pub proxy_addr: String,
+    pub proxy_port: u32,
+    pub server_sel: u32,
+}
+
+struct Ini(Vec<(String, String, Vec<u8>)>);
+
+impl Ini {
+    fn parse(data: &[u8]) -> Self {
+        let mut section = String::new();
+        let mut entries = vec![];
+        for line in data.split(|&c| c == b'\n') {
+            let line = line.trim_ascii();
+            if line.starts_with(b";") || line.starts_with(b"#") {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix(b"[").and_then(|l| l.strip_suffix(b"]")) {
+                section = String::from_utf8_lossy(name).trim().to_ascii_uppercase();
+            } else if let Some(i) = line.iter().position(|&c| c == b'=') {
+                let key = String::from_utf8_lossy(&line[..i])
+                    .trim()
+                    .to_ascii_uppercase();
+                entries.push((section.clone(), key, line[i + 1..].trim_ascii().to_vec()));
+            }
+        }
+        Self(entries)
+    }
+
+    fn get(&self, section: &str, key: &str) -> Option<&[u8]> {
+        self.0
+            .iter()
+            .find(|(s, k, _)| s.eq_ignore_ascii_case(section) && k.eq_ignore_ascii_case(key))
+            .map(|(_, _, v)| v.as_slice())
+    }
+
+    // Same as GetPrivateProfileIntA: a missing or empty value gives the default, otherwise the
+    // leading digits are read, so a value without any is 0
+    fn int(&self, section: &str, key: &str, default: u32) -> u32 {
+        let value = self.bytes(section, key, b"");
+        if value.is_empty() {
+            return default;
+        }
+        let (negative, digits) = match value.trim_ascii_start() {
+            [b'-', digits @ ..] => (true, digits),
+            [b'+', digits @ ..] => (false, digits),
+            digits => (false, digits),
+        };
+        let n = digits
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .fold(0u32, |n, c| {
+                n.wrapping_mul(10).wrapping_add((c - b'0') as u32)
+            });
+        match negative {
+            true => n.wrapping_neg(),
+            false => n,
+        }
+    }
+
+    fn bytes(&self, section: &str, key: &str, default: &[u8]) -> Vec<u8> {
+        let value = self.get(section, key).unwrap_or(default);
+        let value = match value {
+            [b'"', inner @ .., b'"'] | [b'\'', inner @ .., b'\''] => inner,
+            value => value,
+        };
+        value.to_vec()
+    }
+}
+
+impl Default for MhfSettings {
+    fn default() -> Self {
+        Self::parse(b"")
+    }
+}
+
+impl MhfSettings {
+    pub fn parse(data: &[u8]) -> Self {
+        let ini = Ini::parse(data);
+        Self {
+            preset_level: ini.int("SET", "PRESET_LEVEL", 0),
+            custom: ini.int("SET", "CUSTOM", 1),
+            fullscreen_mode: ini.int("SCREEN", "FULLSCREEN_MODE", 1),
+            window_resolution_w: ini.int("SCREEN", "WINDOW_RESOLUTION_W", 1920),
+            window_resolution_h: ini.int("SCREEN", "WINDOW_RESOLUTION_H", 1080),
+            fullscreen_resolution_w: ini.int("SCREEN", "FULLSCREEN_RESOLUTION_W", 1920),
+            fullscreen_resolution_h: ini.int("SCREEN", "FULLSCREEN_RESOLUTION_H", 1080),
+            graphics_ver: ini.int("VIDEO", "GRAPHICS_VER", 1),
+            disp_max_char: ini.int("VIDEO", "DISP_MAX_CHAR", 100),
+            texture_dxt_use: ini.int("VIDEO", "TEXTURE_DXT_USE", 0),
+            now_monitor_wh: ini.int("VIDEO", "NOW_MONITOR_WH", 0),
+            sound_notuse: ini.int("SOUND", "SOUND_NOTUSE", 0),
+            sound_volume: ini.int("SOUND", "SOUND_VOLUME", 0),
+            sound_volume_inactivity: ini.int("SOUND", "SOUND_VOLUME_INACTIVITY", 0),
+            sound_volume_minimize: ini.int("SOUND", "SOUND_VOLUME_MINIMIZE", 0),
+            sound_frequency: ini.int("SOUND", "SOUND_FREQUENCY", 48000),
+            sound_buffernum: ini.int("SOUND", "SOUND_BUFFERNUM", 2048),
+            language: ini.int("LOCALIZATION", "LANGUAGE", 0),
+            font_quality: ini.int("FONT", "QUALITY", 4),
+            font_weight: ini.int("FONT", "WEIGHT", 0x2bc),
+            font_name: ini.bytes("FONT", "NAME", b"MS ????"),
+            drawskip: ini.int("OPTION", "DRAWSKIP", 1),
+            clogdis: ini.int("OPTION", "CLOGDIS", 0),
+            proxy_use: ini.int("LAUNCH", "PROXY_USE", 0),
+            proxy_ie: ini.int("LAUNCH", "PROXY_IE", 0),
+            proxy_set: ini.int("LAUNCH", "PROXY_SET", 1),
+            proxy_addr: String::from_utf8_lossy(&ini.bytes("LAUNCH", "PROXY_ADDR", b"127.0.0.1"))
+                .into_owned(),
+            proxy_port: ini.int("LAUNCH", "PROXY_PORT", 8888),
+            server_sel: ini.int("LAUNCH", "SERVER_SEL", 1),
+        }
+    }
+
+    /// Reads `mhf.ini` from `mhf_folder`. A missing file behaves like an empty one.
+    pub fn load(mhf_folder: &Path) -> Self {
+        Self::parse(&std::fs::read(mhf_folder.join("mhf.ini")).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ints() {
+        let ini = Ini::parse(
+            b"[VIDEO]\r\nGRAPHICS_VER=0\r\nDISP_MAX_CHAR=50 chars\r\nTEXTURE_DXT_USE=yes\r\n\
+              NOW_MONITOR_WH=\r\nLANGUAGE=-1\r\nQUALITY=\"3\"\r\nWEIGHT= +700\r\n",
+        );
+        assert_eq!(ini.int("VIDEO", "GRAPHICS_VER", 1), 0);
+        assert_eq!(ini.int("video", "graphics_ver", 1), 0);
+        assert_eq!(ini.int("VIDEO", "DISP_MAX_CHAR", 100), 50);
+        // Unlike a missing or empty value, one without digits doesn't give the default
+        assert_eq!(ini.int("VIDEO", "TEXTURE_DXT_USE", 7), 0);
+        assert_eq!(ini.int("VIDEO", "NOW_MONITOR_WH", 7), 7);
+        assert_eq!(ini.int("VIDEO", "MISSING", 7), 7);
+        assert_eq!(ini.int("VIDEO", "LANGUAGE", 0), u32::MAX);
+        assert_eq!(ini.int("VIDEO", "QUALITY", 4), 3);
+        assert_eq!(ini.int("VIDEO", "WEIGHT", 0), 700);
+        assert_eq!(ini.int("SOUND", "LANGUAGE", 2), 2);
+    }
+
+    #[test]
+    fn defaults() {
+        let settings =
+            MhfSettings::parse(b"[VIDEO]\nGRAPHICS_VER=junk\n[SCREEN]\nWINDOW_RESOLUTION_W=1280\n");
+        assert_eq!(settings.graphics_ver, 0);
+        assert_eq!(settings.window_resolution_w, 1280);
+        assert_eq!(settings.window_resolution_h, 1080);
+        assert_eq!(settings.font_name, MhfSettings::default().font_name);
+    }
+}
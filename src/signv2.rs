@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rights::UserRights;
+use crate::{Error, MezFesStall, MhfConfig, Notice, Result};
+
+pub const DEFAULT_ENDPOINT: &str = "http://127.0.0.1:8080";
+pub const DEFAULT_SERVER_PORT: u32 = 53312;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct User {
+    pub token_id: u32,
+    pub token: String,
+    pub rights: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Character {
+    pub id: u32,
+    pub name: String,
+    pub is_female: bool,
+    pub weapon: u32,
+    pub hr: u32,
+    pub gr: u32,
+    pub last_login: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MezFes {
+    pub id: u32,
+    pub start: u32,
+    pub end: u32,
+    pub solo_tickets: u32,
+    pub group_tickets: u32,
+    pub stalls: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthData {
+    pub current_ts: u32,
+    pub expiry_ts: u32,
+    pub entrance_count: u32,
+    pub notices: Vec<String>,
+    pub user: User,
+    pub characters: Vec<Character>,
+    pub mez_fes: Option<MezFes>,
+    pub patch_server: String,
+}
+
+#[derive(Serialize)]
+struct Credentials<'a> {
+    username: &'a str,
+    password: &'a str,
+}
+
+#[derive(Serialize)]
+struct TokenRequest<'a> {
+    token: &'a str,
+}
+
+/// Client for the launcher endpoints of Erupe's signv2server.
+#[derive(Debug, Clone)]
+pub struct Signv2Client {
+    endpoint: String,
+}
+
+impl Default for Signv2Client {
+    fn default() -> Self {
+        Self::new(DEFAULT_ENDPOINT)
+    }
+}
+
+impl Signv2Client {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Host part of the endpoint, which is where the sign server also lives in a default setup.
+    pub fn host(&self) -> &str {
+        let host = self
+            .endpoint
+            .split_once("://")
+            .map_or(self.endpoint.as_str(), |(_, rest)| rest);
+        let host = host.split('/').next().unwrap_or(host);
+        match host.rsplit_once(':') {
+            Some((name, port)) if port.parse::<u16>().is_ok() => name,
+            _ => host,
+        }
+    }
+
+    fn post<T: for<'de> Deserialize<'de>>(&self, action: &str, body: impl Serialize) -> Result<T> {
+        let url = format!("{}/{action}", self.endpoint);
+        let response = ureq::post(&url).send_json(body).map_err(|e| match e {
+            ureq::Error::Status(code, _) => Error::Server(format!("'{action}' returned {code}")),
+            e => Error::Server(e.to_string()),
+        })?;
+        response
+            .into_json()
+            .map_err(|e| Error::Server(format!("invalid '{action}' response: {e}")))
+    }
+
+    pub fn login(&self, username: &str, password: &str) -> Result<AuthData> {
+        self.post("login", Credentials { username, password })
+    }
+
+    pub fn register(&self, username: &str, password: &str) -> Result<AuthData> {
+        self.post("register", Credentials { username, password })
+    }
+
+    pub fn create_character(&self, token: &str) -> Result<Character> {
+        self.post("character/create", TokenRequest { token })
+    }
+}
+
+impl AuthData {
+    /// Builds the config to boot `character`. signv2server doesn't know where the sign server
+    /// lives, so `server_host` and `server_port` keep their defaults for the caller to fill.
+    pub fn to_config(
+        &self,
+        character: &Character,
+        char_new: bool,
+        user_name: &str,
+        user_password: &str,
+    ) -> MhfConfig {
+        let mez_fes = self.mez_fes.clone().unwrap_or_default();
+        let mut char_ids: Vec<u32> = self.characters.iter().map(|c| c.id).collect();
+        if !char_ids.contains(&character.id) {
+            char_ids.push(character.id);
+        }
+        MhfConfig {
+            char_id: character.id,
+            char_name: character.name.clone(),
+            char_gr: character.gr,
+            char_hr: character.hr,
+            char_ids,
+            char_new,
+            user_token_id: self.user.token_id,
+            user_token: self.user.token.clone(),
+            user_name: user_name.to_owned(),
+            user_password: user_password.to_owned(),
+            user_rights: Some(UserRights::from_bits_retain(self.user.rights)),
+            entrance_count: self.entrance_count,
+            current_ts: self.current_ts,
+            expiry_ts: self.expiry_ts,
+            notices: self
+                .notices
+                .iter()
+                .map(|data| Notice {
+                    flags: 0,
+                    data: data.clone(),
+                })
+                .collect(),
+            mez_event_id: mez_fes.id,
+            mez_start: mez_fes.start,
+            mez_end: mez_fes.end,
+            mez_solo_tickets: mez_fes.solo_tickets,
+            mez_group_tickets: mez_fes.group_tickets,
+            mez_stalls: mez_fes
+                .stalls
+                .iter()
+                .filter_map(|&s| MezFesStall::try_from(s).ok())
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+
+    // Answers a single request with `status` and `body`, returning the request line and body
+    fn serve(status: &str, body: &'static str) -> (Signv2Client, JoinHandle<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let status = status.to_owned();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut request = vec![0; length];
+            reader.read_exact(&mut request).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            let request_line = request_line.trim().to_owned();
+            (request_line, String::from_utf8(request).unwrap())
+        });
+        (Signv2Client::new(&endpoint), server)
+    }
+
+    const AUTH_DATA: &str = r#"{
+        "currentTs": 1700000000,
+        "expiryTs": 1700086400,
+        "entranceCount": 2,
+        "notices": ["<BODY>Welcome"],
+        "user": { "tokenId": 7, "token": "0123456789abcdef", "rights": 14 },
+        "characters": [
+            { "id": 11, "name": "Hunter", "isFemale": true, "weapon": 3, "hr": 999, "gr": 50, "lastLogin": 1 },
+            { "id": 12, "name": "Alt" }
+        ],
+        "mezFes": { "id": 5, "start": 1, "end": 2, "soloTickets": 3, "groupTickets": 4, "stalls": [10, 999] },
+        "patchServer": ""
+    }"#;
+
+    #[test]
+    fn login() {
+        let (client, server) = serve("200 OK", AUTH_DATA);
+        let auth = client.login("account", "hunter2").unwrap();
+        let (request_line, body) = server.join().unwrap();
+        assert_eq!(request_line, "POST /login HTTP/1.1");
+        assert_eq!(body, r#"{"username":"account","password":"hunter2"}"#);
+
+        assert_eq!(auth.user.token, "0123456789abcdef");
+        assert_eq!(auth.characters.len(), 2);
+        assert!(auth.characters[0].is_female);
+        assert_eq!(auth.characters[1].name, "Alt");
+
+        let config = auth.to_config(&auth.characters[1], false, "account", "hunter2");
+        assert_eq!(config.char_id, 12);
+        assert_eq!(config.char_ids, [11, 12]);
+        assert_eq!(config.user_token_id, 7);
+        assert_eq!(config.user_rights.map(|r| r.bits()), Some(14));
+        assert_eq!(config.expiry_ts, 1700086400);
+        assert_eq!(config.notices[0].data, "<BODY>Welcome");
+        assert_eq!(config.mez_event_id, 5);
+        // Stalls the game doesn't know are dropped
+        assert_eq!(config.mez_stalls.len(), 1);
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn bad_credentials() {
+        let (client, server) = serve("400 Bad Request", r#"{"error":"invalid password"}"#);
+        let e = client.login("account", "wrong").unwrap_err();
+        server.join().unwrap();
+        assert!(
+            matches!(&e, Error::Server(m) if m == "'login' returned 400"),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn malformed_body() {
+        let (client, server) = serve("200 OK", r#"{"user":"#);
+        let e = client.login("account", "hunter2").unwrap_err();
+        server.join().unwrap();
+        assert!(
+            matches!(&e, Error::Server(m) if m.starts_with("invalid 'login' response")),
+            "{e}"
+        );
+
+        let (client, server) = serve("200 OK", r#"{"user":{"token":7}}"#);
+        assert!(client.login("account", "hunter2").is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn create_character() {
+        let (client, server) = serve("200 OK", r#"{"id":13,"name":""}"#);
+        let character = client.create_character("0123456789abcdef").unwrap();
+        let (request_line, body) = server.join().unwrap();
+        assert_eq!(request_line, "POST /character/create HTTP/1.1");
+        assert_eq!(body, r#"{"token":"0123456789abcdef"}"#);
+        assert_eq!(character.id, 13);
+    }
+
+    #[test]
+    fn host() {
+        assert_eq!(Signv2Client::default().host(), "127.0.0.1");
+        assert_eq!(
+            Signv2Client::new("https://mhf.example/api/").host(),
+            "mhf.example"
+        );
+        assert_eq!(Signv2Client::new("mhf.example:8080").host(), "mhf.example");
+        assert_eq!(
+            Signv2Client::new("http://mhf.example/api/").endpoint(),
+            "http://mhf.example/api"
+        );
+    }
+}
@@ -0,0 +1,13 @@
+use std::sync::atomic::AtomicBool;
+
+use crate::{Error, Event, MhfConfig, Plan, Result};
+
+// Everything else still works, so other platforms can validate and plan configs
+pub fn run_mhf(
+    _config: MhfConfig,
+    _plan: Plan,
+    _on_event: &mut dyn FnMut(Event),
+    _cancelled: &AtomicBool,
+) -> Result<isize> {
+    Err(Error::Unsupported)
+}
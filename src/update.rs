@@ -0,0 +1,464 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{CliFlags, Error, Result};
+
+const PART_EXT: &str = "mhf-iel-part";
+const BACKUP_EXT: &str = "mhf-iel-bak";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    // Relative to the game folder, always using '/' as separator
+    pub path: String,
+    pub size: u64,
+    // Lowercase hex SHA-256
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub enum UpdateSource {
+    Http(String),
+    Local(PathBuf),
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct UpdateReport {
+    pub checked: usize,
+    pub updated: Vec<String>,
+    pub resumed: Vec<String>,
+}
+
+impl FromStr for UpdateSource {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(Self::Http(s.trim_end_matches('/').to_owned()))
+        } else if s.is_empty() {
+            Err(Error::Update("empty update source".to_owned()))
+        } else {
+            Ok(Self::Local(PathBuf::from(s)))
+        }
+    }
+}
+
+impl UpdateSource {
+    fn open(&self, path: &str, offset: u64) -> Result<(Box<dyn Read>, bool)> {
+        match self {
+            Self::Http(base) => {
+                let url = format!("{base}/{path}");
+                let mut request = ureq::get(&url);
+                if offset > 0 {
+                    request = request.set("Range", &format!("bytes={offset}-"));
+                }
+                let response = request
+                    .call()
+                    .map_err(|e| Error::Update(format!("unable to fetch '{url}': {e}")))?;
+                // Servers that ignore the range header answer with the whole file
+                let resumed = offset > 0 && response.status() == 206;
+                Ok((response.into_reader(), resumed))
+            }
+            Self::Local(base) => {
+                let source = base.join(path);
+                let mut file = File::open(&source).map_err(|e| {
+                    Error::Update(format!("unable to open '{}': {e}", source.display()))
+                })?;
+                let resumed = offset > 0 && file.seek(SeekFrom::Start(offset)).is_ok();
+                Ok((Box::new(file), resumed))
+            }
+        }
+    }
+
+    pub fn manifest(&self) -> Result<Manifest> {
+        let (reader, _) = self.open("manifest.json", 0)?;
+        Manifest::from_reader(reader)
+    }
+}
+
+impl Manifest {
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        let manifest: Manifest = serde_json::from_reader(reader)
+            .map_err(|e| Error::Update(format!("invalid manifest: {e}")))?;
+        if let Some(entry) = manifest.files.iter().find(|e| !is_safe_path(&e.path)) {
+            return Err(Error::Update(format!(
+                "manifest path '{}' escapes the game folder",
+                entry.path
+            )));
+        }
+        Ok(manifest)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| {
+            Error::Update(format!("unable to open manifest '{}': {e}", path.display()))
+        })?;
+        Self::from_reader(file)
+    }
+
+    /// Entries whose file in `mhf_folder` is missing, or differs in size or hash.
+    pub fn outdated(&self, mhf_folder: &Path) -> Result<Vec<&ManifestEntry>> {
+        let mut outdated = vec![];
+        for entry in &self.files {
+            let path = mhf_folder.join(&entry.path);
+            let up_to_date = match fs::metadata(&path) {
+                Ok(metadata) if metadata.len() == entry.size => {
+                    hash_file(&path)?.eq_ignore_ascii_case(&entry.sha256)
+                }
+                _ => false,
+            };
+            if !up_to_date {
+                outdated.push(entry);
+            }
+        }
+        Ok(outdated)
+    }
+}
+
+impl CliFlags {
+    pub fn requests_update(&self) -> bool {
+        matches!(self, Self::Selfup | Self::Restat | Self::DmmSelfup)
+    }
+}
+
+fn is_safe_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn with_ext(path: &Path, ext: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(ext);
+    PathBuf::from(name)
+}
+
+pub(crate) fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .map_err(|e| Error::Update(format!("unable to read '{}': {e}", path.display())))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| Error::Update(format!("unable to read '{}': {e}", path.display())))?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fetched {
+    Transferred,
+    Resumed,
+    /// The part file was already complete, so nothing was transferred
+    Complete,
+}
+
+// Downloads or copies `entry` into its part file, resuming from whatever was already transferred.
+fn fetch(source: &UpdateSource, entry: &ManifestEntry, part: &Path) -> Result<Fetched> {
+    let io_err =
+        |e: std::io::Error| Error::Update(format!("unable to write '{}': {e}", part.display()));
+
+    if let Some(parent) = part.parent() {
+        fs::create_dir_all(parent).map_err(io_err)?;
+    }
+    let offset = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+    if offset == entry.size && hash_file(part)?.eq_ignore_ascii_case(&entry.sha256) {
+        return Ok(Fetched::Complete);
+    }
+    let offset = if offset < entry.size { offset } else { 0 };
+    let (mut reader, resumed) = source.open(&entry.path, offset)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part)
+        .map_err(io_err)?;
+    let remaining = if resumed {
+        entry.size - offset
+    } else {
+        entry.size
+    };
+    std::io::copy(&mut reader.by_ref().take(remaining), &mut file).map_err(io_err)?;
+    file.flush().map_err(io_err)?;
+    drop(file);
+
+    let size = fs::metadata(part).map_err(io_err)?.len();
+    if size != entry.size || !hash_file(part)?.eq_ignore_ascii_case(&entry.sha256) {
+        // A corrupted part file can't be resumed from, so start over next time
+        let _ = fs::remove_file(part);
+        return Err(Error::Update(format!(
+            "checksum mismatch for '{}'",
+            entry.path
+        )));
+    }
+    match resumed {
+        true => Ok(Fetched::Resumed),
+        false => Ok(Fetched::Transferred),
+    }
+}
+
+// Moves every part file over its target, backing up the old files so that a failure halfway
+// through leaves the game folder as it was.
+fn commit(mhf_folder: &Path, entries: &[&ManifestEntry]) -> Result<()> {
+    let mut done: Vec<(PathBuf, bool)> = vec![];
+    let mut result = Ok(());
+    for entry in entries {
+        let target = mhf_folder.join(&entry.path);
+        let part = with_ext(&target, PART_EXT);
+        let backup = with_ext(&target, BACKUP_EXT);
+        let existed = target.exists();
+        if existed {
+            if let Err(e) = fs::rename(&target, &backup) {
+                result = Err(Error::Update(format!(
+                    "unable to back up '{}': {e}",
+                    target.display()
+                )));
+                break;
+            }
+        }
+        if let Err(e) = fs::rename(&part, &target) {
+            if existed {
+                let _ = fs::rename(&backup, &target);
+            }
+            result = Err(Error::Update(format!(
+                "unable to replace '{}': {e}",
+                target.display()
+            )));
+            break;
+        }
+        done.push((target, existed));
+    }
+
+    for (target, existed) in &done {
+        let backup = with_ext(target, BACKUP_EXT);
+        if result.is_ok() {
+            let _ = fs::remove_file(backup);
+        } else if *existed {
+            let _ = fs::rename(backup, target);
+        } else {
+            let _ = fs::remove_file(target);
+        }
+    }
+    result
+}
+
+/// Brings `mhf_folder` in line with `manifest`, fetching only the files that differ.
+///
+/// Files are first transferred next to their target with a `.mhf-iel-part` extension, so an
+/// interrupted update resumes where it stopped, and only replace the originals once every file
+/// was fetched and verified.
+pub fn update(
+    manifest: &Manifest,
+    source: &UpdateSource,
+    mhf_folder: &Path,
+) -> Result<UpdateReport> {
+    let outdated = manifest.outdated(mhf_folder)?;
+    let mut report = UpdateReport {
+        checked: manifest.files.len(),
+        ..Default::default()
+    };
+    for entry in &outdated {
+        let part = with_ext(&mhf_folder.join(&entry.path), PART_EXT);
+        if fetch(source, entry, &part)? == Fetched::Resumed {
+            report.resumed.push(entry.path.clone());
+        }
+    }
+    commit(mhf_folder, &outdated)?;
+    report.updated = outdated.into_iter().map(|e| e.path.clone()).collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("mhf-iel-update-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        Sha256::digest(data)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    // A mirror with `files`, and an empty game folder
+    fn fixture(name: &str, files: &[(&str, &[u8])]) -> (TempDir, Manifest, UpdateSource, PathBuf) {
+        let dir = TempDir::new(name);
+        let mirror = dir.0.join("mirror");
+        let mhf_folder = dir.0.join("mhf");
+        fs::create_dir_all(&mhf_folder).unwrap();
+        let mut manifest = Manifest::default();
+        for (path, data) in files {
+            let file = mirror.join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, data).unwrap();
+            manifest.files.push(ManifestEntry {
+                path: path.to_string(),
+                size: data.len() as u64,
+                sha256: sha256(data),
+            });
+        }
+        (dir, manifest, UpdateSource::Local(mirror), mhf_folder)
+    }
+
+    fn leftovers(dir: &Path) -> Vec<PathBuf> {
+        let mut found = vec![];
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                found.extend(leftovers(&path));
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext == PART_EXT || ext == BACKUP_EXT)
+            {
+                found.push(path);
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn updates_only_what_differs() {
+        let files: &[(&str, &[u8])] = &[("mhf.exe", b"exe"), ("dat/emote.bin", b"emote")];
+        let (_dir, manifest, source, mhf_folder) = fixture("differs", files);
+        fs::write(mhf_folder.join("mhf.exe"), b"exe").unwrap();
+
+        let report = update(&manifest, &source, &mhf_folder).unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.updated, ["dat/emote.bin"]);
+        assert!(report.resumed.is_empty());
+        assert_eq!(
+            fs::read(mhf_folder.join("dat/emote.bin")).unwrap(),
+            b"emote"
+        );
+
+        fs::write(mhf_folder.join("mhf.exe"), b"old").unwrap();
+        let report = update(&manifest, &source, &mhf_folder).unwrap();
+        assert_eq!(report.updated, ["mhf.exe"]);
+        assert_eq!(fs::read(mhf_folder.join("mhf.exe")).unwrap(), b"exe");
+        assert!(manifest.outdated(&mhf_folder).unwrap().is_empty());
+        assert!(leftovers(&mhf_folder).is_empty());
+    }
+
+    #[test]
+    fn resumes_part_files() {
+        let (_dir, manifest, source, mhf_folder) = fixture("resume", &[("a.dat", b"0123456789")]);
+        fs::write(mhf_folder.join("a.dat.mhf-iel-part"), b"0123").unwrap();
+        let report = update(&manifest, &source, &mhf_folder).unwrap();
+        assert_eq!(report.updated, ["a.dat"]);
+        assert_eq!(report.resumed, ["a.dat"]);
+        assert_eq!(fs::read(mhf_folder.join("a.dat")).unwrap(), b"0123456789");
+        assert!(leftovers(&mhf_folder).is_empty());
+    }
+
+    #[test]
+    fn complete_part_files_arent_resumed() {
+        let (_dir, manifest, source, mhf_folder) = fixture("complete", &[("a.dat", b"0123")]);
+        fs::write(mhf_folder.join("a.dat.mhf-iel-part"), b"0123").unwrap();
+        let report = update(&manifest, &source, &mhf_folder).unwrap();
+        assert_eq!(report.updated, ["a.dat"]);
+        assert!(report.resumed.is_empty());
+        assert_eq!(fs::read(mhf_folder.join("a.dat")).unwrap(), b"0123");
+    }
+
+    #[test]
+    fn restarts_bad_part_files() {
+        let (_dir, manifest, source, mhf_folder) = fixture("restart", &[("a.dat", b"0123")]);
+        // Longer than the file, so it can't be resumed from
+        fs::write(mhf_folder.join("a.dat.mhf-iel-part"), b"012345").unwrap();
+        let report = update(&manifest, &source, &mhf_folder).unwrap();
+        assert!(report.resumed.is_empty());
+        assert_eq!(fs::read(mhf_folder.join("a.dat")).unwrap(), b"0123");
+
+        // Resumed from, but with the wrong start
+        fs::write(mhf_folder.join("a.dat"), b"old").unwrap();
+        fs::write(mhf_folder.join("a.dat.mhf-iel-part"), b"xx").unwrap();
+        assert!(update(&manifest, &source, &mhf_folder).is_err());
+        assert_eq!(fs::read(mhf_folder.join("a.dat")).unwrap(), b"old");
+        assert!(leftovers(&mhf_folder).is_empty());
+        update(&manifest, &source, &mhf_folder).unwrap();
+        assert_eq!(fs::read(mhf_folder.join("a.dat")).unwrap(), b"0123");
+    }
+
+    #[test]
+    fn checksum_mismatches_change_nothing() {
+        let files: &[(&str, &[u8])] = &[("a.dat", b"new a"), ("b.dat", b"new b")];
+        let (_dir, mut manifest, source, mhf_folder) = fixture("mismatch", files);
+        manifest.files[1].sha256 = sha256(b"something else");
+        fs::write(mhf_folder.join("a.dat"), b"old a").unwrap();
+        let e = update(&manifest, &source, &mhf_folder).unwrap_err();
+        assert!(
+            e.to_string().contains("checksum mismatch for 'b.dat'"),
+            "{e}"
+        );
+        assert_eq!(fs::read(mhf_folder.join("a.dat")).unwrap(), b"old a");
+        assert!(!mhf_folder.join("b.dat").exists());
+    }
+
+    #[test]
+    fn commit_rolls_back() {
+        let files: &[(&str, &[u8])] = &[("a.dat", b"a"), ("new.dat", b"new"), ("b.dat", b"b")];
+        let (_dir, manifest, _, mhf_folder) = fixture("rollback", files);
+        fs::write(mhf_folder.join("a.dat"), b"old a").unwrap();
+        fs::write(mhf_folder.join("a.dat.mhf-iel-part"), b"a").unwrap();
+        fs::write(mhf_folder.join("new.dat.mhf-iel-part"), b"new").unwrap();
+        fs::write(mhf_folder.join("b.dat"), b"old b").unwrap();
+        // b.dat has no part file, so replacing it fails after the others were moved
+        let entries: Vec<&ManifestEntry> = manifest.files.iter().collect();
+        assert!(commit(&mhf_folder, &entries).is_err());
+        assert_eq!(fs::read(mhf_folder.join("a.dat")).unwrap(), b"old a");
+        assert_eq!(fs::read(mhf_folder.join("b.dat")).unwrap(), b"old b");
+        assert!(!mhf_folder.join("new.dat").exists());
+        assert!(!mhf_folder.join("a.dat.mhf-iel-bak").exists());
+        assert!(!mhf_folder.join("b.dat.mhf-iel-bak").exists());
+    }
+
+    #[test]
+    fn paths_stay_in_the_game_folder() {
+        for path in ["mhf.exe", "dat/emote.bin", "a/./b"] {
+            assert!(is_safe_path(path), "{path}");
+        }
+        for path in [
+            "",
+            "..",
+            "../mhf.exe",
+            "dat/../../mhf.exe",
+            "/etc/passwd",
+            "./",
+        ] {
+            assert!(!is_safe_path(path), "{path}");
+        }
+        let manifest = r#"{ "files": [{ "path": "../x", "size": 0, "sha256": "" }] }"#;
+        let e = Manifest::from_reader(manifest.as_bytes()).unwrap_err();
+        assert!(e.to_string().contains("escapes the game folder"), "{e}");
+    }
+}
@@ -9,13 +9,9 @@ use windows::{
     },
 };
 
+use crate::block::GLOBAL_ALLOC_SIZE;
 use crate::{Error, Result};
 
-pub fn bufcopy<T: Copy>(s: &mut [T], v: &[T]) {
-    let l = s.len().min(v.len());
-    s[..l].copy_from_slice(&v[..l])
-}
-
 pub fn get_mutex_name(s: &str) -> String {
     let pid = std::process::id();
     // F5 uses 'Monster Hunter Frontier Online', but it's probably fine
@@ -37,7 +33,7 @@ pub fn get_or_create_mutex(name: impl Into<HSTRING> + Copy) -> Result<HANDLE> {
 // }
 
 pub fn create_global_alloc() -> Result<HGLOBAL> {
-    unsafe { GlobalAlloc(GLOBAL_ALLOC_FLAGS(0x42), 0x8ae0) }.or(Err(Error::GlobalAlloc))
+    unsafe { GlobalAlloc(GLOBAL_ALLOC_FLAGS(0x42), GLOBAL_ALLOC_SIZE) }.or(Err(Error::GlobalAlloc))
 }
 
 pub fn release_global_alloc(handle: HGLOBAL) -> Result<HGLOBAL> {
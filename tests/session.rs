@@ -0,0 +1,53 @@
+#![cfg(unix)]
+//! `launch_detached_with` under a policy, in its own process since the policy is global.
+
+use std::os::unix::fs::PermissionsExt;
+
+use mhf_iel::policy::{set_policy, AllowedServer, Policy};
+use mhf_iel::session::launch_detached_with;
+use mhf_iel::MhfConfig;
+
+// Keeps the policy it's given and exits like a game that ran fine
+const STUB: &str = r#"#!/bin/sh
+printf '%s' "$MHF_IEL_POLICY" > "$(dirname "$0")/policy"
+cat > /dev/null
+printf '%s\n' '{"ok":true,"result":{"update":null,"game_code":0}}'
+"#;
+
+fn config(server_host: &str) -> MhfConfig {
+    MhfConfig {
+        char_id: 1,
+        user_token: "0123456789abcdef".to_owned(),
+        server_host: server_host.to_owned(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn checks_and_passes_on_the_policy() {
+    let dir = std::env::temp_dir().join(format!("mhf-iel-session-policy-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let cli = dir.join("cli");
+    std::fs::write(&cli, STUB).unwrap();
+    std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let policy = Policy {
+        allowed_servers: Some(vec![AllowedServer {
+            host: "10.0.0.2".to_owned(),
+            port: Some(53312),
+        }]),
+        trusted_keys: vec![],
+    };
+    set_policy(policy.clone());
+    assert!(launch_detached_with(&cli, config("10.0.0.9")).is_err());
+    // Refused before the CLI ever ran
+    assert!(!dir.join("policy").exists());
+
+    let mut session = launch_detached_with(&cli, config("10.0.0.2")).unwrap();
+    session.wait().unwrap();
+    let passed: Policy =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("policy")).unwrap()).unwrap();
+    assert_eq!(passed, policy);
+    std::fs::remove_dir_all(dir).unwrap();
+}